@@ -1,12 +1,18 @@
 use clap::Parser;
 use js_watcher::{
+  entry::ResolveAlias,
+  export::{GraphEdge, GraphFormat},
+  file_item::ImportKind,
   path_clean::PathClean,
-  watch_info::WatchInfo,
-  watcher::{SetupOptions, Watcher},
+  rules::{load_rules, BoundaryRule},
+  snapshot::{is_valid_snapshot_name, GraphDiff},
+  watcher::{
+    EntryChange, ExplainDependencyOptions, GraphOptions, SetupOptions, UnreachableOptions, Watcher,
+  },
 };
 use owo_colors::OwoColorize;
 use serde_json::json;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::process::Command;
 
 #[derive(Parser, Debug)]
@@ -26,21 +32,517 @@ struct Cli {
   /// suppress output from js_watcher
   #[clap(short, long)]
   silent: bool,
+  /// resolve alias, as `find=path` or `find=path1,path2`. Paths are relative to project's path
+  #[clap(short, long("alias"), value_parser = parse_alias)]
+  aliases: Vec<ResolveAlias>,
+  /// rules restricting what files may import, as a JSON file relative to project's path. New
+  /// violations are reported as changes
+  #[clap(long, global = true)]
+  rules: Option<String>,
+  #[clap(subcommand)]
+  command: Option<Commands>,
 }
 
-fn serialize_watch_info(info: &WatchInfo) -> String {
-  let json = json!({
-    "event": info.event_to_string(),
-    "affectedFile": info.affected_file,
-    "affectedEntries": info.affected_entries.as_ref().unwrap_or(&vec![]).iter().map(|item| json!({
-      "path": item.path.to_str().unwrap(),
-      "deps": item.deps
-    })).collect::<serde_json::Value>()
-  });
+#[derive(clap::Subcommand, Debug)]
+enum Commands {
+  /// explain how an import is resolved, step by step
+  Explain {
+    /// file containing the import, relative to project's path
+    importer: String,
+    /// the import, as written in the source
+    specifier: String,
+    /// the kind of import, which picks the `import` or `require` condition of `exports`
+    #[clap(short, long, default_value = "esm", possible_values = &["esm", "require", "css"])]
+    kind: String,
+  },
+  /// show the chains of imports through which an entry depends on a file
+  Why {
+    /// the entry, relative to project's path
+    entry: String,
+    /// the dependency, relative to project's path
+    file: String,
+    /// how many chains to show at most, the shortest first
+    #[clap(short('n'), long, default_value_t = 1)]
+    max_paths: u32,
+  },
+  /// print the dependency graph of the entries
+  #[clap(args_conflicts_with_subcommands = true)]
+  Graph {
+    #[clap(subcommand)]
+    command: Option<GraphCommands>,
+    /// paths or globs of entries, relative to project's path
+    entries: Vec<String>,
+    #[clap(short, long, default_value = "json", possible_values = &["json", "dot", "mermaid"])]
+    format: String,
+    /// only the graph of this entry, relative to project's path
+    #[clap(short, long)]
+    entry: Option<String>,
+    /// how many imports away from the entries files are kept
+    #[clap(short('d'), long)]
+    max_depth: Option<u32>,
+    /// show each package of node_modules as a single node
+    #[clap(short, long)]
+    collapse_packages: bool,
+  },
+  /// list the files no entry depends on, and fail when there are some
+  Unreachable {
+    /// paths or globs of the files to check, relative to project's path
+    globs: Vec<String>,
+    /// paths or globs of entries, relative to project's path
+    #[clap(short, long("entry"), required = true)]
+    entries: Vec<String>,
+    /// globs of files that are never reported, relative to project's path
+    #[clap(short, long)]
+    ignore: Vec<String>,
+    /// test files are entries too
+    #[clap(short, long)]
+    tests_as_roots: bool,
+  },
+  /// list the circular imports between the files of the entries, and fail when there are some
+  Cycles {
+    /// paths or globs of entries, relative to project's path
+    entries: Vec<String>,
+  },
+  /// check the imports of the entries and of their deps against the rules, and fail when some
+  /// break them
+  Check {
+    /// paths or globs of entries, relative to project's path
+    entries: Vec<String>,
+  },
+  /// list the exports no file imports, and fail when there are some. Entries' exports aren't
+  /// checked
+  UnusedExports {
+    /// paths or globs of entries, relative to project's path
+    entries: Vec<String>,
+  },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum GraphCommands {
+  /// save the graph of the entries in the cache directory, to compare it later
+  Snapshot {
+    /// name of the snapshot
+    #[clap(value_parser = parse_snapshot_name)]
+    name: String,
+    /// paths or globs of entries, relative to project's path
+    entries: Vec<String>,
+  },
+  /// show what changed between two snapshots
+  Diff {
+    #[clap(value_parser = parse_snapshot_name)]
+    from: String,
+    #[clap(value_parser = parse_snapshot_name)]
+    to: String,
+    /// print the changes as JSON
+    #[clap(long)]
+    json: bool,
+  },
+}
+
+fn serialize_changes(changes: &[EntryChange]) -> String {
+  let json = changes
+    .iter()
+    .map(|change| {
+      json!({
+        "changeType": format!("{:?}", change.change_type),
+        "entry": change.entry,
+        "cause": change.cause.as_ref().map(|cause| json!({
+          "file": cause.file,
+          "state": format!("{:?}", cause.state),
+        })),
+        "tree": change.tree,
+        "cycle": change.cycle,
+        "violation": change.violation.as_ref().map(|violation| json!({
+          "kind": format!("{:?}", violation.kind),
+          "rule": violation.rule,
+          "importer": violation.importer,
+          "imported": violation.imported,
+          "specifier": violation.specifier,
+          "line": violation.span.map(|x| x.line),
+        })),
+        "diagnostic": change.diagnostic.as_ref().map(|diagnostic| json!({
+          "kind": format!("{:?}", diagnostic.kind),
+          "specifier": diagnostic.specifier,
+          "importer": diagnostic.importer,
+          "line": diagnostic.span.map(|x| x.line),
+          "message": diagnostic.message,
+        })),
+      })
+    })
+    .collect::<serde_json::Value>();
 
   json.to_string()
 }
 
+fn parse_alias(input: &str) -> Result<ResolveAlias, String> {
+  let (find, replacements) = input
+    .split_once('=')
+    .ok_or_else(|| format!("invalid alias \"{}\", expected find=path", input))?;
+  Ok(ResolveAlias {
+    find: find.to_string(),
+    replacements: replacements.split(',').map(String::from).collect(),
+  })
+}
+
+fn parse_snapshot_name(input: &str) -> Result<String, String> {
+  if !is_valid_snapshot_name(input) {
+    return Err(format!(
+      "invalid snapshot name \"{}\", it can't hold path separators or \"..\"",
+      input
+    ));
+  }
+  Ok(input.to_string())
+}
+
+fn explain_resolve(
+  watcher: &Watcher,
+  project_root: &str,
+  importer: &str,
+  specifier: &str,
+  kind: &str,
+) {
+  let kind = match kind {
+    "require" => ImportKind::Require,
+    "css" => ImportKind::Css,
+    _ => ImportKind::Esm,
+  };
+  let trace = watcher.explain_resolve(importer.to_string(), specifier.to_string(), Some(kind));
+  println!(
+    "{} Resolving \"{}\" from {}",
+    "!".blue(),
+    trace.specifier.blue().bold(),
+    trace.importer.replace(project_root, ".").blue()
+  );
+  for step in &trace.steps {
+    let kind = format!("{:?}", step.kind);
+    if step.matched {
+      println!(
+        "  {} {}: {}",
+        "+".green(),
+        kind.green(),
+        step.message.replace(project_root, ".")
+      );
+    } else {
+      println!(
+        "  {} {}: {}",
+        "-".dimmed(),
+        kind.dimmed(),
+        step.message.replace(project_root, ".")
+      );
+    }
+    for candidate in &step.candidates {
+      println!("      {}", candidate.replace(project_root, ".").dimmed());
+    }
+  }
+  if let Some(resolved) = &trace.resolved {
+    println!(
+      "{} Resolved to {}",
+      "!".green().bold(),
+      resolved.replace(project_root, ".").green()
+    );
+  } else if let Some(external) = &trace.external {
+    println!(
+      "{} External dependency ({:?}), it isn't watched",
+      "!".blue().bold(),
+      external.kind
+    );
+  } else if let Some(error) = &trace.error {
+    println!("{} Unresolved: {}", "!".red().bold(), error);
+  } else {
+    println!("{} Ignored through the `browser` field", "!".blue().bold());
+  }
+}
+
+fn explain_dependency(watcher: &Watcher, project_root: &str, entry: &str, file: &str, max: u32) {
+  let chains = watcher.explain_dependency(
+    entry.to_string(),
+    file.to_string(),
+    Some(ExplainDependencyOptions {
+      max_paths: Some(max),
+    }),
+  );
+  if chains.is_empty() {
+    println!(
+      "{} {} doesn't depend on {}",
+      "!".yellow().bold(),
+      entry.blue(),
+      file.blue()
+    );
+    return;
+  }
+  for chain in &chains {
+    println!(
+      "{} {} depends on {} through",
+      "!".green().bold(),
+      entry.blue(),
+      file.blue()
+    );
+    for step in chain {
+      println!(
+        "  {}{} {}",
+        step.importer.replace(project_root, "."),
+        step
+          .span
+          .map(|x| format!(":{}", x.line))
+          .unwrap_or_default()
+          .dimmed(),
+        format!("\"{}\"", step.specifier.clone().unwrap_or_default()).dimmed()
+      );
+    }
+    if let Some(step) = chain.last() {
+      println!("  {}", step.file.replace(project_root, ".").green());
+    }
+  }
+}
+
+fn run_graph_command(command: &GraphCommands, cli: &Cli, project_root: &str) {
+  let setup = |entries: &[String]| setup_watcher(cli, project_root, entries);
+  match command {
+    GraphCommands::Snapshot { name, entries } => {
+      let path = match setup(entries).save_snapshot(name.to_string()) {
+        Ok(path) => path,
+        Err(err) => {
+          println!("{} {}", "!".red().bold(), err.reason);
+          std::process::exit(1);
+        }
+      };
+      println!(
+        "{} Saved snapshot {} at {}",
+        "!".green().bold(),
+        name.blue().bold(),
+        path.replace(project_root, ".")
+      );
+    }
+    GraphCommands::Diff { from, to, json } => {
+      let diff = match setup(&[]).diff_graphs(from.to_string(), to.to_string()) {
+        Some(diff) => diff,
+        None => {
+          println!(
+            "{} Couldn't read the snapshots {} and {}",
+            "!".red().bold(),
+            from,
+            to
+          );
+          std::process::exit(1);
+        }
+      };
+      if *json {
+        println!("{}", serialize_graph_diff(&diff));
+      } else {
+        print_graph_diff(&diff, project_root);
+      }
+    }
+  }
+}
+
+fn serialize_graph_diff(diff: &GraphDiff) -> String {
+  let edges = |edges: &[GraphEdge]| -> serde_json::Value {
+    edges
+      .iter()
+      .map(|edge| json!({ "from": edge.from, "to": edge.to }))
+      .collect()
+  };
+  json!({
+    "addedEdges": edges(&diff.added_edges),
+    "removedEdges": edges(&diff.removed_edges),
+    "addedEntries": diff.added_entries,
+    "removedEntries": diff.removed_entries,
+    "entries": diff.entries.iter().map(|entry| json!({
+      "entry": entry.entry,
+      "addedNodes": entry.added_nodes,
+      "removedNodes": entry.removed_nodes,
+      "addedPackages": entry.added_packages,
+      "removedPackages": entry.removed_packages,
+    })).collect::<serde_json::Value>(),
+  })
+  .to_string()
+}
+
+fn print_graph_diff(diff: &GraphDiff, project_root: &str) {
+  let relative = |x: &str| x.replace(project_root, ".");
+  if diff.added_edges.is_empty()
+    && diff.removed_edges.is_empty()
+    && diff.added_entries.is_empty()
+    && diff.removed_entries.is_empty()
+  {
+    println!("{} The graphs are the same", "!".green().bold());
+    return;
+  }
+  for entry in &diff.added_entries {
+    println!("{} New entry {}", "+".green(), relative(entry).green());
+  }
+  for entry in &diff.removed_entries {
+    println!("{} Removed entry {}", "-".red(), relative(entry).red());
+  }
+  for entry in &diff.entries {
+    println!("{} {}", "!".blue(), relative(&entry.entry).blue().bold());
+    for package in &entry.added_packages {
+      println!("  {} package {}", "+".green(), package.green().bold());
+    }
+    for package in &entry.removed_packages {
+      println!("  {} package {}", "-".red(), package.red().bold());
+    }
+    for node in &entry.added_nodes {
+      println!("  {} {}", "+".green(), relative(node).green());
+    }
+    for node in &entry.removed_nodes {
+      println!("  {} {}", "-".red(), relative(node).red());
+    }
+  }
+  for edge in &diff.added_edges {
+    println!(
+      "{} {} -> {}",
+      "+".green(),
+      relative(&edge.from),
+      relative(&edge.to)
+    );
+  }
+  for edge in &diff.removed_edges {
+    println!(
+      "{} {} -> {}",
+      "-".red(),
+      relative(&edge.from),
+      relative(&edge.to)
+    );
+  }
+}
+
+fn print_cycles(watcher: &Watcher, project_root: &str) -> bool {
+  let cycles = watcher.get_cycles();
+  if cycles.is_empty() {
+    println!("{} No circular imports", "!".green().bold());
+    return false;
+  }
+  for cycle in &cycles {
+    println!("{} Circular import between", "!".yellow().bold());
+    for file in cycle {
+      println!("  {}", file.replace(project_root, ".").yellow());
+    }
+  }
+  true
+}
+
+/// Reads the rules file given with `--rules`, exits with code 2 when it can't be read.
+fn read_rules(path: &Option<String>, project_root: &str) -> Option<Vec<BoundaryRule>> {
+  let path = Path::new(project_root).join(path.as_ref()?);
+  match load_rules(&path) {
+    Ok(rules) => Some(rules),
+    Err(err) => {
+      eprintln!(
+        "{} Couldn't read rules from {}: {}",
+        "!".red().bold(),
+        path.to_str().unwrap(),
+        err
+      );
+      std::process::exit(2);
+    }
+  }
+}
+
+/// Sets up a watcher of the entries matching `globs` with the options of the command line.
+fn setup_watcher(cli: &Cli, project_root: &str, globs: &[String]) -> Watcher {
+  Watcher::setup(SetupOptions {
+    project: "test".into(),
+    project_root: project_root.to_string(),
+    glob_entries: Some(globs.to_vec()),
+    aliases: Some(cli.aliases.clone()),
+    rules: read_rules(&cli.rules, project_root),
+    ..Default::default()
+  })
+  .unwrap_or_else(|err| {
+    eprintln!("{} {}", "!".red().bold(), err.reason);
+    std::process::exit(2);
+  })
+}
+
+fn print_graph(watcher: &Watcher, format: &str, opts: GraphOptions) {
+  let format = match format {
+    "dot" => GraphFormat::Dot,
+    "mermaid" => GraphFormat::Mermaid,
+    _ => GraphFormat::Json,
+  };
+  print!("{}", watcher.export_graph(format, Some(opts)));
+}
+
+fn print_unreachable(
+  watcher: &Watcher,
+  project_root: &str,
+  globs: &[String],
+  opts: UnreachableOptions,
+) -> bool {
+  let unreachable = match watcher.get_unreachable(globs.to_vec(), Some(opts)) {
+    Ok(unreachable) => unreachable,
+    Err(err) => {
+      eprintln!("{} {}", "!".red().bold(), err.reason);
+      std::process::exit(2);
+    }
+  };
+  if unreachable.is_empty() {
+    println!("{} Every file is reachable", "!".green().bold());
+    return false;
+  }
+  println!(
+    "{} {} files aren't reachable from any entry",
+    "!".yellow().bold(),
+    unreachable.len().yellow()
+  );
+  for file in &unreachable {
+    println!("  {}", file.replace(project_root, ".").yellow());
+  }
+  true
+}
+
+fn print_violations(watcher: &Watcher, project_root: &str) -> bool {
+  let violations = watcher.check_rules();
+  if violations.is_empty() {
+    println!("{} Every import follows the rules", "!".green().bold());
+    return false;
+  }
+  println!(
+    "{} {} imports break the rules",
+    "!".yellow().bold(),
+    violations.len().yellow()
+  );
+  for violation in &violations {
+    println!(
+      "  {}{} {} {} ({:?}, {})",
+      violation.importer.replace(project_root, "."),
+      violation
+        .span
+        .map(|x| format!(":{}", x.line))
+        .unwrap_or_default(),
+      "->".dimmed(),
+      violation.imported.replace(project_root, ".").yellow(),
+      violation.kind,
+      violation.rule
+    );
+  }
+  true
+}
+
+fn print_unused_exports(watcher: &Watcher, project_root: &str) -> bool {
+  let unused = watcher.get_unused_exports();
+  if unused.is_empty() {
+    println!("{} Every export is used", "!".green().bold());
+    return false;
+  }
+  println!(
+    "{} {} exports aren't imported by any file",
+    "!".yellow().bold(),
+    unused.len().yellow()
+  );
+  for export in &unused {
+    println!(
+      "  {}:{}:{} {}",
+      export.file.replace(project_root, "."),
+      export.span.line,
+      export.span.column,
+      export.name.yellow()
+    );
+  }
+  true
+}
+
 fn main() {
   let cli = Cli::parse();
 
@@ -48,14 +550,114 @@ fn main() {
   let project_root = if project_path.is_absolute() {
     project_path.to_str().unwrap().to_string()
   } else {
-    PathBuf::from(std::env::current_dir().unwrap())
+    std::env::current_dir()
+      .unwrap()
       .join(project_path)
       .clean()
       .to_str()
       .unwrap()
       .to_string()
   };
-  println!("{} Project: {}", "!".blue(), project_root.blue().bold());
+  // the watcher reports real paths, they're printed relative to the real root
+  let project_root = Path::new(&project_root)
+    .canonicalize()
+    .ok()
+    .and_then(|x| x.to_str().map(String::from))
+    .unwrap_or(project_root);
+  // the graph is printed alone, so it can be piped
+  if !matches!(cli.command, Some(Commands::Graph { .. })) {
+    println!("{} Project: {}", "!".blue(), project_root.blue().bold());
+  }
+  let require_entries = |entries: &[String]| {
+    if entries.is_empty() {
+      panic!("No entry was specified");
+    }
+  };
+  let failed = match &cli.command {
+    Some(Commands::Graph {
+      command: Some(command),
+      ..
+    }) => {
+      run_graph_command(command, &cli, &project_root);
+      false
+    }
+    Some(Commands::Graph {
+      command: None,
+      entries,
+      format,
+      entry,
+      max_depth,
+      collapse_packages,
+    }) => {
+      let watcher = setup_watcher(&cli, &project_root, entries);
+      let opts = GraphOptions {
+        entry: entry.clone(),
+        max_depth: *max_depth,
+        collapse_packages: Some(*collapse_packages),
+      };
+      print_graph(&watcher, format, opts);
+      false
+    }
+    Some(Commands::Explain {
+      importer,
+      specifier,
+      kind,
+    }) => {
+      let watcher = setup_watcher(&cli, &project_root, &[]);
+      explain_resolve(&watcher, &project_root, importer, specifier, kind);
+      false
+    }
+    Some(Commands::Why {
+      entry,
+      file,
+      max_paths,
+    }) => {
+      let watcher = setup_watcher(&cli, &project_root, std::slice::from_ref(entry));
+      explain_dependency(&watcher, &project_root, entry, file, *max_paths);
+      false
+    }
+    Some(Commands::Unreachable {
+      globs,
+      entries,
+      ignore,
+      tests_as_roots,
+    }) => {
+      let watcher = setup_watcher(&cli, &project_root, entries);
+      let opts = UnreachableOptions {
+        ignore: Some(ignore.clone()),
+        tests_as_roots: Some(*tests_as_roots),
+        test_patterns: None,
+      };
+      print_unreachable(&watcher, &project_root, globs, opts)
+    }
+    Some(Commands::Cycles { entries }) => {
+      require_entries(entries);
+      print_cycles(&setup_watcher(&cli, &project_root, entries), &project_root)
+    }
+    Some(Commands::Check { entries }) => {
+      require_entries(entries);
+      if cli.rules.is_none() {
+        eprintln!("{} No rules were specified, see --rules", "!".red().bold());
+        std::process::exit(2);
+      }
+      print_violations(&setup_watcher(&cli, &project_root, entries), &project_root)
+    }
+    Some(Commands::UnusedExports { entries }) => {
+      require_entries(entries);
+      print_unused_exports(&setup_watcher(&cli, &project_root, entries), &project_root)
+    }
+    None => {
+      watch(cli, project_root);
+      false
+    }
+  };
+  if failed {
+    std::process::exit(1);
+  }
+}
+
+/// Watches the entries, running the commands of `--run` and `--exec` on changes, until Ctrl-C.
+fn watch(cli: Cli, project_root: String) {
   let entries_input = match &cli.run {
     Some(wexec) => wexec
       .split_ascii_whitespace()
@@ -74,16 +676,8 @@ fn main() {
     println!("{} No paths to watch", "!".yellow().bold());
   }
 
-  let mut watcher = Watcher::setup(SetupOptions {
-    cache_dir: None,
-    debug: None,
-    entries: None,
-    glob_entries: Some(entries_input),
-    project: "test".into(),
-    project_root: project_root.clone(),
-    supported_paths: None,
-  });
-  let entries = watcher.get_entries();
+  let mut watcher = setup_watcher(&cli, &project_root, &entries_input);
+  let entries = watcher.get_entries(None);
   if !entries.is_empty() {
     let mut message = format!("{} Watching for\n", "!".blue());
     for entry in entries.iter().take(4) {
@@ -116,14 +710,28 @@ fn main() {
     );
   }
 
+  for diagnostic in watcher.get_diagnostics() {
+    println!(
+      "{} {}{} \"{}\": {}",
+      "!".yellow().bold(),
+      diagnostic.importer.replace(project_root.as_str(), "."),
+      diagnostic
+        .span
+        .map(|x| format!(":{}", x.line))
+        .unwrap_or_default(),
+      diagnostic.specifier,
+      diagnostic.message
+    );
+  }
+
   let cli_exec = match cli.run {
     Some(_) => cli.run.clone(),
     _ => cli.exec.clone(),
   };
   let cwd = project_root.clone();
-  watcher.watch(true, move |info| {
+  watcher.watch(move |changes| {
     if let Some(cmd_str) = &cli_exec {
-      let json_str = serialize_watch_info(&info);
+      let json_str = serialize_changes(&changes);
       std::env::set_var("JS_WATCHER_INFO", json_str.clone());
       let args = shellwords::split(&cmd_str.replace("[info]", &json_str)).unwrap();
       Command::new(&args[0])
@@ -137,19 +745,23 @@ fn main() {
     Ok(())
   });
 
-  if let Some(cmd_str) = cli.run {
-    println!("");
+  let child = cli.run.map(|cmd_str| {
+    println!();
     let args = shellwords::split(&cmd_str).unwrap();
     Command::new(&args[0])
       .args(&args[1..])
       .current_dir(&project_root)
       .spawn()
-      .expect("Failed to execute command");
-  }
+      .expect("Failed to execute command")
+  });
 
   let (tx, rx) = std::sync::mpsc::channel();
   ctrlc::set_handler(move || tx.send(()).expect("Could not send signal on channel."))
     .expect("Error setting Ctrl-C handler");
   rx.recv().expect("Could not receive from channel.");
-  println!("");
+  if let Some(mut child) = child {
+    child.kill().ok();
+    child.wait().ok();
+  }
+  println!();
 }
@@ -35,7 +35,9 @@ fn bench_make_changes(c: &mut Criterion) {
     cache_dir: None,
     supported_paths: None,
     debug: None,
-  });
+    ..Default::default()
+  })
+  .unwrap();
   let mut group = c.benchmark_group("make_changes");
   group.bench_function("three_js", |b| {
     b.iter_with_large_drop(|| {
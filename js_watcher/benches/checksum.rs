@@ -83,7 +83,7 @@ fn bench_hash(c: &mut Criterion) {
       let bytes = std::fs::read(FILE_A.as_path()).unwrap();
       let mut hasher = WyHash::with_seed(3);
       hasher.write(&bytes);
-      hasher.finish();
+      hasher.finish()
     })
   });
   group.finish();
@@ -3,12 +3,74 @@
 
 /* auto-generated by NAPI-RS */
 
+/** How files inside `node_modules` are handled */
+export const enum ExternalMode {
+  /** Packages are walked like any other file */
+  Walk = 'Walk',
+  /** Each package is a single dependency, its package.json, which changes with its version */
+  Package = 'Package',
+  /** Packages aren't dependencies */
+  Ignore = 'Ignore'
+}
 export interface SupportedPaths {
   esm?: Array<string>
   dynEsm?: Array<string>
   cjs?: Array<string>
   css?: Array<string>
 }
+/**
+ * Maps import specifiers to paths, like webpack's `resolve.alias`.
+ * `find` is matched against the specifiers in three ways:
+ * * `foo` matches `foo` and every `foo/...` import
+ * * `foo$` only matches `foo`
+ * * `foo/*` matches imports starting with `foo/`, and the `*` of replacements is substituted
+ */
+export interface ResolveAlias {
+  find: string
+  /** Paths tried in order, relative to the project's path */
+  replacements: Array<string>
+}
+/** What was tried while resolving an import */
+export const enum ResolveStepKind {
+  /** `http:`, `https:`, `data:` or `file:` imports */
+  Url = 'Url',
+  /** The `browser` field of the importing package, or of the package of the resolved file */
+  BrowserField = 'BrowserField',
+  Relative = 'Relative',
+  Alias = 'Alias',
+  Builtin = 'Builtin',
+  /** The `imports` field of the closest package.json, for `#internal` imports */
+  PackageImports = 'PackageImports',
+  TsconfigPaths = 'TsconfigPaths',
+  SelfReference = 'SelfReference',
+  Workspace = 'Workspace',
+  PnpManifest = 'PnpManifest',
+  NodeModules = 'NodeModules',
+  /** The `exports` or main fields of the package.json of the resolved package */
+  PackageJson = 'PackageJson',
+  Extensions = 'Extensions',
+  Symlink = 'Symlink'
+}
+export interface ResolveStep {
+  kind: ResolveStepKind
+  /** Whether this step decided the outcome of the resolution */
+  matched: boolean
+  message: string
+  /** Paths looked up during this step */
+  candidates: Array<string>
+}
+/** Every step tried to resolve an import, see `explain_resolve` */
+export interface ResolveTrace {
+  importer: string
+  specifier: string
+  steps: Array<ResolveStep>
+  /** The file the import resolves to */
+  resolved?: string
+  /** Set when the import resolves to something that isn't watched, like a node built-in */
+  external?: ExternalDep
+  /** Why the import couldn't be resolved */
+  error?: string
+}
 export interface SetupOptions {
   project: string
   projectRoot: string
@@ -17,6 +79,251 @@ export interface SetupOptions {
   cacheDir?: string
   supportedPaths?: SupportedPaths
   debug?: boolean
+  /** Aliases tried before any other resolution. `~` points to `project_root` by default */
+  aliases?: Array<ResolveAlias>
+  /**
+   * Conditions matched against package `exports`, on top of `import` or `require`.
+   * Defaults to `["node"]`
+   */
+  conditions?: Array<string>
+  /**
+   * package.json fields tried in order for the entry point of packages without `exports`.
+   * Defaults to `["main"]`. With `browser`, the object form of that field is applied too
+   */
+  mainFields?: Array<string>
+  /**
+   * Extensions tried in order for imports without one, and for `index` files.
+   * Defaults to TypeScript extensions, then JavaScript and style ones
+   */
+  extensions?: Array<string>
+  /** How packages of `node_modules` are watched. Defaults to `Walk`, every file is followed */
+  externalMode?: ExternalMode
+  /**
+   * Bare imports are resolved to the packages of the workspace of the project, declared by
+   * `pnpm-workspace.yaml` or the `workspaces` of package.json, even when they aren't
+   * installed. The workspace root has to be inside `project_root`. Defaults to `true`
+   */
+  workspaces?: boolean
+  /** Reports a `CycleAdded` change when a circular import appears. Defaults to `false` */
+  reportCycles?: boolean
+  /**
+   * Rules restricting what files may import, see `check_rules`. Each new violation is reported
+   * as a `RuleViolated` change. `setup` fails on invalid globs
+   */
+  rules?: Array<BoundaryRule>
+}
+export const enum ExternalKind {
+  /** A package inside a zip archive, like the Yarn Plug'n'Play cache */
+  Archive = 'Archive',
+  /** A module built into node, like `fs` or `node:test` */
+  Builtin = 'Builtin',
+  /** A remote module, like `https://esm.sh/react`, or a `data:` URI */
+  Url = 'Url'
+}
+export interface ExternalDep {
+  /** The import as written in the source */
+  specifier: string
+  kind: ExternalKind
+  /** Where the dependency lives, if it's on disk */
+  path?: string
+}
+/** Location of an import statement. Offsets are in bytes, lines and columns start at 1 */
+export interface Span {
+  start: number
+  end: number
+  line: number
+  column: number
+}
+export const enum DiagnosticKind {
+  /** Files only differing by their extension match an import without one, the first is used */
+  AmbiguousImport = 'AmbiguousImport',
+  /** The import couldn't be resolved, it's ignored until it does */
+  UnresolvedImport = 'UnresolvedImport',
+  /** The file couldn't be read, its imports are unknown */
+  UnreadableFile = 'UnreadableFile'
+}
+export interface Diagnostic {
+  kind: DiagnosticKind
+  /** The import as written in the source, or the path of the file for `UnreadableFile` */
+  specifier: string
+  /** The file containing the import */
+  importer: string
+  /** The import statement inside `importer` */
+  span?: Span
+  message: string
+}
+export const enum ImportKind {
+  /** `import`, `export ... from` or `import()` */
+  Esm = 'Esm',
+  Require = 'Require',
+  /** `@import` of style sheets */
+  Css = 'Css'
+}
+/** An import statement leading to a file */
+export interface ImportSite {
+  /** The import as written in the source */
+  specifier: string
+  kind: ImportKind
+  span: Span
+}
+export const enum GraphFormat {
+  Json = 'Json',
+  /** Graphviz */
+  Dot = 'Dot',
+  Mermaid = 'Mermaid'
+}
+export const enum GraphNodeKind {
+  /** A file of the project */
+  File = 'File',
+  /** A file of a package of `node_modules` */
+  PackageFile = 'PackageFile',
+  /** A whole package of `node_modules`, when packages are collapsed */
+  Package = 'Package',
+  /** A dependency that isn't watched, like a node built-in */
+  External = 'External'
+}
+export interface GraphNode {
+  /**
+   * The path of the file, the directory of a collapsed package, or the specifier of an
+   * external dependency
+   */
+  id: string
+  kind: GraphNodeKind
+  /** Size in bytes, summed up for collapsed packages */
+  size?: number
+  /** Name of the package the node belongs to */
+  package?: string
+}
+export interface GraphEdge {
+  from: string
+  to: string
+  /** Unknown for external dependencies */
+  kind?: ImportKind
+  /** The import statement inside `from` */
+  span?: Span
+}
+/** Nodes and edges are sorted, so the same graph is always written the same way */
+export interface DependencyGraph {
+  nodes: Array<GraphNode>
+  edges: Array<GraphEdge>
+}
+/** The dependency graph of every entry at some point, persisted in the cache directory */
+/** Restricts what the files matching `from` may import */
+export interface BoundaryRule {
+  /** Glob of the files the rule applies to, relative to the project root */
+  from: string
+  /** Globs of the files they may import, relative to the project root. Defaults to any file */
+  allow?: Array<string>
+  /** Globs of the files they may not import, relative to the project root. Wins over `allow` */
+  deny?: Array<string>
+  /** Packages they may not import, builtin modules included */
+  bannedPackages?: Array<string>
+  /**
+   * Relative imports may not reach a file of another package, whose closest package.json
+   * isn't the same. Defaults to `false`
+   */
+  noRelativeAcrossPackages?: boolean
+}
+export const enum RuleViolationKind {
+  /** The imported file matches none of the `allow` globs */
+  NotAllowed = 'NotAllowed',
+  /** The imported file matches one of the `deny` globs */
+  Denied = 'Denied',
+  /** The imported package is banned */
+  BannedPackage = 'BannedPackage',
+  /** A relative import reaches a file of another package */
+  RelativeAcrossPackages = 'RelativeAcrossPackages'
+}
+/** An import breaking a rule */
+export interface RuleViolation {
+  kind: RuleViolationKind
+  /** The `from` glob of the rule */
+  rule: string
+  importer: string
+  /** The imported file, or the name of the package for `BannedPackage` */
+  imported: string
+  /** The import as written in the source */
+  specifier?: string
+  /** The import statement inside `importer`, unknown for packages that aren't watched */
+  span?: Span
+}
+export interface GraphSnapshot {
+  entries: Array<string>
+  graph: DependencyGraph
+}
+/** What changed in the graph of an entry between two snapshots */
+export interface EntryGraphDiff {
+  entry: string
+  /** Files and external dependencies the entry now depends on */
+  addedNodes: Array<string>
+  /** Files and external dependencies the entry doesn't depend on anymore */
+  removedNodes: Array<string>
+  /** Names of the packages the entry now depends on */
+  addedPackages: Array<string>
+  /** Names of the packages the entry doesn't depend on anymore */
+  removedPackages: Array<string>
+}
+/** Structural changes between two snapshots. Every list is sorted */
+export interface GraphDiff {
+  addedEdges: Array<GraphEdge>
+  removedEdges: Array<GraphEdge>
+  /** Entries of the second snapshot only */
+  addedEntries: Array<string>
+  /** Entries of the first snapshot only */
+  removedEntries: Array<string>
+  /** Every entry whose graph changed, the added ones included */
+  entries: Array<EntryGraphDiff>
+}
+/** An export that no file of the store imports */
+export interface UnusedExport {
+  file: string
+  name: string
+  /** The statement exporting `name` */
+  span: Span
+}
+export interface GraphOptions {
+  /** Only the graph of this entry, relative to the project root. Defaults to every entry */
+  entry?: string | undefined | null
+  /** How many imports away from the entries files are kept. Defaults to no limit */
+  maxDepth?: number | undefined | null
+  /** Each package of `node_modules` is a single node. Defaults to `false` */
+  collapsePackages?: boolean | undefined | null
+}
+export interface ExplainDependencyOptions {
+  /** How many chains of imports are returned at most, the shortest first. Defaults to `1` */
+  maxPaths?: number | undefined | null
+}
+/** An import along a chain leading from an entry to one of its deps */
+export interface ImportStep {
+  importer: string
+  /** The file imported by `importer` */
+  file: string
+  /** The import as written in the source */
+  specifier?: string
+  /** The import statement inside `importer` */
+  span?: Span
+}
+export interface UnreachableOptions {
+  /** Globs of files that are never reported, relative to the project root */
+  ignore?: Array<string> | undefined | null
+  /** Test files are roots too, along with the entries. Defaults to `false` */
+  testsAsRoots?: boolean | undefined | null
+  /**
+   * Globs of test files, relative to the project root. Defaults to `*.test.*`, `*.spec.*`
+   * and files inside `__tests__`
+   */
+  testPatterns?: Array<string> | undefined | null
+}
+export interface GetEntriesOptions {
+  /**
+   * Entries come leaves first, after every entry they depend on, see `topological_order`.
+   * Defaults to `false`, in no particular order
+   */
+  sorted?: boolean
+}
+export interface GetImportersOptions {
+  /** Also returns the files depending on them, and so on. Defaults to `false` */
+  transitive?: boolean | undefined | null
 }
 export interface EntryChangeCause {
   file: string
@@ -27,6 +334,12 @@ export interface EntryChange {
   entry: string
   cause?: EntryChangeCause
   tree?: Array<string>
+  /** For `ImportUnresolved` and `ImportResolved`, the diagnostic that appeared or disappeared */
+  diagnostic?: Diagnostic
+  /** For `CycleAdded`, the files of the cycle, sorted */
+  cycle?: Array<string>
+  /** For `RuleViolated`, the import breaking a rule */
+  violation?: RuleViolation
 }
 export const enum EntryChangeType {
   Added = 'Added',
@@ -34,7 +347,15 @@ export const enum EntryChangeType {
   Modified = 'Modified',
   DepModified = 'DepModified',
   Deleted = 'Deleted',
-  DepDeleted = 'DepDeleted'
+  DepDeleted = 'DepDeleted',
+  /** An import of the entry or of one of its deps stopped resolving */
+  ImportUnresolved = 'ImportUnresolved',
+  /** An import of the entry or of one of its deps that didn't resolve now does */
+  ImportResolved = 'ImportResolved',
+  /** The entry or one of its deps became part of a circular import */
+  CycleAdded = 'CycleAdded',
+  /** An import of the entry or of one of its deps started breaking a rule */
+  RuleViolated = 'RuleViolated'
 }
 export const enum FileState {
   NotModified = 'NotModified',
@@ -44,11 +365,29 @@ export const enum FileState {
 }
 export type Watcher = ModulesWatcher
 export class ModulesWatcher {
+  /** Fails on invalid rules */
   static setup(opts: SetupOptions): Watcher
   cacheDir(): string
-  getEntries(): Array<FileItem>
+  getEntries(opts?: GetEntriesOptions | undefined | null): Array<FileItem>
+  topologicalOrder(entry?: string | undefined | null): Array<Array<string>>
   makeChanges(): Array<EntryChange>
   getDirsToWatch(): Array<string>
+  getDiagnostics(): Array<Diagnostic>
+  explainDependency(entry: string, file: string, opts?: ExplainDependencyOptions | undefined | null): Array<Array<ImportStep>>
+  getGraph(opts?: GraphOptions | undefined | null): DependencyGraph
+  /** Writes the graph of the files of the entries as JSON, Graphviz DOT or Mermaid */
+  exportGraph(format: GraphFormat, opts?: GraphOptions | undefined | null): string
+  /** Fails for invalid names, and when the snapshot can't be written */
+  saveSnapshot(name: string): string
+  diffGraphs(from: string, to: string): GraphDiff | null
+  /** Fails on invalid globs */
+  getUnreachable(globs: Array<string>, opts?: UnreachableOptions | undefined | null): Array<string>
+  getCycles(): Array<Array<string>>
+  getUnusedExports(): Array<UnusedExport>
+  checkRules(): Array<RuleViolation>
+  getImporters(file: string, opts?: GetImportersOptions | undefined | null): Array<string>
+  /** `kind` defaults to `Esm` */
+  explainResolve(importer: string, specifier: string, kind?: ImportKind | undefined | null): ResolveTrace
   stopWatching(): void
   watch(callback: (err: null | Error, result: EntryChange[]) => void): void
 }
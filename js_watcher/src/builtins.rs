@@ -0,0 +1,94 @@
+/// Modules built into node that can be imported without the `node:` scheme, as listed by
+/// `require('module').builtinModules`.
+pub const NODE_BUILTINS: [&str; 68] = [
+  "_http_agent",
+  "_http_client",
+  "_http_common",
+  "_http_incoming",
+  "_http_outgoing",
+  "_http_server",
+  "_stream_duplex",
+  "_stream_passthrough",
+  "_stream_readable",
+  "_stream_transform",
+  "_stream_wrap",
+  "_stream_writable",
+  "_tls_common",
+  "_tls_wrap",
+  "assert",
+  "assert/strict",
+  "async_hooks",
+  "buffer",
+  "child_process",
+  "cluster",
+  "console",
+  "constants",
+  "crypto",
+  "dgram",
+  "diagnostics_channel",
+  "dns",
+  "dns/promises",
+  "domain",
+  "events",
+  "fs",
+  "fs/promises",
+  "http",
+  "http2",
+  "https",
+  "inspector",
+  "inspector/promises",
+  "module",
+  "net",
+  "os",
+  "path",
+  "path/posix",
+  "path/win32",
+  "perf_hooks",
+  "process",
+  "punycode",
+  "querystring",
+  "readline",
+  "readline/promises",
+  "repl",
+  "stream",
+  "stream/consumers",
+  "stream/promises",
+  "stream/web",
+  "string_decoder",
+  "sys",
+  "timers",
+  "timers/promises",
+  "tls",
+  "trace_events",
+  "tty",
+  "url",
+  "util",
+  "util/types",
+  "v8",
+  "vm",
+  "wasi",
+  "worker_threads",
+  "zlib",
+];
+
+/// Returns whether `specifier` designates a node built-in module, like `fs`, `fs/promises` or
+/// `node:test`. Every `node:` import is considered built-in, since some modules are only
+/// reachable through the scheme.
+pub fn is_node_builtin(specifier: &str) -> bool {
+  specifier.starts_with("node:") || NODE_BUILTINS.contains(&specifier)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::is_node_builtin;
+
+  #[test]
+  fn test_is_node_builtin() {
+    for specifier in ["fs", "fs/promises", "node:path", "node:test", "_http_agent"] {
+      assert!(is_node_builtin(specifier), "{}", specifier);
+    }
+    for specifier in ["fs-extra", "path/to", "./fs", "test", "@types/node"] {
+      assert!(!is_node_builtin(specifier), "{}", specifier);
+    }
+  }
+}
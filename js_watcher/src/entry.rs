@@ -1,19 +1,61 @@
+use dashmap::mapref::entry::Entry;
 use dashmap::mapref::one::Ref;
 use dashmap::DashMap;
 use glob::glob;
-use memoize::memoize;
+#[cfg(not(feature = "without-napi"))]
+use napi::bindgen_prelude::{FromNapiValue, ToNapiValue};
 use rayon::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::*;
-use std::path::{Component, Path, PathBuf};
-
-use crate::file_item::FileItem;
-use crate::parser::{parse_deps, ImportDep, ParseConditions};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::builtins::is_node_builtin;
+use crate::file_item::{
+  Diagnostic, DiagnosticKind, ExternalDep, ExternalKind, FileItem, ImportKind, ImportSite,
+};
+use crate::graph::{link_imports, refresh_deps};
+use crate::parser::{parse_deps_with_spans, ImportDep, ParseConditions};
 use crate::path_clean::*;
-
-#[derive(Clone)]
+use crate::pnp::{find_pnp_manifest, load_pnp_manifest, PnpLocation, PnpManifest};
+use crate::resolve_cache::{BrowserField, ResolveCache};
+use crate::symbols::parse_symbols;
+use crate::tsconfig::{find_tsconfig, load_tsconfig, TsConfig};
+use crate::workspaces::find_workspace_packages;
+
+pub(crate) const JS_EXTS: [&str; 8] = ["cjs", "mjs", "js", "ts", "tsx", "jsx", "cts", "mts"];
+const STYLE_EXTS: [&str; 3] = ["css", "scss", "sass"];
+/// Extensions tried in order for imports without one. TypeScript sources win over JavaScript
+/// ones, like for `tsc`
+const DEFAULT_EXTENSIONS: [&str; 13] = [
+  ".ts", ".tsx", ".mts", ".cts", ".js", ".jsx", ".mjs", ".cjs", ".json", ".css", ".scss", ".sass",
+  ".mdx",
+];
+
+#[derive(Clone, Default)]
 pub struct MakeEntriesOptions {
   pub supported_paths: Option<SupportedPaths>,
+  pub aliases: Option<Vec<ResolveAlias>>,
+  pub conditions: Option<Vec<String>>,
+  pub main_fields: Option<Vec<String>>,
+  pub extensions: Option<Vec<String>>,
+  pub external_mode: Option<ExternalMode>,
+  /// Bare imports are looked up among the packages of the workspace. Defaults to `true`
+  pub workspaces: Option<bool>,
+  /// Shared by every clone of the options, see `ResolveCache`
+  pub cache: Arc<ResolveCache>,
+}
+
+/// How files inside `node_modules` are handled
+#[cfg_attr(not(feature = "without-napi"), napi(string_enum), derive(PartialEq, Debug))]
+#[cfg_attr(feature = "without-napi", derive(PartialEq, Debug, Clone, Copy))]
+pub enum ExternalMode {
+  /// Packages are walked like any other file
+  Walk,
+  /// Each package is a single dependency, its package.json, which changes with its version
+  Package,
+  /// Packages aren't dependencies
+  Ignore,
 }
 
 #[napi(object)]
@@ -25,6 +67,19 @@ pub struct SupportedPaths {
   pub css: Option<Vec<String>>,
 }
 
+/// Maps import specifiers to paths, like webpack's `resolve.alias`.
+/// `find` is matched against the specifiers in three ways:
+/// * `foo` matches `foo` and every `foo/...` import
+/// * `foo$` only matches `foo`
+/// * `foo/*` matches imports starting with `foo/`, and the `*` of replacements is substituted
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct ResolveAlias {
+  pub find: String,
+  /// Paths tried in order, relative to the project's path
+  pub replacements: Vec<String>,
+}
+
 pub fn make_entries(
   entry_paths: Vec<PathBuf>,
   entry_globs: Option<Vec<&str>>,
@@ -36,18 +91,13 @@ pub fn make_entries(
   (store, entries)
 }
 
-pub fn make_missing_entries(
-  entry_paths: Vec<PathBuf>,
-  entry_globs: Option<Vec<&str>>,
-  project_path: PathBuf,
-  store: &DashMap<String, FileItem>,
-  opts: &Option<MakeEntriesOptions>,
-) -> Vec<FileItem> {
-  let mut paths: Vec<PathBuf> = entry_paths;
-
-  for glob_str in entry_globs.unwrap_or_default() {
+/// Lists the real paths of the files matching `globs`, the form of the keys of the store, see
+/// `canonicalize_path`. Relative globs are relative to `project_path`.
+pub fn expand_globs(globs: &[&str], project_path: &Path) -> Vec<PathBuf> {
+  let mut paths = Vec::new();
+  for glob_str in globs {
     let full_glob = if glob_str.starts_with('/') {
-      glob_str.to_owned()
+      glob_str.to_string()
     } else {
       project_path
         .join(glob_str)
@@ -59,15 +109,41 @@ pub fn make_missing_entries(
     paths.extend(
       glob(&full_glob)
         .expect("Failed to read glob pattern")
-        .map(|x| x.unwrap()),
+        .filter_map(|x| canonicalize_path(x.unwrap())),
     );
   }
+  paths
+}
 
-  paths.retain(|x| !store.contains_key(x.to_str().unwrap()));
+pub fn make_missing_entries(
+  entry_paths: Vec<PathBuf>,
+  entry_globs: Option<Vec<&str>>,
+  project_path: PathBuf,
+  store: &DashMap<String, FileItem>,
+  opts: &Option<MakeEntriesOptions>,
+) -> Vec<FileItem> {
+  // a symlinked project root would otherwise split the store between two forms of the paths
+  let project_path = canonicalize_path(project_path.clone()).unwrap_or(project_path);
+  let mut paths: Vec<PathBuf> = entry_paths
+    .into_iter()
+    .filter_map(canonicalize_path)
+    .collect();
+  paths.extend(expand_globs(
+    &entry_globs.unwrap_or_default(),
+    &project_path,
+  ));
+
+  // paths that aren't valid UTF-8 can't be keys of the store
+  paths.retain(|x| x.to_str().is_some_and(|x| !store.contains_key(x)));
 
   paths.par_iter().for_each(|p| {
-    make_file_item(p, &project_path, store, opts);
+    walk_file_item(p, &project_path, store, opts);
   });
+  // The deps of entries made in parallel may miss files other threads were making, they are
+  // only computed once every file is made
+  for path in &paths {
+    refresh_deps(store, path.to_str().unwrap());
+  }
 
   let entries = paths
     .iter()
@@ -78,23 +154,37 @@ pub fn make_missing_entries(
   entries
 }
 
+/// Makes `file_path` and the files it imports, directly or not, unless they are in the store
+/// already. Only the deps of `file_path` are computed, see `graph::refresh_deps`.
 pub fn make_file_item<'a>(
   file_path: &'a Path,
   project_path: &'a Path,
   store: &'a DashMap<String, FileItem>,
   opts: &Option<MakeEntriesOptions>,
 ) -> Option<Ref<'a, String, FileItem>> {
-  let key = file_path.to_str().unwrap();
+  let key = file_path.to_str()?;
+  walk_file_item(file_path, project_path, store, opts)?;
+  refresh_deps(store, key);
+  Some(
+    store
+      .get(key)
+      .unwrap_or_else(|| panic!("Couldn't read {} inside the store", key)),
+  )
+}
+
+/// Makes `file_path` and the files it imports like `make_file_item`, without computing deps.
+/// Returns `None` when the file isn't watched.
+fn walk_file_item(
+  file_path: &Path,
+  project_path: &Path,
+  store: &DashMap<String, FileItem>,
+  opts: &Option<MakeEntriesOptions>,
+) -> Option<()> {
+  let key = file_path.to_str()?;
   if store.contains_key(key) {
-    return Some(
-      store
-        .get(key)
-        .unwrap_or_else(|| panic!("Couldn't read {} inside the store", key)),
-    );
+    return Some(());
   }
 
-  let js_exts = &["cjs", "mjs", "js", "ts", "tsx", "jsx", "cts", "mts"];
-  let style_exts = &["css", "scss", "sass"];
   let supported_paths: SupportedPaths = {
     let mut value = match opts {
       Some(opts_val) => match &opts_val.supported_paths {
@@ -115,24 +205,24 @@ pub fn make_file_item<'a>(
     };
     if value.esm.is_none() {
       value.esm = Some(
-        (*js_exts)
+        JS_EXTS
           .into_iter()
-          .chain(["mdx"].into_iter())
+          .chain(["mdx"])
           .map(String::from)
           .collect(),
       );
     }
     if value.dyn_esm.is_none() {
-      value.dyn_esm = Some((*js_exts).into_iter().map(String::from).collect());
+      value.dyn_esm = Some(JS_EXTS.into_iter().map(String::from).collect());
     }
     if value.cjs.is_none() {
-      value.cjs = Some((*js_exts).into_iter().map(String::from).collect());
+      value.cjs = Some(JS_EXTS.into_iter().map(String::from).collect());
     }
     if value.css.is_none() {
       value.css = Some(
-        (*style_exts)
+        STYLE_EXTS
           .into_iter()
-          .chain(["mdx"].into_iter())
+          .chain(["mdx"])
           .map(String::from)
           .collect(),
       );
@@ -140,7 +230,7 @@ pub fn make_file_item<'a>(
     value
   };
 
-  let file_ext = file_path.extension().unwrap().to_str().unwrap().to_string();
+  let file_ext = file_path.extension()?.to_str()?.to_string();
   let parse_conditions = ParseConditions {
     css: supported_paths.css.unwrap().contains(&file_ext),
     esm: supported_paths.esm.unwrap().contains(&file_ext),
@@ -156,269 +246,1541 @@ pub fn make_file_item<'a>(
     return None;
   }
 
-  store.insert(
-    key.to_string(),
-    FileItem {
-      path: PathBuf::from(&file_path),
-      deps: HashSet::new(),
-    },
-  );
-  let mut all_deps: HashSet<String> = HashSet::new();
+  // Files are made in parallel, only the thread claiming the file first makes it
+  let claimed = match store.entry(key.to_string()) {
+    Entry::Occupied(_) => false,
+    Entry::Vacant(entry) => {
+      entry.insert(FileItem {
+        path: PathBuf::from(&file_path),
+        imports: HashSet::new(),
+        external_imports: HashSet::new(),
+        import_sites: HashMap::new(),
+        symbols: Vec::new(),
+        importers: HashSet::new(),
+        deps: HashSet::new(),
+        externals: HashSet::new(),
+        diagnostics: Vec::new(),
+      });
+      true
+    }
+  };
+  if !claimed {
+    return Some(());
+  }
+  let mut imports: HashSet<String> = HashSet::new();
+  let mut external_imports: HashSet<ExternalDep> = HashSet::new();
+  let mut import_sites: HashMap<String, ImportSite> = HashMap::new();
+  // every import resolved to a file of the store, by statement
+  let mut resolved: HashMap<ImportDep, String> = HashMap::new();
+  let mut diagnostics: Vec<Diagnostic> = Vec::new();
 
   // Scan file for imports
-  let content = read_to_string(file_path).unwrap_or_else(|_| panic!("Couldn't read file {} ", key));
+  let content = match read_to_string(file_path) {
+    Ok(content) => content,
+    Err(err) => {
+      // A missing file is tracked until it's created, there is nothing to report
+      if file_path.exists() {
+        store.get_mut(key).unwrap().diagnostics.push(Diagnostic {
+          kind: DiagnosticKind::UnreadableFile,
+          specifier: key.to_string(),
+          importer: key.to_string(),
+          span: None,
+          message: format!("Couldn't read file: {}", err),
+        });
+      }
+      return Some(());
+    }
+  };
 
-  let imports = parse_deps(&content, parse_conditions);
-  for source_imp in imports {
-    let source = match &source_imp {
-      ImportDep::ESM(path) | ImportDep::REQUIRE(path) | ImportDep::CSS(path) => path.clone(),
+  // Exports are only looked for in the JS files of the project, see `unused`
+  let has_symbols = parse_conditions.esm
+    && !file_path
+      .components()
+      .any(|x| x.as_os_str() == "node_modules");
+  let statements = parse_deps_with_spans(&content, parse_conditions);
+  let mut resolver = ImportResolver::new(file_path, project_path, opts);
+  let external_mode = get_external_mode(opts);
+  for (source_imp, span) in statements {
+    let make_diagnostic = |kind: DiagnosticKind, message: String| Diagnostic {
+      kind,
+      specifier: source_imp.specifier().to_string(),
+      importer: key.to_string(),
+      span: Some(span),
+      message,
     };
-    let maybe_path_buf = if source.starts_with("./") || source.starts_with("../") {
-      let dir = file_path.parent().unwrap();
-      Some(dir.join(source).clean())
-    } else if source.starts_with("~/") {
-      let transformed_path = source.replace("~/", "");
-      Some(project_path.join(Path::new(&transformed_path)).clean())
-    } else {
-      let node_modules_path = find_node_modules_dir(project_path.to_path_buf())
-        .expect("Couldn't find node_modules folder");
-      resolve_node_module(&source, &source_imp, node_modules_path.as_path())
+    let site = || ImportSite {
+      specifier: source_imp.specifier().to_string(),
+      kind: ImportKind::from(&source_imp),
+      span,
     };
-    let mut path_buf = maybe_path_buf?;
-    // If the imported file is a directory, we need to resolve it's index file
-    if path_buf.is_dir() {
-      if let Some(found) = resolve_index(&path_buf) {
-        path_buf = found;
-      } else {
-        panic!("Couldn't handle import: {}", path_buf.to_str().unwrap());
+    let (path_buf, candidates) = match resolver.resolve(&source_imp) {
+      ImportResolution::File(path_buf, candidates) => (path_buf, candidates),
+      ImportResolution::External(external) => {
+        external_imports.insert(external);
+        continue;
+      }
+      ImportResolution::Ignored => continue,
+      ImportResolution::Unresolved(reason) => {
+        diagnostics.push(make_diagnostic(DiagnosticKind::UnresolvedImport, reason));
+        continue;
+      }
+    };
+    // files are keyed by their path in the store
+    let import_key = match path_buf.to_str() {
+      Some(import_key) => import_key.to_string(),
+      None => {
+        let reason = format!("The path isn't valid UTF-8: {}", path_buf.display());
+        diagnostics.push(make_diagnostic(DiagnosticKind::UnresolvedImport, reason));
+        continue;
       }
+    };
+    if candidates.len() > 1 {
+      diagnostics.push(make_diagnostic(
+        DiagnosticKind::AmbiguousImport,
+        format!(
+          "Several files match, the first one is used: {}",
+          candidates
+            .iter()
+            .map(|x| x.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(", ")
+        ),
+      ));
     }
-    // If the imported file has no known extension, we need to resolve it
-    else if path_buf.extension().is_none()
-      || (!js_exts.contains(&path_buf.extension().unwrap().to_str().unwrap())
-        && !style_exts.contains(&path_buf.extension().unwrap().to_str().unwrap()))
-    {
-      if let Some(found) = resolve_with_extension(&path_buf) {
-        path_buf = found;
+    if external_mode != ExternalMode::Walk {
+      let pkg_dir = get_package_root(&path_buf).filter(|x| !file_path.starts_with(x));
+      if let Some(pkg_dir) = pkg_dir {
+        if external_mode == ExternalMode::Package {
+          let pkg_path = pkg_dir.join("package.json");
+          let pkg_key = pkg_path.to_string_lossy().to_string();
+          imports.insert(pkg_key.clone());
+          import_sites.entry(pkg_key.clone()).or_insert_with(site);
+          resolved.insert(source_imp, pkg_key.clone());
+          store.entry(pkg_key).or_insert_with(|| FileItem {
+            path: pkg_path,
+            imports: HashSet::new(),
+            external_imports: HashSet::new(),
+            import_sites: HashMap::new(),
+            symbols: Vec::new(),
+            importers: HashSet::new(),
+            deps: HashSet::new(),
+            externals: HashSet::new(),
+            diagnostics: Vec::new(),
+          });
+        }
+        continue;
+      }
+    }
+    import_sites.entry(import_key.clone()).or_insert_with(site);
+    resolved.insert(source_imp, import_key.clone());
+    imports.insert(import_key);
+    walk_file_item(&path_buf, project_path, store, opts);
+  }
+  let symbols = if has_symbols {
+    parse_symbols(&content)
+      .into_iter()
+      .filter_map(|(symbol, span)| Some((symbol.resolve(|x| resolved.get(x).cloned())?, span)))
+      .collect()
+  } else {
+    Vec::new()
+  };
+  link_imports(store, key, &imports);
+  {
+    let mut item = store
+      .get_mut(key)
+      .unwrap_or_else(|| panic!("Couldn't read {} inside the store", key));
+    item.imports = imports;
+    item.external_imports = external_imports;
+    item.import_sites = import_sites;
+    item.symbols = symbols;
+    item.diagnostics = diagnostics;
+  }
+  Some(())
+}
+
+/// Outcome of resolving an import
+enum ImportResolution {
+  /// The file designated by the import, with the files it collides with, see
+  /// `get_colliding_candidates`
+  File(PathBuf, Vec<PathBuf>),
+  External(ExternalDep),
+  /// The import is replaced by an empty module through the `browser` field
+  Ignored,
+  Unresolved(String),
+}
+
+/// What was tried while resolving an import
+#[cfg_attr(not(feature = "without-napi"), napi(string_enum), derive(PartialEq, Debug))]
+#[cfg_attr(feature = "without-napi", derive(PartialEq, Debug, Clone, Copy))]
+pub enum ResolveStepKind {
+  /// `http:`, `https:`, `data:` or `file:` imports
+  Url,
+  /// The `browser` field of the importing package, or of the package of the resolved file
+  BrowserField,
+  Relative,
+  Alias,
+  Builtin,
+  /// The `imports` field of the closest package.json, for `#internal` imports
+  PackageImports,
+  TsconfigPaths,
+  SelfReference,
+  Workspace,
+  PnpManifest,
+  NodeModules,
+  /// The `exports` or main fields of the package.json of the resolved package
+  PackageJson,
+  Extensions,
+  Symlink,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct ResolveStep {
+  pub kind: ResolveStepKind,
+  /// Whether this step decided the outcome of the resolution
+  pub matched: bool,
+  pub message: String,
+  /// Paths looked up during this step
+  pub candidates: Vec<String>,
+}
+
+impl ResolveStep {
+  fn new(kind: ResolveStepKind, matched: bool, message: String) -> Self {
+    ResolveStep {
+      kind,
+      matched,
+      message,
+      candidates: Vec::new(),
+    }
+  }
+
+  fn with_candidates(mut self, candidates: &[PathBuf]) -> Self {
+    self.candidates = candidates
+      .iter()
+      .map(|x| x.to_string_lossy().to_string())
+      .collect();
+    self
+  }
+}
+
+/// Every step tried to resolve an import, see `explain_resolve`
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct ResolveTrace {
+  pub importer: String,
+  pub specifier: String,
+  pub steps: Vec<ResolveStep>,
+  /// The file the import resolves to
+  pub resolved: Option<String>,
+  /// Set when the import resolves to something that isn't watched, like a node built-in
+  pub external: Option<ExternalDep>,
+  /// Why the import couldn't be resolved
+  pub error: Option<String>,
+}
+
+/// Records a step of the resolution, `step` is only called when tracing.
+fn record(trace: &mut Option<Vec<ResolveStep>>, step: impl FnOnce() -> ResolveStep) {
+  if let Some(steps) = trace {
+    steps.push(step());
+  }
+}
+
+/// Resolves the imports of a file. Options are only read once per file, and the steps tried
+/// can be recorded to explain a resolution.
+struct ImportResolver<'a> {
+  file_path: &'a Path,
+  project_path: &'a Path,
+  opts: &'a Option<MakeEntriesOptions>,
+  aliases: Vec<ResolveAlias>,
+  main_fields: Vec<String>,
+  extensions: Vec<String>,
+  browser: bool,
+  workspaces: bool,
+  cache: Arc<ResolveCache>,
+  /// Only loaded once a bare import needs it
+  tsconfig: Option<Option<Arc<TsConfig>>>,
+  trace: Option<Vec<ResolveStep>>,
+}
+
+impl<'a> ImportResolver<'a> {
+  fn new(
+    file_path: &'a Path,
+    project_path: &'a Path,
+    opts: &'a Option<MakeEntriesOptions>,
+  ) -> Self {
+    let main_fields = get_main_fields(opts);
+    ImportResolver {
+      file_path,
+      project_path,
+      opts,
+      aliases: get_aliases(opts, project_path),
+      browser: main_fields.iter().any(|x| x == "browser"),
+      main_fields,
+      extensions: get_extensions(opts),
+      workspaces: opts.as_ref().and_then(|x| x.workspaces).unwrap_or(true),
+      cache: opts.as_ref().map(|x| x.cache.clone()).unwrap_or_default(),
+      tsconfig: None,
+      trace: None,
+    }
+  }
+
+  fn resolve(&mut self, import: &ImportDep) -> ImportResolution {
+    let conditions = get_conditions(import, self.opts);
+    let mut source = import.specifier().to_string();
+    if is_remote_url(&source) {
+      record(&mut self.trace, || {
+        let message = "A remote module or a `data:` URI, it isn't watched".to_string();
+        ResolveStep::new(ResolveStepKind::Url, true, message)
+      });
+      return ImportResolution::External(ExternalDep {
+        specifier: source,
+        kind: ExternalKind::Url,
+        path: None,
+      });
+    }
+    let mut remapped = None;
+    if source.starts_with("file:") {
+      let path = file_url_to_path(&source);
+      record(&mut self.trace, || {
+        let message = match &path {
+          Some(path) => format!("A file URL: {}", path.display()),
+          None => "Only absolute file URLs of the local host are supported".to_string(),
+        };
+        ResolveStep::new(ResolveStepKind::Url, path.is_some(), message)
+      });
+      match path {
+        Some(path) => remapped = Some(path),
+        None => return ImportResolution::Unresolved("Invalid file URL".to_string()),
+      }
+    } else if self.browser && !source.starts_with('.') && !source.starts_with('/') {
+      let mapping = remap_browser_module(&source, self.file_path, &self.cache);
+      record(&mut self.trace, || {
+        let message = match &mapping {
+          Some(BrowserMapping::Ignored) => "Ignored by the `browser` field".to_string(),
+          Some(BrowserMapping::Path(path)) => {
+            format!("Replaced by {} in the `browser` field", path.display())
+          }
+          Some(BrowserMapping::Module(module)) => {
+            format!("Replaced by `{}` in the `browser` field", module)
+          }
+          None => "Not in the `browser` field".to_string(),
+        };
+        ResolveStep::new(ResolveStepKind::BrowserField, mapping.is_some(), message)
+      });
+      match mapping {
+        Some(BrowserMapping::Ignored) => return ImportResolution::Ignored,
+        Some(BrowserMapping::Path(path)) => remapped = Some(path),
+        Some(BrowserMapping::Module(module)) => source = module,
+        None => {}
+      }
+    }
+    let maybe_path_buf = match remapped {
+      Some(path) => path,
+      None => match self.resolve_specifier(&source, &conditions) {
+        Ok(path) => path,
+        Err(resolution) => return resolution,
+      },
+    };
+
+    let candidates = resolve_path_candidates(&maybe_path_buf, &self.extensions);
+    let unmatched_reason = |path: &Path| format!("No file matches {}", path.display());
+    let selected = select_candidate(maybe_path_buf.clone(), candidates.clone());
+    record(&mut self.trace, || {
+      let message = if maybe_path_buf.is_file() {
+        format!("{} is a file", maybe_path_buf.display())
       } else {
-        panic!("Couldn't handle import: {}", path_buf.to_str().unwrap());
+        format!(
+          "Probed {} with the extensions {}, then its index files",
+          maybe_path_buf.display(),
+          self.extensions.join(", ")
+        )
+      };
+      ResolveStep::new(ResolveStepKind::Extensions, selected.is_some(), message)
+        .with_candidates(&candidates)
+    });
+    let mut path_buf = match selected {
+      Some(path_buf) => path_buf,
+      None => return ImportResolution::Unresolved(unmatched_reason(&maybe_path_buf)),
+    };
+    if self.browser {
+      let mapping = remap_browser_file(&path_buf, &self.cache);
+      record(&mut self.trace, || {
+        let message = match &mapping {
+          Some(BrowserMapping::Ignored) => "The file is ignored by the `browser` field".to_string(),
+          Some(BrowserMapping::Path(path)) => format!(
+            "The file is replaced by {} in the `browser` field",
+            path.display()
+          ),
+          _ => "The file isn't in the `browser` field".to_string(),
+        };
+        ResolveStep::new(ResolveStepKind::BrowserField, mapping.is_some(), message)
+      });
+      match mapping {
+        Some(BrowserMapping::Ignored) => return ImportResolution::Ignored,
+        Some(BrowserMapping::Path(path)) => match resolve_path(path.clone(), &self.extensions) {
+          Some(remapped_path) => path_buf = remapped_path,
+          None => return ImportResolution::Unresolved(unmatched_reason(&path)),
+        },
+        _ => {}
       }
     }
-    all_deps.insert(path_buf.to_str().unwrap().to_string());
-    if let Some(file_ref) = make_file_item(&path_buf.clone(), project_path, store, opts) {
-      all_deps.extend(file_ref.deps.clone());
+    match canonicalize_path(path_buf.clone()) {
+      Some(real_path) => {
+        if real_path != path_buf {
+          record(&mut self.trace, || {
+            let message = format!("Followed symlinks to {}", real_path.display());
+            ResolveStep::new(ResolveStepKind::Symlink, true, message)
+          });
+        }
+        ImportResolution::File(real_path, get_colliding_candidates(candidates))
+      }
+      None => {
+        let reason = "The file can't be resolved, it may be a symlink loop".to_string();
+        record(&mut self.trace, || {
+          ResolveStep::new(ResolveStepKind::Symlink, false, reason.clone())
+        });
+        ImportResolution::Unresolved(reason)
+      }
     }
   }
-  store
-    .get_mut(key)
-    .unwrap_or_else(|| panic!("Couldn't read {} inside the store", key))
-    .deps = all_deps;
 
-  Some(
-    store
-      .get(key)
-      .unwrap_or_else(|| panic!("Couldn't read {} inside the store", key)),
-  )
+  /// Resolves `source` to the path it designates, which may lack its extension.
+  /// Imports that don't lead to a file are returned as an `Err`.
+  fn resolve_specifier(
+    &mut self,
+    source: &str,
+    conditions: &[String],
+  ) -> Result<PathBuf, ImportResolution> {
+    let file_path = self.file_path;
+    if source.starts_with("./") || source.starts_with("../") {
+      let path = file_path.parent().unwrap().join(source).clean();
+      record(&mut self.trace, || {
+        let message = format!("Relative to the importing file: {}", path.display());
+        ResolveStep::new(ResolveStepKind::Relative, true, message)
+      });
+      return Ok(path);
+    }
+
+    let found = resolve_alias(source, &self.aliases, self.project_path, &self.extensions);
+    record(&mut self.trace, || match &found {
+      Some(path) => {
+        let message = format!("Matches an alias: {}", path.display());
+        ResolveStep::new(ResolveStepKind::Alias, true, message)
+      }
+      None => {
+        let finds: Vec<&str> = self.aliases.iter().map(|x| &x.find[..]).collect();
+        let message = format!("No alias matches among {}", finds.join(", "));
+        ResolveStep::new(ResolveStepKind::Alias, false, message)
+      }
+    });
+    if let Some(found) = found {
+      return Ok(found);
+    }
+
+    if is_node_builtin(source) {
+      // Bundlers targeting browsers replace built-ins like `buffer` or `process` with the
+      // polyfills installed under the same name
+      if self.browser && !source.starts_with("node:") {
+        let node_modules_dirs = find_node_modules_dirs(file_path.parent().unwrap(), &self.cache);
+        let polyfill = resolve_bare_import(
+          source,
+          conditions,
+          &self.main_fields,
+          &node_modules_dirs,
+          &mut self.trace,
+        );
+        if let Some(polyfill) = polyfill {
+          return Ok(polyfill);
+        }
+      }
+      record(&mut self.trace, || {
+        let message = "A node built-in module, it isn't watched".to_string();
+        ResolveStep::new(ResolveStepKind::Builtin, true, message)
+      });
+      return Err(ImportResolution::External(ExternalDep {
+        specifier: source.to_string(),
+        kind: ExternalKind::Builtin,
+        path: None,
+      }));
+    }
+
+    if source.starts_with('#') {
+      let found = resolve_package_imports(
+        source,
+        file_path,
+        conditions,
+        &self.main_fields,
+        &self.cache,
+      );
+      record(&mut self.trace, || {
+        let message = format!(
+          "Looked up in the `imports` of the closest package.json with the conditions {}",
+          conditions.join(", ")
+        );
+        let pkg_path = file_path
+          .parent()
+          .and_then(|x| find_package_json(x, &self.cache));
+        ResolveStep::new(ResolveStepKind::PackageImports, found.is_some(), message)
+          .with_candidates(&pkg_path.into_iter().collect::<Vec<PathBuf>>())
+      });
+      return found.ok_or_else(|| {
+        let reason = "No match in the `imports` of the closest package.json".to_string();
+        ImportResolution::Unresolved(reason)
+      });
+    }
+
+    let found = resolve_tsconfig_paths(
+      source,
+      file_path,
+      &mut self.tsconfig,
+      &self.extensions,
+      &self.cache,
+    );
+    record(&mut self.trace, || {
+      let config = match &self.tsconfig {
+        Some(Some(config)) => config,
+        _ => {
+          let message = "No tsconfig.json or jsconfig.json".to_string();
+          return ResolveStep::new(ResolveStepKind::TsconfigPaths, false, message);
+        }
+      };
+      let candidates: Vec<PathBuf> = config
+        .path_candidates(source)
+        .into_iter()
+        .chain(config.base_url_candidate(source))
+        .collect();
+      let message = match &found {
+        Some(path) => format!(
+          "Matches `compilerOptions.paths` or `baseUrl`: {}",
+          path.display()
+        ),
+        None => "No candidate of `compilerOptions.paths` or `baseUrl` exists".to_string(),
+      };
+      ResolveStep::new(ResolveStepKind::TsconfigPaths, found.is_some(), message)
+        .with_candidates(&candidates)
+    });
+    if let Some(found) = found {
+      return Ok(found);
+    }
+
+    let found = resolve_self_reference(source, file_path, conditions, &self.cache);
+    record(&mut self.trace, || {
+      let message = match &found {
+        Some(path) => format!(
+          "The package imports itself through its `exports`: {}",
+          path.display()
+        ),
+        None => "Not the package of the importing file".to_string(),
+      };
+      ResolveStep::new(ResolveStepKind::SelfReference, found.is_some(), message)
+    });
+    if let Some(found) = found {
+      return Ok(found);
+    }
+
+    let found = if self.workspaces {
+      resolve_workspace_import(
+        source,
+        file_path,
+        self.project_path,
+        conditions,
+        &self.main_fields,
+        &self.cache,
+      )
+    } else {
+      None
+    };
+    record(&mut self.trace, || {
+      if !self.workspaces {
+        let message = "Workspace packages aren't looked up".to_string();
+        return ResolveStep::new(ResolveStepKind::Workspace, false, message);
+      }
+      let message = match &found {
+        Some(path) => format!("A package of the workspace: {}", path.display()),
+        None => "Not a package of the workspace".to_string(),
+      };
+      ResolveStep::new(ResolveStepKind::Workspace, found.is_some(), message)
+    });
+    if let Some(found) = found {
+      return Ok(found);
+    }
+
+    let parent_dir = file_path.parent().unwrap().to_path_buf();
+    let manifest_path = find_pnp_manifest(&parent_dir, &self.cache);
+    let manifest = manifest_path
+      .as_ref()
+      .and_then(|x| load_pnp_manifest(x, &self.cache));
+    if let Some(manifest) = manifest {
+      let location =
+        resolve_pnp_import(source, file_path, &manifest, conditions, &self.main_fields);
+      record(&mut self.trace, || {
+        let message = match &location {
+          Some(PnpLocation::Archive(archive)) => {
+            format!("Inside the archive {}, it isn't watched", archive.display())
+          }
+          Some(PnpLocation::Directory(path)) => format!(
+            "Resolved through the Plug'n'Play manifest: {}",
+            path.display()
+          ),
+          None => "Not a dependency of the package in the Plug'n'Play manifest".to_string(),
+        };
+        ResolveStep::new(ResolveStepKind::PnpManifest, location.is_some(), message)
+          .with_candidates(&manifest_path.into_iter().collect::<Vec<PathBuf>>())
+      });
+      return match location {
+        Some(PnpLocation::Archive(archive)) => Err(ImportResolution::External(ExternalDep {
+          specifier: source.to_string(),
+          kind: ExternalKind::Archive,
+          path: Some(archive.to_string_lossy().to_string()),
+        })),
+        Some(PnpLocation::Directory(path)) => Ok(path),
+        None => Err(ImportResolution::Unresolved(
+          "Not a dependency of the package in the Plug'n'Play manifest".to_string(),
+        )),
+      };
+    }
+
+    let node_modules_dirs = find_node_modules_dirs(&parent_dir, &self.cache);
+    if node_modules_dirs.is_empty() {
+      let reason = "Couldn't find node_modules folder".to_string();
+      record(&mut self.trace, || {
+        ResolveStep::new(ResolveStepKind::NodeModules, false, reason.clone())
+      });
+      return Err(ImportResolution::Unresolved(reason));
+    }
+    let found = resolve_bare_import(
+      source,
+      conditions,
+      &self.main_fields,
+      &node_modules_dirs,
+      &mut self.trace,
+    );
+    found.ok_or_else(|| {
+      ImportResolution::Unresolved("Couldn't find the module in node_modules".to_string())
+    })
+  }
 }
 
-/// Take a path of a file without extension and resolve it's extension.
+/// Returns whether `specifier` designates a module that can't be on disk: an `http:` or
+/// `https:` URL, or a `data:` URI.
+fn is_remote_url(specifier: &str) -> bool {
+  ["http://", "https://", "data:"]
+    .iter()
+    .any(|scheme| specifier.starts_with(scheme))
+}
+
+/// Converts a `file:` URL to the path it designates.
 /// ```rs
-/// let path = PathBuf::from("/stuff/project/foo");
-/// let index_path = resolve_with_extension(&path).unwrap().to_str();
-/// // "/stuff/project/foo.js"
+/// file_url_to_path("file:///home/me/my%20project/a.js"); // Some("/home/me/my project/a.js")
 /// ```
-/// This will return the path of the first file that:
-/// * matches the file_name of the argument
-/// * possess an extension
-fn resolve_with_extension(path: &Path) -> Option<PathBuf> {
-  let file_name = path.file_name().unwrap();
-  let files = path.parent().unwrap().read_dir().unwrap();
-  for entry in files.flatten() {
-    let entry_path = entry.path();
-    if entry_path.is_file() {
-      if let Some(_ext) = entry_path.extension() {
-        let name = entry_path.file_stem().unwrap().to_str().unwrap();
-        if name.eq(file_name) {
-          return Some(entry.path());
-        }
+fn file_url_to_path(url: &str) -> Option<PathBuf> {
+  let rest = url.strip_prefix("file://")?;
+  // only the local host is supported
+  let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+  if !rest.starts_with('/') {
+    return None;
+  }
+  let path = percent_decode(rest.split(['?', '#']).next()?)?;
+  // `file:///C:/foo.js` on Windows
+  if cfg!(windows) && path.as_bytes().get(2) == Some(&b':') {
+    return Some(PathBuf::from(&path[1..]).clean());
+  }
+  Some(PathBuf::from(path).clean())
+}
+
+fn percent_decode(input: &str) -> Option<String> {
+  let bytes = input.as_bytes();
+  let mut output = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    if bytes[i] == b'%' {
+      output.push(u8::from_str_radix(input.get(i + 1..i + 3)?, 16).ok()?);
+      i += 3;
+    } else {
+      output.push(bytes[i]);
+      i += 1;
+    }
+  }
+  String::from_utf8(output).ok()
+}
+
+/// Resolves `specifier` as if `file_path` imported it with an import of `kind`, recording
+/// every step tried along the way.
+pub fn explain_resolve(
+  file_path: &Path,
+  specifier: &str,
+  kind: ImportKind,
+  project_path: &Path,
+  opts: &Option<MakeEntriesOptions>,
+) -> ResolveTrace {
+  let mut resolver = ImportResolver::new(file_path, project_path, opts);
+  resolver.trace = Some(Vec::new());
+  let specifier_string = specifier.to_string();
+  let import = match kind {
+    ImportKind::Esm => ImportDep::ESM(specifier_string),
+    ImportKind::Require => ImportDep::REQUIRE(specifier_string),
+    ImportKind::Css => ImportDep::CSS(specifier_string),
+  };
+  let resolution = resolver.resolve(&import);
+  let mut trace = ResolveTrace {
+    importer: file_path.to_string_lossy().to_string(),
+    specifier: specifier.to_string(),
+    steps: resolver.trace.take().unwrap_or_default(),
+    resolved: None,
+    external: None,
+    error: None,
+  };
+  match resolution {
+    ImportResolution::File(path, _) => trace.resolved = Some(path.to_string_lossy().to_string()),
+    ImportResolution::External(external) => trace.external = Some(external),
+    ImportResolution::Ignored => {}
+    ImportResolution::Unresolved(reason) => trace.error = Some(reason),
+  }
+  trace
+}
+
+/// Resolves an import of `file_path` to the file it designates, like `make_file_item` does.
+/// Returns `None` when it doesn't designate a file, like a builtin module.
+pub fn resolve_import(
+  file_path: &Path,
+  import: &ImportDep,
+  project_path: &Path,
+  opts: &Option<MakeEntriesOptions>,
+) -> Option<PathBuf> {
+  match ImportResolver::new(file_path, project_path, opts).resolve(import) {
+    ImportResolution::File(path, _) => Some(path),
+    _ => None,
+  }
+}
+
+/// Resolves an imported path to the file it designates, see `resolve_path_candidates`.
+fn resolve_path(path_buf: PathBuf, extensions: &[String]) -> Option<PathBuf> {
+  let candidates = resolve_path_candidates(&path_buf, extensions);
+  select_candidate(path_buf, candidates)
+}
+
+/// Returns the first candidate of an imported path. Without any, a path with a known extension
+/// is kept so that the file is tracked even though it's missing.
+fn select_candidate(path_buf: PathBuf, candidates: Vec<PathBuf>) -> Option<PathBuf> {
+  if let Some(candidate) = candidates.into_iter().next() {
+    return Some(candidate);
+  }
+  match path_buf.extension().and_then(|x| x.to_str()) {
+    Some(ext) if JS_EXTS.contains(&ext) || STYLE_EXTS.contains(&ext) => Some(path_buf),
+    _ => None,
+  }
+}
+
+/// Returns the candidates only differing from the first one by their extension, like
+/// `utils.ts` and `utils.js`. A `utils/index.js` doesn't collide with a `utils.js`, node
+/// always picks the file over the directory.
+fn get_colliding_candidates(candidates: Vec<PathBuf>) -> Vec<PathBuf> {
+  let first = match candidates.first() {
+    Some(first) => first.with_extension(""),
+    None => return candidates,
+  };
+  candidates
+    .into_iter()
+    .filter(|x| x.with_extension("") == first)
+    .collect()
+}
+
+/// Returns the existing files an imported path can designate, in the order node tries them:
+/// * the path itself when it's a file, which is then the only candidate
+/// * the path followed by an extension, see `resolve_with_extension`
+/// * if the imported path is a directory, its index file, following the same order
+fn resolve_path_candidates(path_buf: &Path, extensions: &[String]) -> Vec<PathBuf> {
+  if path_buf.is_file() {
+    return vec![path_buf.to_path_buf()];
+  }
+  let mut candidates = resolve_with_extension(path_buf, extensions);
+  if path_buf.is_dir() {
+    candidates.extend(resolve_with_extension(&path_buf.join("index"), extensions));
+  }
+  candidates
+}
+
+/// Returns the real path of `path`, so that a file reached through several symlinks (pnpm
+/// stores, workspace packages) is a single item, resolving its own imports from its real
+/// location. Missing files keep their name inside their real parent directory.
+/// Returns `None` when the path can't be resolved at all, like for symlink loops.
+pub(crate) fn canonicalize_path(path: PathBuf) -> Option<PathBuf> {
+  match path.canonicalize() {
+    Ok(real_path) => Some(real_path),
+    Err(err) if err.kind() != std::io::ErrorKind::NotFound => None,
+    Err(_) => {
+      let real_parent = path.parent().and_then(|x| x.canonicalize().ok());
+      match (real_parent, path.file_name()) {
+        (Some(real_parent), Some(file_name)) => Some(real_parent.join(file_name)),
+        _ => Some(path),
       }
     }
   }
-  None
 }
 
-/// Resolves the index file that matches the path send as parameter.
-/// ```rs
-/// let path = PathBuf::from("/stuff/project/foo");
-/// let index_path = resolve_index(&path).unwrap().to_str();
-/// // "/stuff/project/foo/index.ts"
+/// Returns the user aliases followed by the built-in ones, unless overridden:
+/// * `~` points to the project's path
+fn get_aliases(opts: &Option<MakeEntriesOptions>, project_path: &Path) -> Vec<ResolveAlias> {
+  let mut aliases = opts
+    .as_ref()
+    .and_then(|x| x.aliases.clone())
+    .unwrap_or_default();
+  if !aliases.iter().any(|x| x.find == "~") {
+    aliases.push(ResolveAlias {
+      find: "~".to_string(),
+      replacements: vec![project_path.to_str().unwrap().to_string()],
+    });
+  }
+  aliases
+}
+
+/// Resolves an import through the first alias matching it.
+/// Replacements are tried in order and the first one pointing to an existing file wins.
+/// If none does, the first replacement is returned so that the missing file is still tracked.
+fn resolve_alias(
+  source: &str,
+  aliases: &[ResolveAlias],
+  project_path: &Path,
+  extensions: &[String],
+) -> Option<PathBuf> {
+  let candidates: Vec<PathBuf> = aliases.iter().find_map(|alias| {
+    let replace = |rest: &str| -> Vec<PathBuf> {
+      alias
+        .replacements
+        .iter()
+        .map(|x| project_path.join(x).join(rest).clean())
+        .collect()
+    };
+    if let Some(exact) = alias.find.strip_suffix('$') {
+      (source == exact).then(|| replace(""))
+    } else if let Some((prefix, suffix)) = alias.find.split_once('*') {
+      let captured = source.strip_prefix(prefix)?.strip_suffix(suffix)?;
+      Some(
+        alias
+          .replacements
+          .iter()
+          .map(|x| project_path.join(x.replacen('*', captured, 1)).clean())
+          .collect(),
+      )
+    } else if source == alias.find {
+      Some(replace(""))
+    } else {
+      let rest = source.strip_prefix(&alias.find)?.strip_prefix('/')?;
+      Some(replace(rest))
+    }
+  })?;
+
+  candidates
+    .iter()
+    .find(|candidate| !resolve_path_candidates(candidate, extensions).is_empty())
+    .cloned()
+    .or_else(|| candidates.into_iter().next())
+}
+
+/// Resolves a bare import through the `compilerOptions.paths` and `baseUrl` of the closest
+/// tsconfig (or jsconfig) of the importing file.
+/// Every candidate is tried in order, the first one pointing to an existing file wins.
+fn resolve_tsconfig_paths(
+  source: &str,
+  file_path: &Path,
+  tsconfig: &mut Option<Option<Arc<TsConfig>>>,
+  extensions: &[String],
+  cache: &ResolveCache,
+) -> Option<PathBuf> {
+  let config = tsconfig
+    .get_or_insert_with(|| {
+      find_tsconfig(file_path.parent()?, cache).and_then(|x| load_tsconfig(&x, cache))
+    })
+    .as_ref()?;
+
+  config
+    .path_candidates(source)
+    .into_iter()
+    .chain(config.base_url_candidate(source))
+    .find(|candidate| !resolve_path_candidates(candidate, extensions).is_empty())
+}
+
+/// Take a path of a file without extension and returns the files it can designate.
+/// ```rs
+/// let path = PathBuf::from("/stuff/project/foo");
+/// let paths = resolve_with_extension(&path, &extensions);
+/// // ["/stuff/project/foo.ts", "/stuff/project/foo.js", "/stuff/project/foo.yml"]
+/// ```
+/// These are the files that match the file_name of the argument and possess an extension:
+/// first the ones of `extensions` in that order, then the others by alphabetical order.
+fn resolve_with_extension(path: &Path, extensions: &[String]) -> Vec<PathBuf> {
+  let (file_name, parent) = match (path.file_name(), path.parent()) {
+    (Some(file_name), Some(parent)) => (file_name, parent),
+    _ => return Vec::new(),
+  };
+  let files = match parent.read_dir() {
+    Ok(files) => files,
+    Err(_) => return Vec::new(),
+  };
+  let mut candidates: Vec<(usize, PathBuf)> = files
+    .flatten()
+    .map(|entry| entry.path())
+    .filter(|entry_path| entry_path.file_stem() == Some(file_name) && entry_path.is_file())
+    .filter_map(|entry_path| {
+      let ext = entry_path.extension()?.to_str()?;
+      let rank = extensions
+        .iter()
+        .position(|x| x.trim_start_matches('.') == ext)
+        .unwrap_or(extensions.len());
+      Some((rank, entry_path))
+    })
+    .collect();
+  candidates.sort();
+  candidates.into_iter().map(|(_, x)| x).collect()
+}
+
+/// Returns every `node_modules` folder an import from `dir` can be resolved in, closest first.
+/// Like node, `node_modules/node_modules` is never looked up.
+pub fn find_node_modules_dirs(dir: &Path, cache: &ResolveCache) -> Vec<PathBuf> {
+  cache.node_modules_dirs.get_or_insert_with(dir, || {
+    let mut dirs = match dir.parent() {
+      Some(parent) => find_node_modules_dirs(parent, cache),
+      None => Vec::new(),
+    };
+    let candidate = dir.join("node_modules");
+    if !dir.ends_with("node_modules") && candidate.is_dir() {
+      dirs.insert(0, candidate);
+    }
+    dirs
+  })
+}
+
+/// Returns the extensions tried for imports without one, in order.
+fn get_extensions(opts: &Option<MakeEntriesOptions>) -> Vec<String> {
+  opts
+    .as_ref()
+    .and_then(|x| x.extensions.clone())
+    .unwrap_or_else(|| DEFAULT_EXTENSIONS.into_iter().map(String::from).collect())
+}
+
+/// Returns the package.json fields pointing to the entry point of a package, in the order
+/// they are tried. Defaults to `["main"]`.
+fn get_main_fields(opts: &Option<MakeEntriesOptions>) -> Vec<String> {
+  opts
+    .as_ref()
+    .and_then(|x| x.main_fields.clone())
+    .unwrap_or_else(|| vec!["main".to_string()])
+}
+
+/// Returns the conditions matched against the `exports` and `imports` fields for this import:
+/// the configured ones (`node` by default) followed by `import` or `require`.
+/// `default` always matches.
+fn get_conditions(import: &ImportDep, opts: &Option<MakeEntriesOptions>) -> Vec<String> {
+  let mut conditions = opts
+    .as_ref()
+    .and_then(|x| x.conditions.clone())
+    .unwrap_or_else(|| vec!["node".to_string()]);
+  let kind = match import {
+    ImportDep::REQUIRE(_) => "require",
+    ImportDep::ESM(_) | ImportDep::CSS(_) => "import",
+  };
+  if !conditions.iter().any(|x| x == kind) {
+    conditions.push(kind.to_string());
+  }
+  conditions
+}
+
+fn get_external_mode(opts: &Option<MakeEntriesOptions>) -> ExternalMode {
+  opts
+    .as_ref()
+    .and_then(|x| x.external_mode)
+    .unwrap_or(ExternalMode::Walk)
+}
+
+/// Returns the directory of the package containing `path` when it's inside `node_modules`.
+/// ```rs
+/// get_package_root("/project/node_modules/@scope/foo/lib/index.js"); // Some("/project/node_modules/@scope/foo")
+/// get_package_root("/project/src/index.js"); // None
+/// ```
+pub fn get_package_root(path: &Path) -> Option<PathBuf> {
+  let components: Vec<_> = path.components().collect();
+  let index = components
+    .iter()
+    .rposition(|x| x.as_os_str() == "node_modules")?;
+  let scoped = components
+    .get(index + 1)?
+    .as_os_str()
+    .to_str()?
+    .starts_with('@');
+  let end = index + if scoped { 2 } else { 1 };
+  // `path` has to be a file of the package
+  if end + 1 >= components.len() {
+    return None;
+  }
+  Some(components[..=end].iter().collect())
+}
+
+/// Returns the name of the package living in `pkg_dir`, from its path.
+/// ```rs
+/// get_package_name("/project/node_modules/@scope/foo"); // Some("@scope/foo")
+/// ```
+pub fn get_package_name(pkg_dir: &Path) -> Option<String> {
+  let name = pkg_dir.file_name()?.to_str()?;
+  match pkg_dir.parent()?.file_name()?.to_str()? {
+    scope if scope.starts_with('@') => Some(format!("{}/{}", scope, name)),
+    _ => Some(name.to_string()),
+  }
+}
+
+/// Splits a bare import into the package name and the subpath inside the package.
+/// ```rs
+/// split_package_specifier("@scope/foo/bar"); // Some(("@scope/foo", "./bar"))
+/// split_package_specifier("foo"); // Some(("foo", "."))
+/// ```
+fn split_package_specifier(specifier: &str) -> Option<(&str, String)> {
+  let mut separators = specifier.match_indices('/').map(|(i, _)| i);
+  let name_end = if specifier.starts_with('@') {
+    // a scoped package needs a name after its scope
+    separators.next()?;
+    separators.next()
+  } else {
+    separators.next()
+  }
+  .unwrap_or(specifier.len());
+  let name = &specifier[..name_end];
+  if name.is_empty() || name.ends_with('/') {
+    return None;
+  }
+  Some((name, format!(".{}", &specifier[name_end..])))
+}
+
+fn read_package_json(path: &Path) -> Option<serde_json::Value> {
+  let content = std::fs::read(path).ok()?;
+  serde_json::from_slice(&content).ok()
+}
+
+/// Resolves a bare import in the first of `node_modules_dirs` containing its package.
+fn resolve_bare_import(
+  module: &str,
+  conditions: &[String],
+  main_fields: &[String],
+  node_modules_dirs: &[PathBuf],
+  trace: &mut Option<Vec<ResolveStep>>,
+) -> Option<PathBuf> {
+  // The package folders looked up, until the one that exists
+  let mut visited = Vec::new();
+  let package = split_package_specifier(module).and_then(|(name, subpath)| {
+    let pkg_dir = node_modules_dirs
+      .iter()
+      .map(|dir| dir.join(name))
+      .find(|pkg_dir| {
+        visited.push(pkg_dir.clone());
+        pkg_dir.is_dir()
+      })?;
+    Some((pkg_dir, subpath))
+  });
+  record(trace, || {
+    let message = match &package {
+      Some((pkg_dir, _)) => format!("Found the package at {}", pkg_dir.display()),
+      None => "Couldn't find the module in node_modules".to_string(),
+    };
+    ResolveStep::new(ResolveStepKind::NodeModules, package.is_some(), message)
+      .with_candidates(&visited)
+  });
+  let (pkg_dir, subpath) = package?;
+  resolve_package(&pkg_dir, &subpath, conditions, main_fields, trace)
+}
+
+/// Resolves a package importing itself by name, like `my-lib/utils` from inside `my-lib`.
+/// As for node, this only works through the `exports` of the package.
+fn resolve_self_reference(
+  module: &str,
+  file_path: &Path,
+  conditions: &[String],
+  cache: &ResolveCache,
+) -> Option<PathBuf> {
+  let (name, subpath) = split_package_specifier(module)?;
+  let pkg_path = find_package_json(file_path.parent()?, cache)?;
+  let json = read_package_json(&pkg_path)?;
+  if json["name"].as_str()? != name || json["exports"].is_null() {
+    return None;
+  }
+  match resolve_package_exports(pkg_path.parent()?, &subpath, &json["exports"], conditions) {
+    PackageTarget::Path(path, _) => Some(path),
+    _ => None,
+  }
+}
+
+/// Resolves a bare import to a package of the workspace of the importing file, whether it's
+/// installed in `node_modules` or not. Only workspaces inside the project are looked up.
+fn resolve_workspace_import(
+  module: &str,
+  file_path: &Path,
+  project_path: &Path,
+  conditions: &[String],
+  main_fields: &[String],
+  cache: &ResolveCache,
+) -> Option<PathBuf> {
+  let (name, subpath) = split_package_specifier(module)?;
+  let packages = find_workspace_packages(file_path.parent()?, project_path, cache)?;
+  resolve_package(
+    packages.get(name)?,
+    &subpath,
+    conditions,
+    main_fields,
+    &mut None,
+  )
+}
+
+/// Resolves a bare import through a Yarn Plug'n'Play manifest.
+/// Packages only available inside an archive can't be read, the archive is returned instead.
+fn resolve_pnp_import(
+  module: &str,
+  file_path: &Path,
+  manifest: &PnpManifest,
+  conditions: &[String],
+  main_fields: &[String],
+) -> Option<PnpLocation> {
+  let (name, subpath) = split_package_specifier(module)?;
+  match manifest.resolve_package(name, file_path)? {
+    PnpLocation::Directory(pkg_dir) => {
+      resolve_package(&pkg_dir, &subpath, conditions, main_fields, &mut None)
+        .map(PnpLocation::Directory)
+    }
+    archive => Some(archive),
+  }
+}
+
+/// Resolves `subpath` (`.` or `./foo`) inside the package at `pkg_dir`:
+/// * through `exports` when the package has one. Nothing else is reachable then
+/// * otherwise through the first of `main_fields` holding a path for the package itself, and
+///   relatively to the package for subpaths
+fn resolve_package(
+  pkg_dir: &Path,
+  subpath: &str,
+  conditions: &[String],
+  main_fields: &[String],
+  trace: &mut Option<Vec<ResolveStep>>,
+) -> Option<PathBuf> {
+  // maybe the module is an internal node_modules, which doesn't reside inside the project
+  // node_modules folder
+  if !pkg_dir.is_dir() {
+    return None;
+  }
+  let pkg_path = pkg_dir.join("package.json");
+  let json = read_package_json(&pkg_path).unwrap_or_default();
+  let mut record_package = |matched: bool, message: String| {
+    record(trace, || {
+      ResolveStep::new(ResolveStepKind::PackageJson, matched, message)
+        .with_candidates(std::slice::from_ref(&pkg_path))
+    })
+  };
+
+  let exports = &json["exports"];
+  if !exports.is_null() {
+    let target = resolve_package_exports(pkg_dir, subpath, exports, conditions);
+    let message = match &target {
+      PackageTarget::Path(path, matched) if matched.is_empty() => {
+        format!("`{}` is exported as {}", subpath, path.display())
+      }
+      PackageTarget::Path(path, matched) => format!(
+        "`{}` is exported as {} under the condition {}",
+        subpath,
+        path.display(),
+        matched.join(" > ")
+      ),
+      PackageTarget::Excluded => format!("`{}` is excluded from `exports`", subpath),
+      PackageTarget::Unmatched => format!(
+        "`{}` isn't in `exports` for the conditions {}",
+        subpath,
+        conditions.join(", ")
+      ),
+      PackageTarget::Bare(_) | PackageTarget::Invalid => {
+        format!("The target of `{}` in `exports` is invalid", subpath)
+      }
+    };
+    return match target {
+      PackageTarget::Path(path, _) => {
+        record_package(true, message);
+        Some(path)
+      }
+      _ => {
+        record_package(false, message);
+        None
+      }
+    };
+  }
+
+  if subpath == "." {
+    // If we have "main": "./foo.js". The object form of "browser" is a remapping, not a path
+    return match main_fields
+      .iter()
+      .find_map(|field| Some((field, json[field].as_str()?)))
+    {
+      Some((field, main)) => {
+        record_package(
+          true,
+          format!(
+            "No `exports`, using `{}`, the first defined of the main fields {}",
+            field,
+            main_fields.join(", ")
+          ),
+        );
+        Some(pkg_dir.join(main).clean())
+      }
+      None => {
+        record_package(
+          true,
+          format!(
+            "No `exports` nor main field among {}, using the index file",
+            main_fields.join(", ")
+          ),
+        );
+        Some(pkg_dir.to_path_buf())
+      }
+    };
+  }
+  record_package(
+    true,
+    format!("No `exports`, `{}` is relative to the package", subpath),
+  );
+  Some(pkg_dir.join(subpath).clean())
+}
+
+/// Resolves a `#internal` import through the `imports` field of the closest package.json of
+/// the importing file.
+/// ```json
+/// "imports": { "#utils/*": "./src/utils/*.js", "#dep": "some-package" }
 /// ```
-fn resolve_index(path: &Path) -> Option<PathBuf> {
-  if !path.is_dir() {
+fn resolve_package_imports(
+  specifier: &str,
+  file_path: &Path,
+  conditions: &[String],
+  main_fields: &[String],
+  cache: &ResolveCache,
+) -> Option<PathBuf> {
+  if specifier == "#" || specifier.starts_with("#/") {
     return None;
   }
-  let files = path.read_dir().unwrap();
-  for entry in files.flatten() {
-    let entry_path = entry.path();
-    if entry_path.is_file() {
-      if let Some(_ext) = entry_path.extension() {
-        let name = entry_path.file_stem().unwrap().to_str().unwrap();
-        if name.eq("index") {
-          return Some(entry.path());
-        }
-      }
-    }
+  let pkg_path = find_package_json(file_path.parent()?, cache)?;
+  let pkg_dir = pkg_path.parent()?;
+  let json = read_package_json(&pkg_path)?;
+  let imports = json["imports"].as_object()?;
+
+  match resolve_imports_exports(specifier, imports, pkg_dir, true, conditions) {
+    PackageTarget::Path(path, _) => Some(path),
+    PackageTarget::Bare(module) => resolve_bare_import(
+      &module,
+      conditions,
+      main_fields,
+      &find_node_modules_dirs(pkg_dir, cache),
+      &mut None,
+    ),
+    _ => None,
   }
-  None
 }
 
-// TODO: handle error
-#[memoize]
-pub fn find_node_modules_dir(root: PathBuf) -> Option<PathBuf> {
-  let mut counter: u8 = 0;
-  let mut work_fn = move || {
-    if counter >= 100 {
-      return None;
+/// Outcome of looking an import up in the object form of the `browser` field
+/// ```json
+/// "browser": { "./lib/node.js": "./lib/browser.js", "fs": false, "foo": "bar" }
+/// ```
+#[derive(Debug, PartialEq)]
+enum BrowserMapping {
+  /// `false`: the import is replaced by an empty module, there is nothing to watch
+  Ignored,
+  Path(PathBuf),
+  /// Another bare import, resolved instead of the original one
+  Module(String),
+}
+
+/// Returns the directory and the object form of the `browser` field of the closest
+/// package.json from `dir`.
+fn get_browser_field(dir: &Path, cache: &ResolveCache) -> Option<(PathBuf, Arc<BrowserField>)> {
+  let pkg_dir = find_package_json(dir, cache)?.parent()?.to_path_buf();
+  let browser = cache.browser_fields.get_or_insert_with(&pkg_dir, || {
+    match read_package_json(&pkg_dir.join("package.json"))?
+      .get_mut("browser")?
+      .take()
+    {
+      serde_json::Value::Object(browser) => Some(Arc::new(browser)),
+      _ => None,
     }
-    for entry in root.read_dir().unwrap().flatten() {
-      if entry.file_name().eq("node_modules") {
-        return Some(entry.path());
-      }
+  })?;
+  Some((pkg_dir, browser))
+}
+
+fn get_browser_mapping(pkg_dir: &Path, value: &serde_json::Value) -> Option<BrowserMapping> {
+  match value {
+    serde_json::Value::Bool(false) => Some(BrowserMapping::Ignored),
+    serde_json::Value::String(x) if x.starts_with("./") || x.starts_with("../") => {
+      Some(BrowserMapping::Path(pkg_dir.join(x).clean()))
     }
-    counter += 1;
-    return find_node_modules_dir(root.parent().unwrap().to_path_buf());
-  };
+    serde_json::Value::String(x) => Some(BrowserMapping::Module(x.to_string())),
+    _ => None,
+  }
+}
 
-  work_fn()
-}
-
-fn resolve_node_module(module: &str, import: &ImportDep, node_modules: &Path) -> Option<PathBuf> {
-  let mut module_path = PathBuf::new();
-  let components = Path::new(module).components();
-  let components_count = components.count();
-  let root = Path::new(module).components().next().unwrap().as_os_str();
-  let root_pkg_dir = node_modules.join(root);
-
-  fn handle_export_value<'a>(
-    value: &'a serde_json::Value,
-    import: &'a ImportDep,
-  ) -> Option<&'a str> {
-    if value.is_string() {
-      return Some(value.as_str().unwrap());
-    } else if value.is_array() {
-      for value in value.as_array().unwrap().iter() {
-        if let Some(res) = handle_export_value(value, import) {
-          return Some(res);
-        }
-      }
+/// Looks a bare import up in the `browser` field of the package of the importing file.
+fn remap_browser_module(
+  source: &str,
+  file_path: &Path,
+  cache: &ResolveCache,
+) -> Option<BrowserMapping> {
+  let (pkg_dir, browser) = get_browser_field(file_path.parent()?, cache)?;
+  get_browser_mapping(&pkg_dir, browser.get(source)?)
+}
+
+/// Looks a resolved file up in the `browser` field of its package, with or without its
+/// extension. A file can only be replaced by another file or ignored.
+fn remap_browser_file(path: &Path, cache: &ResolveCache) -> Option<BrowserMapping> {
+  let (pkg_dir, browser) = get_browser_field(path.parent()?, cache)?;
+  let relative = path.strip_prefix(&pkg_dir).ok()?;
+  [relative.to_path_buf(), relative.with_extension("")]
+    .iter()
+    .find_map(|x| browser.get(&format!("./{}", x.to_str()?)))
+    .and_then(|value| get_browser_mapping(&pkg_dir, value))
+    .filter(|x| !matches!(x, BrowserMapping::Module(_)))
+}
+
+/// Finds the closest `package.json` from `dir`, walking up the parents.
+pub fn find_package_json(dir: &Path, cache: &ResolveCache) -> Option<PathBuf> {
+  cache.package_jsons.get_or_insert_with(dir, || {
+    let candidate = dir.join("package.json");
+    if candidate.is_file() {
+      return Some(candidate);
     }
-    // ".": { import: "./foo.js", default: "./bar.js" }
-    else if value.is_object() {
-      let mapping = value.as_object().unwrap();
-      let first_match = mapping
-        .get(mapping.keys().into_iter().next().unwrap())
-        .unwrap();
-      for (type_, value) in mapping.into_iter() {
-        if (type_ == "import" && matches!(import, ImportDep::ESM(_)))
-          || (type_ == "require" && matches!(import, ImportDep::REQUIRE(_)))
-          || (type_ == "default")
-        {
-          return Some(value.as_str().unwrap());
-        }
+    find_package_json(dir.parent()?, cache)
+  })
+}
+
+/// Outcome of resolving a target of the `exports` or `imports` fields
+#[derive(Debug, PartialEq)]
+enum PackageTarget {
+  /// The path of the target, with the nested conditions leading to it
+  Path(PathBuf, Vec<String>),
+  /// The target is `null`: the subpath is explicitly not exported
+  Excluded,
+  /// None of the conditions matched
+  Unmatched,
+  /// The target is a bare import to resolve from the package, only valid for `imports`
+  Bare(String),
+  /// The target is malformed, like `../foo.js` or `./node_modules/foo.js`
+  Invalid,
+}
+
+/// Follows node's `PACKAGE_EXPORTS_RESOLVE`.
+fn resolve_package_exports(
+  pkg_dir: &Path,
+  subpath: &str,
+  exports: &serde_json::Value,
+  conditions: &[String],
+) -> PackageTarget {
+  // "exports": { ".": "./foo.js", "./bar": "./bar.js" } as opposed to
+  // "exports": { "import": "./foo.js" }, "exports": "./foo.js" or "exports": ["./foo.js"]
+  let subpaths = match exports.as_object() {
+    Some(mapping) => {
+      let dotted = mapping.keys().filter(|x| x.starts_with('.')).count();
+      if dotted != 0 && dotted != mapping.len() {
+        return PackageTarget::Invalid;
       }
-      // normally, this should crash like node's `require.resolve` but I don't want this
-      return Some(first_match.as_str().unwrap());
+      (dotted != 0).then_some(mapping)
     }
-    None
+    None => None,
+  };
+
+  match (subpath, subpaths) {
+    (".", None) => resolve_package_target(pkg_dir, exports, None, false, conditions),
+    (_, Some(mapping)) => resolve_imports_exports(subpath, mapping, pkg_dir, false, conditions),
+    _ => PackageTarget::Unmatched,
   }
+}
 
-  for (i, comp) in Path::new(module).components().into_iter().enumerate() {
-    if comp == Component::RootDir {
-      continue;
-    }
-    let root = comp.as_os_str().to_str().unwrap();
-    module_path = module_path.join(root);
-    let pkg_path = node_modules.join(module_path.clone()).join("package.json");
-    // maybe the module is an internal node_modules, which doesn't reside inside the project
-    // node_modules folder
-    if !pkg_path.exists() {
-      return None;
+/// Follows node's `PACKAGE_IMPORTS_EXPORTS_RESOLVE`: `match_key` is looked up in `mapping`,
+/// either as is or through the most specific pattern like `./features/*.js`.
+fn resolve_imports_exports(
+  match_key: &str,
+  mapping: &serde_json::Map<String, serde_json::Value>,
+  pkg_dir: &Path,
+  is_imports: bool,
+  conditions: &[String],
+) -> PackageTarget {
+  if !match_key.contains('*') {
+    if let Some(target) = mapping.get(match_key) {
+      return resolve_package_target(pkg_dir, target, None, is_imports, conditions);
     }
-    let pkg_dir = node_modules.join(module_path.clone());
-    let json_content = std::fs::read(pkg_path).unwrap();
-    let json: serde_json::Value = serde_json::from_slice(&json_content).unwrap();
-
-    let exports = &json["exports"];
+  }
 
-    // If we have "exports": "./foo.js"
-    if exports.is_string() {
-      return Some(pkg_dir.join(exports.as_str().unwrap()).clean());
+  let mut patterns: Vec<&String> = mapping
+    .keys()
+    .filter(|x| x.matches('*').count() == 1)
+    .collect();
+  // the longest prefix wins, then the longest pattern
+  patterns.sort_by(|a, b| {
+    let base_a = a.find('*').unwrap();
+    let base_b = b.find('*').unwrap();
+    base_b.cmp(&base_a).then_with(|| b.len().cmp(&a.len()))
+  });
+  for pattern in patterns {
+    let (base, trailer) = pattern.split_once('*').unwrap();
+    if match_key.starts_with(base)
+      && match_key != base
+      && match_key.ends_with(trailer)
+      && match_key.len() >= pattern.len()
+    {
+      let captured = &match_key[base.len()..match_key.len() - trailer.len()];
+      return resolve_package_target(
+        pkg_dir,
+        &mapping[pattern],
+        Some(captured),
+        is_imports,
+        conditions,
+      );
     }
+  }
 
-    // If we have "exports": ["./foo.js", "./bar.js"]
-    if exports.is_array() {
-      for value in exports.as_array().unwrap().iter() {
-        if let Some(res) = handle_export_value(value, import) {
-          return Some(pkg_dir.join(res).clean());
-        }
+  PackageTarget::Unmatched
+}
+
+/// Follows node's `PACKAGE_TARGET_RESOLVE`.
+/// ```json
+/// "./foo.js"
+/// { "import": "./foo.mjs", "default": "./foo.js" }
+/// [{ "import": "./foo.mjs" }, "./foo.js"]
+/// null
+/// ```
+fn resolve_package_target(
+  pkg_dir: &Path,
+  target: &serde_json::Value,
+  pattern_match: Option<&str>,
+  is_imports: bool,
+  conditions: &[String],
+) -> PackageTarget {
+  match target {
+    serde_json::Value::String(value) => {
+      let is_invalid_segment = |x: &str| x == "." || x == ".." || x == "node_modules";
+      // "imports": { "#dep": "some-package" }
+      if is_imports
+        && !value.starts_with("./")
+        && !value.starts_with("../")
+        && !value.starts_with('/')
+        && !value.contains(':')
+      {
+        return match pattern_match {
+          Some(captured) => PackageTarget::Bare(value.replace('*', captured)),
+          None => PackageTarget::Bare(value.to_string()),
+        };
+      }
+      if !value.starts_with("./") || value.split('/').skip(1).any(is_invalid_segment) {
+        return PackageTarget::Invalid;
       }
+      let value = match pattern_match {
+        Some(captured) => {
+          if captured.split('/').any(is_invalid_segment) {
+            return PackageTarget::Invalid;
+          }
+          value.replace('*', captured)
+        }
+        None => value.to_string(),
+      };
+      PackageTarget::Path(pkg_dir.join(value).clean(), Vec::new())
     }
-
-    // If we have "exports": {}
-    if exports.is_object() {
-      // transforms module to a relative path
-      // foo     => .
-      // foo/bar => ./bar
-      let relative = module.replacen(module_path.to_str().unwrap(), ".", 1);
-      for (key, value) in exports.as_object().unwrap().into_iter() {
-        if key.eq(&relative) {
-          if let Some(res) = handle_export_value(value, import) {
-            return Some(pkg_dir.join(res).clean());
+    serde_json::Value::Object(mapping) => {
+      for (condition, value) in mapping {
+        if condition == "default" || conditions.contains(condition) {
+          match resolve_package_target(pkg_dir, value, pattern_match, is_imports, conditions) {
+            PackageTarget::Unmatched => continue,
+            PackageTarget::Path(path, mut matched) => {
+              matched.insert(0, condition.to_string());
+              return PackageTarget::Path(path, matched);
+            }
+            res => return res,
           }
-          panic!("failed to handle exports field for module {}", module);
         }
       }
+      PackageTarget::Unmatched
     }
-
-    if i != (components_count - 1) {
-      continue;
-    }
-
-    // If we have "main": "./foo.js"
-    if json["main"].is_string() {
-      return Some(root_pkg_dir.join(json["main"].as_str().unwrap()).clean());
+    serde_json::Value::Array(values) => {
+      // fallbacks: the first valid one is used
+      let mut res = PackageTarget::Excluded;
+      for value in values {
+        res = resolve_package_target(pkg_dir, value, pattern_match, is_imports, conditions);
+        match res {
+          PackageTarget::Invalid | PackageTarget::Unmatched => continue,
+          _ => return res,
+        }
+      }
+      match res {
+        PackageTarget::Unmatched => PackageTarget::Excluded,
+        res => res,
+      }
     }
+    serde_json::Value::Null => PackageTarget::Excluded,
+    _ => PackageTarget::Invalid,
   }
-
-  None
 }
 
 #[cfg(test)]
 mod tests {
+  #[cfg(unix)]
+  use crate::test_utils::{copy_fixture, copy_fixture_with_links};
   use crate::{
-    entry::{make_entries, make_file_item, resolve_with_extension},
+    entry::{
+      explain_resolve, make_entries, make_file_item, resolve_with_extension, ExternalMode,
+      MakeEntriesOptions, ResolveAlias, ResolveStepKind,
+    },
+    file_item::{DiagnosticKind, ExternalDep, ExternalKind, ImportKind},
     parser::ImportDep,
+    resolve_cache::ResolveCache,
   };
   use dashmap::DashMap;
   use lazy_static::lazy_static;
+  use std::collections::HashSet;
   use std::path::PathBuf;
   use std::string::String;
 
-  use super::{find_node_modules_dir, resolve_node_module};
+  use super::{
+    file_url_to_path, find_node_modules_dirs, get_browser_field, get_conditions, get_extensions,
+    get_main_fields, resolve_bare_import,
+  };
+
+  /// The links pnpm makes between the packages of its store, `src/loop.js` is a loop
+  #[cfg(unix)]
+  const PNPM_LINKS: [(&str, &str); 5] = [
+    ("node_modules/foo", ".pnpm/foo@1.0.0/node_modules/foo"),
+    (
+      "node_modules/.pnpm/foo@1.0.0/node_modules/bar",
+      "../../bar@2.0.0/node_modules/bar",
+    ),
+    ("node_modules/@acme/ui", "../../packages/ui"),
+    (
+      "packages/ui/node_modules/foo",
+      "../../../node_modules/.pnpm/foo@1.0.0/node_modules/foo",
+    ),
+    ("src/loop.js", "loop.js"),
+  ];
 
   lazy_static! {
     static ref CWD: PathBuf = PathBuf::from(std::env::current_dir().unwrap());
     static ref PROJECT_A_PATH: PathBuf = CWD.join("tests").join("fixtures").join("project_a");
     static ref THREEJS_PATH: PathBuf = CWD.join("tests").join("fixtures").join("three_js");
+    static ref TSCONFIG_PATHS_PATH: PathBuf =
+      CWD.join("tests").join("fixtures").join("tsconfig_paths");
+    static ref ALIASES_PATH: PathBuf = CWD.join("tests").join("fixtures").join("aliases");
+    static ref PACKAGE_IMPORTS_PATH: PathBuf =
+      CWD.join("tests").join("fixtures").join("package_imports");
+    static ref BROWSER_FIELD_PATH: PathBuf =
+      CWD.join("tests").join("fixtures").join("browser_field");
+    static ref MONOREPO_PATH: PathBuf = CWD.join("tests").join("fixtures").join("monorepo");
+    static ref YARN_PNP_PATH: PathBuf = CWD.join("tests").join("fixtures").join("yarn_pnp");
+    static ref EXTENSIONS_PATH: PathBuf = CWD.join("tests").join("fixtures").join("extensions");
+    static ref DIAGNOSTICS_PATH: PathBuf = CWD.join("tests").join("fixtures").join("diagnostics");
+    static ref BUILTINS_PATH: PathBuf = CWD.join("tests").join("fixtures").join("builtins");
+    static ref WORKSPACES_PATH: PathBuf = CWD.join("tests").join("fixtures").join("workspaces");
+    static ref URL_IMPORTS_PATH: PathBuf = CWD.join("tests").join("fixtures").join("url_imports");
   }
 
   #[test]
   fn test_resolve_with_extension() {
     let path = PROJECT_A_PATH.join("b");
 
-    let res = resolve_with_extension(&path).unwrap();
+    let res = resolve_with_extension(&path, &get_extensions(&None));
     assert_eq!(
-      res.to_str(),
+      res[0].to_str(),
       CWD
         .join("tests")
         .join("fixtures")
@@ -669,148 +2031,699 @@ mod tests {
   }
 
   #[test]
-  fn test_find_node_modules_dir() {
-    let expected = CWD.join("node_modules");
-    {
-      let result = find_node_modules_dir(CWD.clone()).unwrap();
+  fn make_user_file_extensions_order() {
+    let store = DashMap::new();
+    let path = EXTENSIONS_PATH.join("main.ts");
+
+    let res = make_file_item(&path, EXTENSIONS_PATH.as_path(), &store, &None).unwrap();
+    assert_eq!(res.deps.len(), 3usize);
+    for dep in ["Button.tsx", "utils.js", "c/index.ts"] {
+      assert!(res
+        .deps
+        .contains(EXTENSIONS_PATH.join(dep).to_str().unwrap()));
+    }
+    let mut ambiguous: Vec<&str> = res.diagnostics.iter().map(|x| &x.specifier[..]).collect();
+    ambiguous.sort();
+    // `utils.js` always wins over `utils/index.js`
+    assert_eq!(ambiguous, vec!["./Button", "./c"]);
+    assert!(res
+      .diagnostics
+      .iter()
+      .all(|x| x.kind == DiagnosticKind::AmbiguousImport));
+  }
 
-      assert_eq!(result.to_str().unwrap(), expected.to_str().unwrap());
+  #[test]
+  fn make_user_file_custom_extensions() {
+    let store = DashMap::new();
+    let path = EXTENSIONS_PATH.join("main.ts");
+    let opts = Some(MakeEntriesOptions {
+      extensions: Some(vec![".css".to_string(), ".js".to_string()]),
+      ..Default::default()
+    });
+
+    let res = make_file_item(&path, EXTENSIONS_PATH.as_path(), &store, &opts).unwrap();
+    for dep in ["Button.css", "utils.js", "c/index.js"] {
+      assert!(res
+        .deps
+        .contains(EXTENSIONS_PATH.join(dep).to_str().unwrap()));
     }
-    {
-      let result = find_node_modules_dir(CWD.join("src")).unwrap();
+  }
+
+  #[test]
+  fn make_user_file_unresolved_imports() {
+    let store = DashMap::new();
+    let path = DIAGNOSTICS_PATH.join("main.js");
 
-      assert_eq!(result.to_str().unwrap(), expected.to_str().unwrap());
+    let res = make_file_item(&path, DIAGNOSTICS_PATH.as_path(), &store, &None).unwrap();
+    // imports following the unresolved ones are still processed
+    assert_eq!(res.deps.len(), 2usize);
+    for dep in ["b.js", "c.js"] {
+      assert!(res
+        .deps
+        .contains(DIAGNOSTICS_PATH.join(dep).to_str().unwrap()));
     }
+    let unresolved: Vec<(&str, u32)> = res
+      .diagnostics
+      .iter()
+      .map(|x| {
+        assert_eq!(x.kind, DiagnosticKind::UnresolvedImport);
+        assert_eq!(x.importer, path.to_str().unwrap());
+        (&x.specifier[..], x.span.unwrap().line)
+      })
+      .collect();
+    assert_eq!(unresolved, vec![("./missing", 2), ("not-installed", 3)]);
   }
 
   #[test]
-  fn test_resolve_node_modules() {
-    let node_modules = CWD.join("tests/fixtures/fake_node_modules");
-    {
-      let result = resolve_node_module(
-        "exports_str",
-        &ImportDep::ESM("exports_str".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("exports_str/main.js"));
+  fn make_user_file_builtins() {
+    let store = DashMap::new();
+    let path = BUILTINS_PATH.join("main.js");
+
+    let res = make_file_item(&path, BUILTINS_PATH.as_path(), &store, &None).unwrap();
+    assert_eq!(
+      res.deps,
+      HashSet::from([BUILTINS_PATH
+        .join("helper.js")
+        .to_str()
+        .unwrap()
+        .to_string()])
+    );
+    let mut builtins: Vec<&str> = res
+      .externals
+      .iter()
+      .map(|x| {
+        assert_eq!(x.kind, ExternalKind::Builtin);
+        assert_eq!(x.path, None);
+        &x.specifier[..]
+      })
+      .collect();
+    builtins.sort_unstable();
+    assert_eq!(
+      builtins,
+      vec!["fs", "fs/promises", "node:crypto", "node:test", "path"]
+    );
+    assert!(res.diagnostics.is_empty());
+  }
+
+  #[test]
+  fn make_user_file_workspaces() {
+    let store = DashMap::new();
+    let path = WORKSPACES_PATH.join("main.js");
+
+    let res = make_file_item(&path, WORKSPACES_PATH.as_path(), &store, &None).unwrap();
+    // `@acme/ui` imports itself, and its sibling package. None of them is installed
+    assert_eq!(res.deps.len(), 3usize);
+    for dep in [
+      "packages/ui/src/index.js",
+      "packages/ui/src/button.js",
+      "packages/utils/index.js",
+    ] {
+      assert!(res
+        .deps
+        .contains(WORKSPACES_PATH.join(dep).to_str().unwrap()));
     }
-    {
-      let result = resolve_node_module(
-        "exports_obj",
-        &ImportDep::ESM("exports_obj".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("exports_obj/main.js"));
+    // excluded from the workspaces
+    let unresolved: Vec<&str> = res.diagnostics.iter().map(|x| &x.specifier[..]).collect();
+    assert_eq!(unresolved, vec!["@acme/ignored"]);
+
+    let store = DashMap::new();
+    let opts = Some(MakeEntriesOptions {
+      workspaces: Some(false),
+      ..Default::default()
+    });
+    let res = make_file_item(&path, WORKSPACES_PATH.as_path(), &store, &opts).unwrap();
+    assert!(res.deps.is_empty());
+    assert_eq!(res.diagnostics.len(), 2);
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn make_file_item_non_utf8_path() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let project_path = copy_fixture("diagnostics", "make_file_item_non_utf8_path");
+    // a file can only be reached through a non UTF-8 path by following a symlink
+    let real_path = project_path.join(OsStr::from_bytes(b"real\xff.js"));
+    std::fs::write(&real_path, "").unwrap();
+    std::os::unix::fs::symlink(&real_path, project_path.join("linked.js")).unwrap();
+    let path = project_path.join("non_utf8.js");
+    std::fs::write(&path, "import './linked.js';\n").unwrap();
+
+    let store = DashMap::new();
+    let res = make_file_item(&path, &project_path, &store, &None).unwrap();
+    assert!(res.imports.is_empty());
+    assert_eq!(res.diagnostics.len(), 1);
+    assert_eq!(res.diagnostics[0].kind, DiagnosticKind::UnresolvedImport);
+    assert!(res.diagnostics[0]
+      .message
+      .starts_with("The path isn't valid UTF-8"));
+  }
+
+  #[test]
+  fn make_user_file_url_imports() {
+    let store = DashMap::new();
+    let path = URL_IMPORTS_PATH.join("main.ts");
+
+    let res = make_file_item(&path, URL_IMPORTS_PATH.as_path(), &store, &None).unwrap();
+    assert_eq!(
+      res.deps,
+      HashSet::from([URL_IMPORTS_PATH
+        .join("local.ts")
+        .to_str()
+        .unwrap()
+        .to_string()])
+    );
+    let mut urls: Vec<&str> = res
+      .externals
+      .iter()
+      .map(|x| {
+        assert_eq!(x.kind, ExternalKind::Url);
+        &x.specifier[..]
+      })
+      .collect();
+    urls.sort_unstable();
+    assert_eq!(
+      urls,
+      vec![
+        "data:text/javascript,export default 42",
+        "http://deno.land/std/assert/mod.ts",
+        "https://esm.sh/react@18.2.0"
+      ]
+    );
+    assert!(res.diagnostics.is_empty());
+  }
+
+  #[test]
+  fn test_file_url_to_path() {
+    assert_eq!(
+      file_url_to_path("file:///home/me/my%20project/a.js?v=1"),
+      Some(PathBuf::from("/home/me/my project/a.js"))
+    );
+    assert_eq!(
+      file_url_to_path("file://localhost/home/me/a.js"),
+      Some(PathBuf::from("/home/me/a.js"))
+    );
+    assert_eq!(file_url_to_path("file://remote-host/a.js"), None);
+    assert_eq!(file_url_to_path("file:./a.js"), None);
+
+    let local = URL_IMPORTS_PATH.join("local");
+    let url = format!("file://{}", local.to_str().unwrap());
+    let trace = explain_resolve(
+      &URL_IMPORTS_PATH.join("main.ts"),
+      &url,
+      ImportKind::Esm,
+      URL_IMPORTS_PATH.as_path(),
+      &None,
+    );
+    assert_eq!(
+      trace.resolved.as_deref(),
+      URL_IMPORTS_PATH.join("local.ts").to_str()
+    );
+  }
+
+  #[test]
+  fn test_explain_resolve() {
+    let path = MONOREPO_PATH.join("packages/app/src/main.js");
+
+    let trace = explain_resolve(
+      &path,
+      "outer",
+      ImportKind::Esm,
+      MONOREPO_PATH.as_path(),
+      &None,
+    );
+    assert_eq!(
+      trace.resolved.as_deref(),
+      MONOREPO_PATH.join("node_modules/outer/index.js").to_str()
+    );
+    let kinds: Vec<ResolveStepKind> = trace.steps.iter().map(|x| x.kind).collect();
+    assert_eq!(
+      &kinds[kinds.len() - 3..],
+      [
+        ResolveStepKind::NodeModules,
+        ResolveStepKind::PackageJson,
+        ResolveStepKind::Extensions
+      ]
+    );
+    let node_modules = &trace.steps[kinds.len() - 3];
+    assert!(node_modules.matched);
+    assert_eq!(
+      node_modules.candidates,
+      [
+        MONOREPO_PATH.join("packages/app/node_modules/outer"),
+        MONOREPO_PATH.join("node_modules/outer")
+      ]
+      .iter()
+      .map(|x| x.to_str().unwrap())
+      .collect::<Vec<&str>>()
+    );
+    assert!(trace.steps[kinds.len() - 2].message.contains("`main`"));
+
+    let trace = explain_resolve(
+      &path,
+      "node:fs",
+      ImportKind::Esm,
+      MONOREPO_PATH.as_path(),
+      &None,
+    );
+    assert_eq!(trace.external.map(|x| x.kind), Some(ExternalKind::Builtin));
+
+    let trace = explain_resolve(
+      &path,
+      "not-installed",
+      ImportKind::Esm,
+      MONOREPO_PATH.as_path(),
+      &None,
+    );
+    assert_eq!(trace.resolved, None);
+    assert_eq!(
+      trace.error.as_deref(),
+      Some("Couldn't find the module in node_modules")
+    );
+    assert!(trace.steps.iter().all(|x| !x.matched));
+  }
+
+  #[test]
+  fn test_explain_resolve_exports() {
+    let path = MONOREPO_PATH.join("packages/app/src/main.js");
+    let dual_path = MONOREPO_PATH.join("node_modules/dual");
+
+    for (kind, file, condition) in [
+      (ImportKind::Esm, "index.mjs", "node > import"),
+      (ImportKind::Require, "index.cjs", "node > require"),
+    ] {
+      let trace = explain_resolve(&path, "dual", kind, MONOREPO_PATH.as_path(), &None);
+      assert_eq!(trace.resolved.as_deref(), dual_path.join(file).to_str());
+      let package = trace
+        .steps
+        .iter()
+        .find(|x| x.kind == ResolveStepKind::PackageJson)
+        .unwrap();
+      assert!(package.matched);
+      assert!(
+        package
+          .message
+          .ends_with(&format!("under the condition {}", condition)),
+        "{}",
+        package.message
+      );
     }
-    {
-      let result = resolve_node_module(
-        "exports_obj/a",
-        &ImportDep::ESM("exports_obj/a".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("exports_obj/a.js"));
+  }
+
+  #[test]
+  fn make_user_file_tsconfig_paths() {
+    let store = DashMap::new();
+    let path = TSCONFIG_PATHS_PATH.join("src/main.ts");
+
+    let res = make_file_item(&path, TSCONFIG_PATHS_PATH.as_path(), &store, &None).unwrap();
+    assert_eq!(res.deps.len(), 3usize);
+    for dep in ["src/components/Button.tsx", "src/utils/index.ts", "lib/helper.ts"] {
+      assert_eq!(
+        res
+          .deps
+          .contains(TSCONFIG_PATHS_PATH.join(dep).to_str().unwrap()),
+        true
+      );
     }
-    {
-      let result = resolve_node_module(
-        "main",
-        &ImportDep::ESM("main".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("main/main.js"));
+  }
+
+  #[test]
+  fn make_user_file_aliases() {
+    let store = DashMap::new();
+    let path = ALIASES_PATH.join("main.js");
+    let alias = |find: &str, replacements: &[&str]| ResolveAlias {
+      find: find.to_string(),
+      replacements: replacements.iter().map(|x| x.to_string()).collect(),
+    };
+    let opts = Some(MakeEntriesOptions {
+      aliases: Some(vec![
+        alias("@app", &["./missing", "./lib"]),
+        alias("vue$", &["./lib/vue.js"]),
+        alias("styles/*", &["./assets/*.css"]),
+      ]),
+      ..Default::default()
+    });
+
+    let res = make_file_item(&path, ALIASES_PATH.as_path(), &store, &opts).unwrap();
+    assert_eq!(res.deps.len(), 4usize);
+    for dep in ["lib/b.js", "lib/vue.js", "assets/theme.css", "lib/c.js"] {
+      assert!(res.deps.contains(ALIASES_PATH.join(dep).to_str().unwrap()));
     }
-    {
-      let result = resolve_node_module(
-        "nested/b",
-        &ImportDep::ESM("nested/b".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("nested/b.js"));
+  }
+
+  #[test]
+  fn make_user_file_package_imports() {
+    let store = DashMap::new();
+    let path = PACKAGE_IMPORTS_PATH.join("src/main.js");
+
+    let res = make_file_item(&path, PACKAGE_IMPORTS_PATH.as_path(), &store, &None).unwrap();
+    assert_eq!(res.deps.len(), 3usize);
+    for dep in [
+      "src/utils/log.js",
+      "src/config.node.js",
+      "node_modules/dep/index.js",
+    ] {
+      assert!(res
+        .deps
+        .contains(PACKAGE_IMPORTS_PATH.join(dep).to_str().unwrap()));
     }
-    {
-      let result = resolve_node_module(
-        "nested",
-        &ImportDep::ESM("nested".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("nested/a.js"));
+  }
+
+  #[test]
+  fn make_user_file_browser_field() {
+    let store = DashMap::new();
+    let path = BROWSER_FIELD_PATH.join("src/main.js");
+    let opts = Some(MakeEntriesOptions {
+      main_fields: Some(vec!["browser".to_string(), "main".to_string()]),
+      ..Default::default()
+    });
+
+    let res = make_file_item(&path, BROWSER_FIELD_PATH.as_path(), &store, &opts).unwrap();
+    assert_eq!(res.deps.len(), 3usize);
+    for dep in [
+      "node_modules/universal/lib/browser.js",
+      "node_modules/universal/shims/crypto.js",
+      // the polyfill of the `buffer` built-in
+      "node_modules/buffer/index.js",
+    ] {
+      assert!(res
+        .deps
+        .contains(BROWSER_FIELD_PATH.join(dep).to_str().unwrap()));
     }
-    {
-      let result = resolve_node_module(
-        "nested/c",
-        &ImportDep::ESM("nested/c".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("nested/c.js"));
+    let builtins: Vec<&str> = res.externals.iter().map(|x| &x.specifier[..]).collect();
+    assert_eq!(builtins, vec!["path"]);
+
+    // built-ins win for node
+    let store = DashMap::new();
+    let res = make_file_item(&path, BROWSER_FIELD_PATH.as_path(), &store, &None).unwrap();
+    assert!(!res.deps.contains(
+      BROWSER_FIELD_PATH
+        .join("node_modules/buffer/index.js")
+        .to_str()
+        .unwrap()
+    ));
+    assert!(res
+      .externals
+      .iter()
+      .any(|x| x.specifier == "buffer" && x.kind == ExternalKind::Builtin));
+  }
+
+  #[test]
+  fn test_get_browser_field_cached() {
+    let cache = ResolveCache::default();
+    let pkg_dir = BROWSER_FIELD_PATH.join("node_modules/universal");
+    let (dir, browser) = get_browser_field(&pkg_dir.join("lib"), &cache).unwrap();
+    assert_eq!(dir, pkg_dir);
+    assert!(browser.contains_key("crypto"));
+
+    // every file of the package shares the field read once
+    let (_, shims_browser) = get_browser_field(&pkg_dir.join("shims"), &cache).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&browser, &shims_browser));
+  }
+
+  #[test]
+  fn test_resolve_node_modules_main_fields() {
+    let node_modules = BROWSER_FIELD_PATH.join("node_modules");
+    let conditions = get_conditions(&ImportDep::ESM(String::new()), &None);
+    let cases = [
+      (vec!["main"], "universal/lib/node.js"),
+      (vec!["module", "main"], "universal/esm/index.js"),
+      (vec!["browser", "main"], "universal/lib/node.js"),
+      (vec!["browser", "module"], "str_browser/browser.js"),
+    ];
+    for (main_fields, expected) in cases {
+      let main_fields: Vec<String> = main_fields.into_iter().map(String::from).collect();
+      let module = expected.split('/').next().unwrap();
+      let result = resolve_bare_import(
+        module,
+        &conditions,
+        &main_fields,
+        std::slice::from_ref(&node_modules),
+        &mut None,
+      );
+      assert_eq!(
+        result,
+        Some(node_modules.join(expected)),
+        "{:?}",
+        main_fields
+      );
     }
-    {
-      let result = resolve_node_module(
-        "exports_cond",
-        &ImportDep::ESM("exports_cond".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("exports_cond/import-main.js"));
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn make_user_file_symlinks() {
+    let pnpm_path = copy_fixture_with_links("pnpm", "make_user_file_symlinks", &PNPM_LINKS);
+    let store = DashMap::new();
+    let path = pnpm_path.join("src/main.js");
+
+    let res = make_file_item(&path, pnpm_path.as_path(), &store, &None).unwrap();
+    assert_eq!(res.deps.len(), 3usize);
+    for dep in [
+      "node_modules/.pnpm/foo@1.0.0/node_modules/foo/index.js",
+      "node_modules/.pnpm/bar@2.0.0/node_modules/bar/index.js",
+      "packages/ui/index.js",
+    ] {
+      assert!(res.deps.contains(pnpm_path.join(dep).to_str().unwrap()));
     }
-    {
-      let result = resolve_node_module(
-        "exports_cond",
-        &ImportDep::REQUIRE("exports_cond".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("exports_cond/require-main.js"));
+    // `./loop.js` links to itself
+    assert_eq!(res.diagnostics[0].specifier, "./loop.js");
+    drop(res);
+    // `foo` is imported through `node_modules/foo` and `packages/ui/node_modules/foo`
+    assert_eq!(store.len(), 4usize);
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn make_entries_symlinked_root() {
+    let pnpm_path = copy_fixture_with_links("pnpm", "make_entries_symlinked_root", &PNPM_LINKS);
+    let root_link = pnpm_path.with_file_name("make_entries_symlinked_root_link");
+    if root_link.exists() {
+      std::fs::remove_file(&root_link).unwrap();
     }
-    {
-      let result = resolve_node_module(
-        "exports_cond_default",
-        &ImportDep::REQUIRE("exports_cond_default".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("exports_cond_default/main.js"));
+    std::os::unix::fs::symlink(&pnpm_path, &root_link).unwrap();
+
+    let (store, entries) = make_entries(
+      vec![root_link.join("src/main.js")],
+      Some(vec!["packages/*/*.js"]),
+      root_link.clone(),
+      &None,
+    );
+    let mut paths: Vec<PathBuf> = entries.iter().map(|x| x.path.clone()).collect();
+    paths.sort();
+    assert_eq!(
+      paths,
+      vec![
+        pnpm_path.join("packages/ui/index.js"),
+        pnpm_path.join("src/main.js"),
+      ]
+    );
+    // `packages/ui/index.js` is both an entry and a dep of `main.js`, under the same key
+    assert_eq!(store.len(), 4usize);
+  }
+
+  #[test]
+  fn make_user_file_yarn_pnp() {
+    let store = DashMap::new();
+    let path = YARN_PNP_PATH.join("src/main.js");
+
+    let res = make_file_item(&path, YARN_PNP_PATH.as_path(), &store, &None).unwrap();
+    assert_eq!(res.deps.len(), 2usize);
+    for dep in [
+      ".yarn/unplugged/left-pad-npm-1.3.0-3f5b3d0c5a/node_modules/left-pad/index.js",
+      "packages/utils/index.js",
+    ] {
+      assert!(res.deps.contains(YARN_PNP_PATH.join(dep).to_str().unwrap()));
     }
-    {
-      let result = resolve_node_module(
-        "exports_cond_no_default",
-        &ImportDep::REQUIRE("exports_cond_no_default".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(
-        result,
-        node_modules.join("exports_cond_no_default/import-main.js")
-      );
+    let archive = YARN_PNP_PATH.join(".yarn/cache/lodash-npm-4.17.21-6382451519-eb835a2e51.zip");
+    assert_eq!(
+      res.externals,
+      HashSet::from([ExternalDep {
+        specifier: "lodash/fp".to_string(),
+        kind: ExternalKind::Archive,
+        path: Some(archive.to_str().unwrap().to_string()),
+      }])
+    );
+  }
+
+  #[test]
+  fn test_find_node_modules_dirs() {
+    let app_path = MONOREPO_PATH.join("packages/app");
+    let result = find_node_modules_dirs(&app_path.join("src"), &ResolveCache::default());
+    assert!(result.starts_with(&[
+      app_path.join("node_modules"),
+      MONOREPO_PATH.join("node_modules"),
+    ]));
+
+    let nested_path = MONOREPO_PATH.join("node_modules/outer/node_modules/inner");
+    let result = find_node_modules_dirs(&nested_path, &ResolveCache::default());
+    assert!(result.starts_with(&[
+      MONOREPO_PATH.join("node_modules/outer/node_modules"),
+      MONOREPO_PATH.join("node_modules"),
+    ]));
+  }
+
+  #[test]
+  fn make_user_file_nested_node_modules() {
+    let store = DashMap::new();
+    let path = MONOREPO_PATH.join("packages/app/src/main.js");
+
+    let res = make_file_item(&path, MONOREPO_PATH.as_path(), &store, &None).unwrap();
+    assert_eq!(res.deps.len(), 5usize);
+    for dep in [
+      "packages/app/node_modules/shared/index.js",
+      "packages/app/node_modules/local-only/index.js",
+      "node_modules/outer/index.js",
+      "node_modules/outer/node_modules/inner/index.js",
+      "node_modules/shared/index.js",
+    ] {
+      assert!(res.deps.contains(MONOREPO_PATH.join(dep).to_str().unwrap()));
     }
-    {
-      let result = resolve_node_module(
-        "exports_array",
-        &ImportDep::ESM("exports_array".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(result, node_modules.join("exports_array/main1.js"));
+  }
+
+  #[test]
+  fn make_user_file_external_mode() {
+    let path = MONOREPO_PATH.join("packages/app/src/main.js");
+    let opts = |external_mode| {
+      Some(MakeEntriesOptions {
+        external_mode: Some(external_mode),
+        ..Default::default()
+      })
+    };
+
+    let store = DashMap::new();
+    let res = make_file_item(
+      &path,
+      MONOREPO_PATH.as_path(),
+      &store,
+      &opts(ExternalMode::Package),
+    )
+    .unwrap();
+    let packages = [
+      "packages/app/node_modules/shared/package.json",
+      "packages/app/node_modules/local-only/package.json",
+      "node_modules/outer/package.json",
+    ];
+    assert_eq!(res.deps.len(), packages.len());
+    for dep in packages {
+      let dep = MONOREPO_PATH.join(dep);
+      assert!(res.deps.contains(dep.to_str().unwrap()));
+      assert!(store.get(dep.to_str().unwrap()).unwrap().deps.is_empty());
     }
-    {
-      let result = resolve_node_module(
+    drop(res);
+    // packages aren't walked
+    assert_eq!(store.len(), packages.len() + 1);
+
+    let store = DashMap::new();
+    let res = make_file_item(
+      &path,
+      MONOREPO_PATH.as_path(),
+      &store,
+      &opts(ExternalMode::Ignore),
+    )
+    .unwrap();
+    assert!(res.deps.is_empty());
+    assert!(res.diagnostics.is_empty());
+  }
+
+  #[test]
+  fn test_resolve_node_modules() {
+    let node_modules = CWD.join("tests/fixtures/fake_node_modules");
+    let esm = get_conditions(&ImportDep::ESM(String::new()), &None);
+    let cjs = get_conditions(&ImportDep::REQUIRE(String::new()), &None);
+    let main_fields = get_main_fields(&None);
+    // expected results are the ones of `import.meta.resolve` and `require.resolve`
+    let cases = [
+      ("exports_str", &esm, Some("exports_str/main.js")),
+      ("exports_obj", &esm, Some("exports_obj/main.js")),
+      ("exports_obj/a", &esm, Some("exports_obj/a.js")),
+      ("main", &esm, Some("main/main.js")),
+      ("nested", &esm, None),
+      ("nested/b", &esm, None),
+      ("nested/c", &esm, Some("nested/c.js")),
+      ("exports_cond", &esm, Some("exports_cond/import-main.js")),
+      ("exports_cond", &cjs, Some("exports_cond/require-main.js")),
+      (
+        "exports_cond_default",
+        &esm,
+        Some("exports_cond_default/import-main.js"),
+      ),
+      (
+        "exports_cond_default",
+        &cjs,
+        Some("exports_cond_default/main.js"),
+      ),
+      (
+        "exports_cond_no_default",
+        &esm,
+        Some("exports_cond_no_default/import-main.js"),
+      ),
+      ("exports_cond_no_default", &cjs, None),
+      ("exports_array", &esm, Some("exports_array/main1.js")),
+      (
         "exports_obj_array",
-        &ImportDep::ESM("exports_obj_array".to_string()),
-        node_modules.as_path(),
-      )
-      .unwrap();
-      assert_eq!(
-        result,
-        node_modules.join("exports_obj_array/import-main.js")
+        &esm,
+        Some("exports_obj_array/import-main.js"),
+      ),
+      (
+        "exports_obj_array",
+        &cjs,
+        Some("exports_obj_array/require-main.js"),
+      ),
+      ("exports_patterns", &esm, Some("exports_patterns/main.js")),
+      (
+        "exports_patterns/utils",
+        &esm,
+        Some("exports_patterns/lib/utils.js"),
+      ),
+      (
+        "exports_patterns/features/a.js",
+        &esm,
+        Some("exports_patterns/src/features/a.js"),
+      ),
+      ("exports_patterns/features/internal/x.js", &esm, None),
+      (
+        "exports_patterns/package.json",
+        &cjs,
+        Some("exports_patterns/package.json"),
+      ),
+      ("missing", &esm, None),
+    ];
+    for (module, conditions, expected) in cases {
+      let result = resolve_bare_import(
+        module,
+        conditions,
+        &main_fields,
+        std::slice::from_ref(&node_modules),
+        &mut None,
       );
+      assert_eq!(result, expected.map(|x| node_modules.join(x)), "{}", module);
     }
   }
 
+  #[test]
+  fn test_resolve_node_modules_custom_conditions() {
+    let node_modules = CWD.join("tests/fixtures/fake_node_modules");
+    let opts = Some(MakeEntriesOptions {
+      conditions: Some(vec!["browser".to_string(), "development".to_string()]),
+      ..Default::default()
+    });
+    let conditions = get_conditions(&ImportDep::ESM(String::new()), &opts);
+
+    let result = resolve_bare_import(
+      "exports_patterns",
+      &conditions,
+      &get_main_fields(&opts),
+      std::slice::from_ref(&node_modules),
+      &mut None,
+    );
+    assert_eq!(
+      result,
+      Some(node_modules.join("exports_patterns/browser-dev.js"))
+    );
+  }
+
   #[test]
   fn make_file_item_node_module() {
     let store = DashMap::new();
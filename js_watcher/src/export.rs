@@ -0,0 +1,552 @@
+use dashmap::DashMap;
+#[cfg(not(feature = "without-napi"))]
+use napi::bindgen_prelude::{FromNapiValue, ToNapiValue};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::Path;
+
+use crate::entry::{get_package_name, get_package_root};
+use crate::file_item::{FileItem, ImportKind};
+use crate::parser::Span;
+
+/// Version of the JSON schema written by `to_json`, increased on breaking changes
+pub const GRAPH_SCHEMA_VERSION: u32 = 1;
+
+#[cfg_attr(not(feature = "without-napi"), napi(string_enum), derive(PartialEq, Debug))]
+#[cfg_attr(feature = "without-napi", derive(PartialEq, Debug, Clone, Copy))]
+pub enum GraphFormat {
+  Json,
+  /// Graphviz
+  Dot,
+  Mermaid,
+}
+
+#[cfg_attr(not(feature = "without-napi"), napi(string_enum), derive(PartialEq, Debug))]
+#[cfg_attr(feature = "without-napi", derive(PartialEq, Debug, Clone, Copy))]
+pub enum GraphNodeKind {
+  /// A file of the project
+  File,
+  /// A file of a package of `node_modules`
+  PackageFile,
+  /// A whole package of `node_modules`, when packages are collapsed
+  Package,
+  /// A dependency that isn't watched, like a node built-in
+  External,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphNode {
+  /// The path of the file, the directory of a collapsed package, or the specifier of an
+  /// external dependency
+  pub id: String,
+  pub kind: GraphNodeKind,
+  /// Size in bytes, summed up for collapsed packages
+  pub size: Option<i64>,
+  /// Name of the package the node belongs to
+  pub package: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphEdge {
+  pub from: String,
+  pub to: String,
+  /// Unknown for external dependencies
+  pub kind: Option<ImportKind>,
+  /// The import statement inside `from`
+  pub span: Option<Span>,
+}
+
+/// Nodes and edges are sorted, so the same graph is always written the same way
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+  pub nodes: Vec<GraphNode>,
+  pub edges: Vec<GraphEdge>,
+}
+
+/// Builds the graph of the files imported from `roots`, up to `max_depth` imports away.
+/// With `collapse_packages`, every file of a package of `node_modules` is merged into a node
+/// for the whole package, and imports inside a package are left out.
+pub fn build_graph(
+  store: &DashMap<String, FileItem>,
+  roots: &[String],
+  max_depth: Option<u32>,
+  collapse_packages: bool,
+) -> DependencyGraph {
+  // the depth of each file, from the closest root
+  let mut depths: HashMap<String, u32> = HashMap::new();
+  let mut queue: VecDeque<String> = VecDeque::new();
+  for root in roots {
+    if store.contains_key(root) && !depths.contains_key(root) {
+      depths.insert(root.to_string(), 0);
+      queue.push_back(root.to_string());
+    }
+  }
+  while let Some(current) = queue.pop_front() {
+    let depth = depths[&current];
+    if max_depth.is_some_and(|x| depth >= x) {
+      continue;
+    }
+    let item = store.get(&current).unwrap();
+    for import in &item.imports {
+      if store.contains_key(import) && !depths.contains_key(import) {
+        depths.insert(import.to_string(), depth + 1);
+        queue.push_back(import.to_string());
+      }
+    }
+  }
+
+  let mut files: Vec<&String> = depths.keys().collect();
+  files.sort();
+  let node_id = |file: &str| match get_package_root(Path::new(file)) {
+    Some(pkg_dir) if collapse_packages => pkg_dir.to_str().unwrap().to_string(),
+    _ => file.to_string(),
+  };
+  let mut nodes: BTreeMap<String, GraphNode> = BTreeMap::new();
+  let mut edges: BTreeMap<(String, String), GraphEdge> = BTreeMap::new();
+  for file in files {
+    let item = store.get(file).unwrap();
+    let pkg_dir = get_package_root(&item.path);
+    let id = node_id(file);
+    let size = std::fs::metadata(file).ok().map(|x| x.len() as i64);
+    let node = nodes.entry(id.clone()).or_insert_with(|| GraphNode {
+      id: id.clone(),
+      kind: match pkg_dir {
+        Some(_) if collapse_packages => GraphNodeKind::Package,
+        Some(_) => GraphNodeKind::PackageFile,
+        None => GraphNodeKind::File,
+      },
+      size: None,
+      package: pkg_dir.as_deref().and_then(get_package_name),
+    });
+    if let Some(size) = size {
+      node.size = Some(node.size.unwrap_or(0) + size);
+    }
+
+    for import in item.imports.iter().filter(|x| depths.contains_key(*x)) {
+      let to = node_id(import);
+      if collapse_packages && to == id && pkg_dir.is_some() {
+        continue;
+      }
+      let site = item.import_sites.get(import);
+      let edge = GraphEdge {
+        from: id.clone(),
+        to: to.clone(),
+        kind: site.map(|x| x.kind),
+        span: site.map(|x| x.span),
+      };
+      // a package imported from several of its files keeps the first import
+      let key = (id.clone(), to);
+      if edges
+        .get(&key)
+        .map_or(true, |x| edge_order(&edge) < edge_order(x))
+      {
+        edges.insert(key, edge);
+      }
+    }
+    if max_depth.is_some_and(|x| depths[file] >= x) {
+      continue;
+    }
+    for external in &item.external_imports {
+      nodes
+        .entry(external.specifier.clone())
+        .or_insert_with(|| GraphNode {
+          id: external.specifier.clone(),
+          kind: GraphNodeKind::External,
+          size: None,
+          package: None,
+        });
+      edges
+        .entry((id.clone(), external.specifier.clone()))
+        .or_insert_with(|| GraphEdge {
+          from: id.clone(),
+          to: external.specifier.clone(),
+          kind: None,
+          span: None,
+        });
+    }
+  }
+
+  DependencyGraph {
+    nodes: nodes.into_values().collect(),
+    edges: edges.into_values().collect(),
+  }
+}
+
+/// Edges merged together are ordered by their position in the source
+fn edge_order(edge: &GraphEdge) -> (u32, u32) {
+  edge
+    .span
+    .map(|x| (x.line, x.column))
+    .unwrap_or((u32::MAX, u32::MAX))
+}
+
+/// Writes the graph with the given format. Labels of DOT and Mermaid are relative to
+/// `project_root`.
+pub fn write_graph(graph: &DependencyGraph, format: GraphFormat, project_root: &str) -> String {
+  match format {
+    GraphFormat::Json => to_json(graph),
+    GraphFormat::Dot => to_dot(graph, project_root),
+    GraphFormat::Mermaid => to_mermaid(graph, project_root),
+  }
+}
+
+/// ```json
+/// {
+///   "version": 1,
+///   "nodes": [{ "id": "/project/src/main.js", "kind": "File", "size": 120, "package": null }],
+///   "edges": [{ "from": "/project/src/main.js", "to": "fs", "kind": null, "span": null }]
+/// }
+/// ```
+pub fn to_json(graph: &DependencyGraph) -> String {
+  serde_json::to_string_pretty(&graph_to_value(graph)).unwrap()
+}
+
+/// The JSON object written by `to_json`
+pub fn graph_to_value(graph: &DependencyGraph) -> serde_json::Value {
+  let nodes: Vec<serde_json::Value> = graph
+    .nodes
+    .iter()
+    .map(|node| {
+      json!({
+        "id": node.id,
+        "kind": match node.kind {
+          GraphNodeKind::File => "File",
+          GraphNodeKind::PackageFile => "PackageFile",
+          GraphNodeKind::Package => "Package",
+          GraphNodeKind::External => "External",
+        },
+        "size": node.size,
+        "package": node.package,
+      })
+    })
+    .collect();
+  let edges: Vec<serde_json::Value> = graph
+    .edges
+    .iter()
+    .map(|edge| {
+      json!({
+        "from": edge.from,
+        "to": edge.to,
+        "kind": edge.kind.map(|x| match x {
+          ImportKind::Esm => "Esm",
+          ImportKind::Require => "Require",
+          ImportKind::Css => "Css",
+        }),
+        "span": edge.span.map(|x| json!({
+          "start": x.start,
+          "end": x.end,
+          "line": x.line,
+          "column": x.column,
+        })),
+      })
+    })
+    .collect();
+  json!({
+    "version": GRAPH_SCHEMA_VERSION,
+    "nodes": nodes,
+    "edges": edges,
+  })
+}
+
+/// Reads a graph written by `to_json`. Returns `None` when it doesn't follow the schema of
+/// `GRAPH_SCHEMA_VERSION`.
+pub fn graph_from_value(value: &serde_json::Value) -> Option<DependencyGraph> {
+  if value["version"].as_u64()? != GRAPH_SCHEMA_VERSION as u64 {
+    return None;
+  }
+  let mut graph = DependencyGraph::default();
+  for node in value["nodes"].as_array()? {
+    graph.nodes.push(GraphNode {
+      id: node["id"].as_str()?.to_string(),
+      kind: match node["kind"].as_str()? {
+        "File" => GraphNodeKind::File,
+        "PackageFile" => GraphNodeKind::PackageFile,
+        "Package" => GraphNodeKind::Package,
+        "External" => GraphNodeKind::External,
+        _ => return None,
+      },
+      size: node["size"].as_i64(),
+      package: node["package"].as_str().map(String::from),
+    });
+  }
+  for edge in value["edges"].as_array()? {
+    let span = &edge["span"];
+    graph.edges.push(GraphEdge {
+      from: edge["from"].as_str()?.to_string(),
+      to: edge["to"].as_str()?.to_string(),
+      kind: match edge["kind"].as_str() {
+        Some("Esm") => Some(ImportKind::Esm),
+        Some("Require") => Some(ImportKind::Require),
+        Some("Css") => Some(ImportKind::Css),
+        _ => None,
+      },
+      span: if span.is_object() {
+        Some(Span {
+          start: span["start"].as_u64()? as u32,
+          end: span["end"].as_u64()? as u32,
+          line: span["line"].as_u64()? as u32,
+          column: span["column"].as_u64()? as u32,
+        })
+      } else {
+        None
+      },
+    });
+  }
+  Some(graph)
+}
+
+fn get_label(node: &GraphNode, project_root: &str) -> String {
+  match (&node.kind, &node.package) {
+    (GraphNodeKind::Package, Some(package)) => package.to_string(),
+    _ => match Path::new(&node.id).strip_prefix(project_root) {
+      Ok(relative) => format!("./{}", relative.to_str().unwrap()),
+      Err(_) => node.id.clone(),
+    },
+  }
+}
+
+pub fn to_dot(graph: &DependencyGraph, project_root: &str) -> String {
+  let escape = |x: &str| x.replace('\\', "\\\\").replace('"', "\\\"");
+  let mut res = String::from("digraph dependencies {\n  rankdir=LR;\n");
+  for node in &graph.nodes {
+    let shape = match node.kind {
+      GraphNodeKind::File => "box",
+      GraphNodeKind::PackageFile => "ellipse",
+      GraphNodeKind::Package => "folder",
+      GraphNodeKind::External => "plaintext",
+    };
+    res += &format!(
+      "  \"{}\" [label=\"{}\", shape={}];\n",
+      escape(&node.id),
+      escape(&get_label(node, project_root)),
+      shape
+    );
+  }
+  for edge in &graph.edges {
+    let attributes = match edge.kind {
+      Some(ImportKind::Require) => " [label=\"require\"]",
+      Some(ImportKind::Css) => " [label=\"css\"]",
+      _ => "",
+    };
+    res += &format!(
+      "  \"{}\" -> \"{}\"{};\n",
+      escape(&edge.from),
+      escape(&edge.to),
+      attributes
+    );
+  }
+  res += "}\n";
+  res
+}
+
+pub fn to_mermaid(graph: &DependencyGraph, project_root: &str) -> String {
+  // paths can't be ids of mermaid nodes, they are numbered instead
+  let ids: HashMap<&str, String> = graph
+    .nodes
+    .iter()
+    .enumerate()
+    .map(|(i, node)| (node.id.as_str(), format!("n{}", i)))
+    .collect();
+  let mut res = String::from("graph LR\n");
+  for node in &graph.nodes {
+    let label = get_label(node, project_root).replace('"', "#quot;");
+    let (open, close) = match node.kind {
+      GraphNodeKind::File => ("[\"", "\"]"),
+      GraphNodeKind::PackageFile => ("(\"", "\")"),
+      GraphNodeKind::Package => ("[[\"", "\"]]"),
+      GraphNodeKind::External => (">\"", "\"]"),
+    };
+    res += &format!("  {}{}{}{}\n", ids[node.id.as_str()], open, label, close);
+  }
+  for edge in &graph.edges {
+    let arrow = match edge.kind {
+      Some(ImportKind::Require) => "-->|require|",
+      Some(ImportKind::Css) => "-->|css|",
+      _ => "-->",
+    };
+    res += &format!(
+      "  {} {} {}\n",
+      ids[edge.from.as_str()],
+      arrow,
+      ids[edge.to.as_str()]
+    );
+  }
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    build_graph, graph_from_value, graph_to_value, to_dot, to_json, to_mermaid, DependencyGraph,
+    GraphEdge, GraphNode, GraphNodeKind,
+  };
+  use crate::entry::make_file_item;
+  use crate::file_item::ImportKind;
+  use crate::parser::Span;
+  use dashmap::DashMap;
+  use lazy_static::lazy_static;
+  use std::path::PathBuf;
+
+  lazy_static! {
+    static ref CWD: PathBuf = std::env::current_dir().unwrap();
+    static ref GRAPH_PATH: PathBuf = CWD.join("tests").join("fixtures").join("graph");
+    static ref MONOREPO_PATH: PathBuf = CWD.join("tests").join("fixtures").join("monorepo");
+  }
+
+  #[test]
+  fn test_build_graph() {
+    let store = DashMap::new();
+    let entry = GRAPH_PATH.join("entry.js");
+    make_file_item(&entry, &GRAPH_PATH, &store, &None);
+    let key = |file: &str| GRAPH_PATH.join(file).to_str().unwrap().to_string();
+
+    let graph = build_graph(&store, &[key("entry.js")], None, false);
+    let ids: Vec<&str> = graph.nodes.iter().map(|x| x.id.as_str()).collect();
+    assert_eq!(
+      ids,
+      vec![key("a.js"), key("b.js"), key("c.js"), key("entry.js")]
+    );
+    assert!(graph.nodes.iter().all(|x| x.kind == GraphNodeKind::File));
+    assert_eq!(graph.edges.len(), 4);
+    let edge = &graph.edges[0];
+    assert_eq!(
+      (edge.from.as_str(), edge.to.as_str()),
+      (key("a.js").as_str(), key("b.js").as_str())
+    );
+    assert_eq!(edge.kind, Some(ImportKind::Esm));
+    assert_eq!(edge.span.map(|x| x.line), Some(1));
+
+    // `b.js` is two imports away
+    let graph = build_graph(&store, &[key("entry.js")], Some(1), false);
+    assert_eq!(graph.nodes.len(), 3);
+    assert_eq!(graph.edges.len(), 2);
+
+    let json: serde_json::Value = serde_json::from_str(&to_json(&graph)).unwrap();
+    assert_eq!(json["version"], 1);
+    assert_eq!(json["nodes"][0]["kind"], "File");
+    assert_eq!(json["edges"][0]["span"]["line"], 1);
+    let root = GRAPH_PATH.to_str().unwrap();
+    assert!(to_dot(&graph, root).contains(&format!(
+      "  \"{}\" [label=\"./entry.js\", shape=box];\n",
+      key("entry.js")
+    )));
+    assert_eq!(
+      to_mermaid(&graph, root),
+      "graph LR\n  n0[\"./a.js\"]\n  n1[\"./c.js\"]\n  n2[\"./entry.js\"]\n  n2 --> n0\n  n2 --> n1\n"
+    );
+  }
+
+  #[test]
+  fn test_graph_to_value_round_trip() {
+    let kinds = [
+      GraphNodeKind::File,
+      GraphNodeKind::PackageFile,
+      GraphNodeKind::Package,
+      GraphNodeKind::External,
+    ];
+    let graph = DependencyGraph {
+      nodes: kinds
+        .into_iter()
+        .enumerate()
+        .map(|(i, kind)| GraphNode {
+          id: i.to_string(),
+          kind,
+          size: Some(i as i64),
+          package: None,
+        })
+        .collect(),
+      edges: [
+        Some(ImportKind::Esm),
+        Some(ImportKind::Require),
+        Some(ImportKind::Css),
+        None,
+      ]
+      .into_iter()
+      .map(|kind| GraphEdge {
+        from: "0".to_string(),
+        to: "1".to_string(),
+        kind,
+        span: kind.map(|_| Span {
+          start: 0,
+          end: 1,
+          line: 1,
+          column: 1,
+        }),
+      })
+      .collect(),
+    };
+
+    let value = graph_to_value(&graph);
+    let kinds: Vec<&str> = value["nodes"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|x| x["kind"].as_str().unwrap())
+      .collect();
+    assert_eq!(kinds, ["File", "PackageFile", "Package", "External"]);
+    let read = graph_from_value(&value).unwrap();
+    assert_eq!(read.nodes, graph.nodes);
+    assert_eq!(read.edges, graph.edges);
+  }
+
+  #[test]
+  fn test_build_graph_collapse_packages() {
+    let store = DashMap::new();
+    let entry = MONOREPO_PATH.join("packages/app/src/main.js");
+    make_file_item(&entry, &MONOREPO_PATH, &store, &None);
+    let key = |file: &str| MONOREPO_PATH.join(file).to_str().unwrap().to_string();
+
+    let graph = build_graph(&store, &[key("packages/app/src/main.js")], None, true);
+    let nodes: Vec<(&str, &GraphNodeKind, Option<&str>)> = graph
+      .nodes
+      .iter()
+      .map(|x| (x.id.as_str(), &x.kind, x.package.as_deref()))
+      .collect();
+    assert_eq!(
+      nodes,
+      vec![
+        (
+          key("node_modules/outer").as_str(),
+          &GraphNodeKind::Package,
+          Some("outer")
+        ),
+        (
+          key("node_modules/outer/node_modules/inner").as_str(),
+          &GraphNodeKind::Package,
+          Some("inner")
+        ),
+        (
+          key("node_modules/shared").as_str(),
+          &GraphNodeKind::Package,
+          Some("shared")
+        ),
+        (
+          key("packages/app/node_modules/local-only").as_str(),
+          &GraphNodeKind::Package,
+          Some("local-only")
+        ),
+        (
+          key("packages/app/node_modules/shared").as_str(),
+          &GraphNodeKind::Package,
+          Some("shared")
+        ),
+        (
+          key("packages/app/src/main.js").as_str(),
+          &GraphNodeKind::File,
+          None
+        ),
+      ]
+    );
+    let edge = graph
+      .edges
+      .iter()
+      .find(|x| {
+        x.from == key("node_modules/outer") && x.to == key("node_modules/outer/node_modules/inner")
+      })
+      .unwrap();
+    assert_eq!(edge.kind, Some(ImportKind::Require));
+  }
+}
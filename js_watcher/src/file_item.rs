@@ -1,4 +1,7 @@
-use std::{collections::HashSet, path::PathBuf};
+use std::{
+  collections::{HashMap, HashSet},
+  path::PathBuf,
+};
 
 use dashmap::DashMap;
 use napi::{
@@ -7,10 +10,113 @@ use napi::{
   Env,
 };
 
+use crate::graph::collect_importers;
+use crate::parser::{ImportDep, Span};
+use crate::symbols::ModuleSymbol;
+
 #[derive(Debug)]
 pub struct FileItem {
   pub path: PathBuf,
+  /// Files imported by this file only
+  pub imports: HashSet<String>,
+  /// Dependencies imported by this file only that aren't watched
+  pub external_imports: HashSet<ExternalDep>,
+  /// The first statement importing each file of `imports`
+  pub import_sites: HashMap<String, ImportSite>,
+  /// What this file exports and the names it imports from the files of `imports`, along with
+  /// their statement. Only parsed for JS files outside of `node_modules`
+  pub symbols: Vec<(ModuleSymbol<String>, Span)>,
+  /// Files importing this file directly, the reverse of `imports`
+  pub importers: HashSet<String>,
+  /// Every file this file depends on, directly or not. Computed from `imports` on demand, for
+  /// entries and the files `make_file_item` is called with only, see `graph::refresh_deps`
   pub deps: HashSet<String>,
+  /// Dependencies that aren't watched, like packages only available inside an archive, of
+  /// this file and of its deps. Computed along with `deps`
+  pub externals: HashSet<ExternalDep>,
+  /// Problems met while resolving the imports of this file only
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+#[cfg_attr(not(feature = "without-napi"), napi(string_enum), derive(PartialEq, Eq, Hash, Debug))]
+#[cfg_attr(
+  feature = "without-napi",
+  derive(PartialEq, Eq, Hash, Debug, Clone, Copy)
+)]
+pub enum ExternalKind {
+  /// A package inside a zip archive, like the Yarn Plug'n'Play cache
+  Archive,
+  /// A module built into node, like `fs` or `node:test`
+  Builtin,
+  /// A remote module, like `https://esm.sh/react`, or a `data:` URI
+  Url,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExternalDep {
+  /// The import as written in the source
+  pub specifier: String,
+  pub kind: ExternalKind,
+  /// Where the dependency lives, if it's on disk
+  pub path: Option<String>,
+}
+
+#[cfg_attr(not(feature = "without-napi"), napi(string_enum), derive(PartialEq, Eq, Hash, Debug))]
+#[cfg_attr(
+  feature = "without-napi",
+  derive(PartialEq, Eq, Hash, Debug, Clone, Copy)
+)]
+pub enum DiagnosticKind {
+  /// Files only differing by their extension match an import without one, the first is used
+  AmbiguousImport,
+  /// The import couldn't be resolved, it's ignored until it does
+  UnresolvedImport,
+  /// The file couldn't be read, its imports are unknown
+  UnreadableFile,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+  pub kind: DiagnosticKind,
+  /// The import as written in the source, or the path of the file for `UnreadableFile`
+  pub specifier: String,
+  /// The file containing the import
+  pub importer: String,
+  /// The import statement inside `importer`
+  pub span: Option<Span>,
+  pub message: String,
+}
+
+#[cfg_attr(not(feature = "without-napi"), napi(string_enum), derive(PartialEq, Eq, Debug))]
+#[cfg_attr(feature = "without-napi", derive(PartialEq, Eq, Debug, Clone, Copy))]
+pub enum ImportKind {
+  /// `import`, `export ... from` or `import()`
+  Esm,
+  Require,
+  /// `@import` of style sheets
+  Css,
+}
+
+impl From<&ImportDep> for ImportKind {
+  fn from(dep: &ImportDep) -> Self {
+    match dep {
+      ImportDep::ESM(_) => ImportKind::Esm,
+      ImportDep::REQUIRE(_) => ImportKind::Require,
+      ImportDep::CSS(_) => ImportKind::Css,
+    }
+  }
+}
+
+/// An import statement leading to a file
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportSite {
+  /// The import as written in the source
+  pub specifier: String,
+  pub kind: ImportKind,
+  pub span: Span,
 }
 
 impl napi::bindgen_prelude::TypeName for FileItem {
@@ -29,13 +135,42 @@ impl ToNapiValue for FileItem {
   ) -> napi::bindgen_prelude::Result<napi_value> {
     let env_wrapper = Env::from(env);
     let mut obj = env_wrapper.create_object()?;
-    let Self { path, deps } = val;
+    let Self {
+      path,
+      imports,
+      external_imports: _,
+      import_sites: _,
+      symbols: _,
+      importers: _,
+      deps,
+      externals,
+      diagnostics: _,
+    } = val;
     obj.set("path", path.to_str().unwrap())?;
+    let mut imports_arr = env_wrapper.create_array(imports.len() as u32)?;
+    for (i, import) in imports.iter().enumerate() {
+      imports_arr.set(i as u32, import.clone()).unwrap();
+    }
+    obj.set("imports", imports_arr)?;
     let mut deps_arr = env_wrapper.create_array(deps.len() as u32)?;
     for (i, dep) in deps.iter().enumerate() {
       deps_arr.set(i as u32, dep.clone()).unwrap();
     }
     obj.set("deps", deps_arr)?;
+    let mut externals_arr = env_wrapper.create_array(externals.len() as u32)?;
+    for (i, external) in externals.iter().enumerate() {
+      let mut external_obj = env_wrapper.create_object()?;
+      external_obj.set("specifier", external.specifier.clone())?;
+      let kind = match external.kind {
+        ExternalKind::Archive => "Archive",
+        ExternalKind::Builtin => "Builtin",
+        ExternalKind::Url => "Url",
+      };
+      external_obj.set("kind", kind)?;
+      external_obj.set("path", external.path.clone())?;
+      externals_arr.set(i as u32, external_obj).unwrap();
+    }
+    obj.set("externals", externals_arr)?;
     Object::to_napi_value(env, obj)
   }
 }
@@ -54,9 +189,41 @@ impl FromNapiValue for FileItem {
       deps.insert(deps_arr.get(0).unwrap().unwrap());
     }
 
+    let mut imports: HashSet<String> = HashSet::new();
+    if let Some(imports_arr) = obj.get::<_, Array>("imports")? {
+      for i in 0..imports_arr.len() {
+        imports.insert(imports_arr.get(i)?.unwrap());
+      }
+    }
+
+    let mut externals: HashSet<ExternalDep> = HashSet::new();
+    if let Some(externals_arr) = obj.get::<_, Array>("externals")? {
+      for i in 0..externals_arr.len() {
+        let external_obj: Object = externals_arr.get(i)?.unwrap();
+        let kind: String = external_obj.get("kind")?.unwrap();
+        externals.insert(ExternalDep {
+          specifier: external_obj.get("specifier")?.unwrap(),
+          kind: match kind.as_str() {
+            "Archive" => ExternalKind::Archive,
+            "Builtin" => ExternalKind::Builtin,
+            "Url" => ExternalKind::Url,
+            _ => continue,
+          },
+          path: external_obj.get("path")?,
+        });
+      }
+    }
+
     let val = Self {
       path: PathBuf::from(path_str),
+      imports,
+      external_imports: HashSet::new(),
+      import_sites: HashMap::new(),
+      symbols: Vec::new(),
+      importers: HashSet::new(),
       deps,
+      externals,
+      diagnostics: Vec::new(),
     };
     Ok(val)
   }
@@ -66,28 +233,23 @@ impl FileItem {
   pub fn clone_item(&self) -> FileItem {
     FileItem {
       path: PathBuf::from(&self.path),
+      imports: self.imports.clone(),
+      external_imports: self.external_imports.clone(),
+      import_sites: self.import_sites.clone(),
+      symbols: self.symbols.clone(),
+      importers: self.importers.clone(),
       deps: self.deps.iter().map(String::from).collect(),
+      externals: self.externals.clone(),
+      diagnostics: self.diagnostics.clone(),
     }
   }
 
+  /// Returns this file and every file depending on it, directly or not, see
+  /// `graph::collect_importers`.
   pub fn get_usage(&self, store: &DashMap<String, FileItem>) -> Vec<String> {
     let self_path = self.path.to_str().unwrap().to_string();
-    let res: Vec<String> = store
-      .iter()
-      .filter(|item| {
-        if item.path.to_str().unwrap() == self_path {
-          return true;
-        }
-        for dep in &item.deps {
-          if dep.eq(&self_path) {
-            return true;
-          }
-        }
-        false
-      })
-      .map(|item| item.value().path.to_str().unwrap().to_string())
-      .collect();
-
-    res
+    let mut res = collect_importers(store, &self_path);
+    res.insert(self_path);
+    res.into_iter().collect()
   }
 }
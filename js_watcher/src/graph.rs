@@ -0,0 +1,541 @@
+use dashmap::DashMap;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::file_item::{ExternalDep, FileItem};
+
+/// Walks the imports of the store from `file`, and returns every file it depends on, directly
+/// or not, along with their external dependencies.
+/// `file` is part of its own deps when it belongs to a cycle.
+pub fn collect_deps(
+  store: &DashMap<String, FileItem>,
+  file: &str,
+) -> (HashSet<String>, HashSet<ExternalDep>) {
+  let mut deps: HashSet<String> = HashSet::new();
+  let mut externals: HashSet<ExternalDep> = HashSet::new();
+  let mut queue = VecDeque::from([file.to_string()]);
+  while let Some(current) = queue.pop_front() {
+    let item = match store.get(&current) {
+      Some(item) => item,
+      None => continue,
+    };
+    externals.extend(item.external_imports.iter().cloned());
+    for import in &item.imports {
+      if deps.insert(import.to_string()) {
+        queue.push_back(import.to_string());
+      }
+    }
+  }
+  (deps, externals)
+}
+
+/// Returns `roots` and every file they depend on, directly or not.
+pub fn collect_reachable(store: &DashMap<String, FileItem>, roots: &[String]) -> HashSet<String> {
+  let mut reachable: HashSet<String> = HashSet::new();
+  for root in roots {
+    if reachable.insert(root.to_string()) {
+      reachable.extend(collect_deps(store, root).0);
+    }
+  }
+  reachable
+}
+
+/// Updates the cached deps of `file` in the store, see `collect_deps`.
+pub fn refresh_deps(store: &DashMap<String, FileItem>, file: &str) {
+  let (deps, externals) = collect_deps(store, file);
+  if let Some(mut item) = store.get_mut(file) {
+    item.deps = deps;
+    item.externals = externals;
+  }
+}
+
+/// Adds `file` to the importers of each of its `imports`.
+pub fn link_imports(store: &DashMap<String, FileItem>, file: &str, imports: &HashSet<String>) {
+  for import in imports {
+    if let Some(mut item) = store.get_mut(import) {
+      item.importers.insert(file.to_string());
+    }
+  }
+}
+
+/// Removes `file` from the store, and from the importers of its imports, so it can be made
+/// again. Returns its importers, to give back with `restore_importers` once it's made.
+pub fn detach_file(store: &DashMap<String, FileItem>, file: &str) -> HashSet<String> {
+  let (_, item) = match store.remove(file) {
+    Some(entry) => entry,
+    None => return HashSet::new(),
+  };
+  for import in &item.imports {
+    if let Some(mut dep) = store.get_mut(import) {
+      dep.importers.remove(file);
+    }
+  }
+  item.importers
+}
+
+pub fn restore_importers(
+  store: &DashMap<String, FileItem>,
+  file: &str,
+  importers: HashSet<String>,
+) {
+  if let Some(mut item) = store.get_mut(file) {
+    item.importers.extend(importers);
+  }
+}
+
+/// Walks the importers of the store from `file`, and returns every file depending on it,
+/// directly or not. `file` is part of them when it belongs to a cycle.
+pub fn collect_importers(store: &DashMap<String, FileItem>, file: &str) -> HashSet<String> {
+  let mut importers: HashSet<String> = HashSet::new();
+  let mut queue = VecDeque::from([file.to_string()]);
+  while let Some(current) = queue.pop_front() {
+    let item = match store.get(&current) {
+      Some(item) => item,
+      None => continue,
+    };
+    for importer in &item.importers {
+      if importers.insert(importer.to_string()) {
+        queue.push_back(importer.to_string());
+      }
+    }
+  }
+  importers
+}
+
+/// Returns up to `max` chains of imports leading from `from` to `to`, both included, the
+/// shortest first. A chain never goes through the same file twice.
+/// Each chain after the first is found by Yen's algorithm: it deviates from a chain found
+/// before at one of its files, through an import no chain with the same start took.
+pub fn find_import_chains(
+  store: &DashMap<String, FileItem>,
+  from: &str,
+  to: &str,
+  max: usize,
+) -> Vec<Vec<String>> {
+  let mut chains = match find_import_chain(store, from, to) {
+    Some(chain) if max > 0 => vec![chain],
+    _ => return Vec::new(),
+  };
+  // chains deviating from the ones found, by length then by files
+  let mut candidates: BTreeSet<(usize, Vec<String>)> = BTreeSet::new();
+  while chains.len() < max {
+    let last = chains.last().unwrap();
+    for i in 0..last.len() - 1 {
+      let root = &last[..=i];
+      let skipped_imports: HashSet<(&str, &str)> = chains
+        .iter()
+        .filter(|x| x.len() > i + 1 && x[..=i] == *root)
+        .map(|x| (&x[i][..], &x[i + 1][..]))
+        .collect();
+      let skipped_files: HashSet<&str> = root[..i].iter().map(|x| &x[..]).collect();
+      let deviation = find_shortest_chain(store, &last[i], to, &skipped_files, &skipped_imports);
+      if let Some(deviation) = deviation {
+        let mut chain = root[..i].to_vec();
+        chain.extend(deviation);
+        candidates.insert((chain.len(), chain));
+      }
+    }
+    match candidates.pop_first() {
+      Some((_, chain)) => chains.push(chain),
+      None => break,
+    }
+  }
+  chains
+}
+
+/// Finds the circular imports of the store, as the strongly connected components of the
+/// graph (Tarjan's algorithm). Each cycle lists its files sorted, and so are the cycles.
+/// A file importing itself is a cycle on its own.
+pub fn find_cycles(store: &DashMap<String, FileItem>) -> Vec<Vec<String>> {
+  let mut files: Vec<String> = store.iter().map(|x| x.key().to_string()).collect();
+  files.sort();
+  let imports_itself = |component: &Vec<String>| {
+    store
+      .get(&component[0])
+      .is_some_and(|x| x.imports.contains(&component[0]))
+  };
+  let mut cycles: Vec<Vec<String>> = find_components(store, files)
+    .into_iter()
+    .filter(|x| x.len() > 1 || imports_itself(x))
+    .collect();
+  cycles.sort();
+  cycles
+}
+
+/// Orders the files reachable from `roots` leaves first: each file comes after every file it
+/// imports, directly or not. The files of a cycle can't be ordered, they are grouped together.
+/// ```rs
+/// topological_order(&store, &["/entry.js"]); // [["/b.js"], ["/a.js", "/c.js"], ["/entry.js"]]
+/// ```
+pub fn topological_order(store: &DashMap<String, FileItem>, roots: &[String]) -> Vec<Vec<String>> {
+  let mut roots: Vec<String> = roots
+    .iter()
+    .filter(|x| store.contains_key(*x))
+    .cloned()
+    .collect();
+  roots.sort();
+  find_components(store, roots)
+}
+
+/// Returns the strongly connected components of the graph reachable from `roots`, in the
+/// order Tarjan's algorithm finds them: a component comes after every component it imports.
+/// Each component lists its files sorted.
+fn find_components(store: &DashMap<String, FileItem>, roots: Vec<String>) -> Vec<Vec<String>> {
+  let mut finder = ComponentFinder {
+    store,
+    index: HashMap::new(),
+    low_link: HashMap::new(),
+    stack: Vec::new(),
+    on_stack: HashSet::new(),
+    components: Vec::new(),
+  };
+  for root in roots {
+    if !finder.index.contains_key(&root) {
+      finder.walk(root);
+    }
+  }
+  finder.components
+}
+
+struct ComponentFinder<'a> {
+  store: &'a DashMap<String, FileItem>,
+  index: HashMap<String, usize>,
+  low_link: HashMap<String, usize>,
+  stack: Vec<String>,
+  on_stack: HashSet<String>,
+  components: Vec<Vec<String>>,
+}
+
+impl<'a> ComponentFinder<'a> {
+  /// Pushes `file` on the stack, and returns its imports left to visit.
+  fn visit(&mut self, file: &str) -> Vec<String> {
+    let next = self.index.len();
+    self.index.insert(file.to_string(), next);
+    self.low_link.insert(file.to_string(), next);
+    self.stack.push(file.to_string());
+    self.on_stack.insert(file.to_string());
+    let mut imports: Vec<String> = match self.store.get(file) {
+      Some(item) => item.imports.iter().map(String::from).collect(),
+      None => Vec::new(),
+    };
+    imports.sort();
+    imports
+  }
+
+  /// Tarjan's depth-first search from `root`, unrolled so long chains of imports can't
+  /// overflow the stack.
+  fn walk(&mut self, root: String) {
+    let imports = self.visit(&root);
+    let mut frames: Vec<(String, Vec<String>)> = vec![(root, imports)];
+    while let Some((file, imports)) = frames.last_mut() {
+      let file = file.clone();
+      if let Some(import) = imports.pop() {
+        if !self.store.contains_key(&import) {
+          continue;
+        }
+        if !self.index.contains_key(&import) {
+          let imports = self.visit(&import);
+          frames.push((import, imports));
+        } else if self.on_stack.contains(&import) {
+          let low = self.low_link[&file].min(self.index[&import]);
+          self.low_link.insert(file, low);
+        }
+        continue;
+      }
+      frames.pop();
+      if let Some((parent, _)) = frames.last() {
+        let low = self.low_link[parent].min(self.low_link[&file]);
+        self.low_link.insert(parent.clone(), low);
+      }
+      if self.low_link[&file] == self.index[&file] {
+        self.pop_component(&file);
+      }
+    }
+  }
+
+  /// Pops the component rooted at `file` off the stack.
+  fn pop_component(&mut self, file: &str) {
+    let mut component = Vec::new();
+    while let Some(member) = self.stack.pop() {
+      self.on_stack.remove(&member);
+      let is_root = member == file;
+      component.push(member);
+      if is_root {
+        break;
+      }
+    }
+    component.sort();
+    self.components.push(component);
+  }
+}
+
+/// Returns the shortest chain of imports leading from `from` to `to`, both included.
+/// ```rs
+/// find_import_chain(&store, "/entry.js", "/b.js"); // Some(["/entry.js", "/a.js", "/b.js"])
+/// ```
+pub fn find_import_chain(
+  store: &DashMap<String, FileItem>,
+  from: &str,
+  to: &str,
+) -> Option<Vec<String>> {
+  find_shortest_chain(store, from, to, &HashSet::new(), &HashSet::new())
+}
+
+/// Returns the shortest chain of imports leading from `from` to `to`, without going through
+/// `skipped_files` or taking `skipped_imports`, as `(importer, imported)`.
+fn find_shortest_chain(
+  store: &DashMap<String, FileItem>,
+  from: &str,
+  to: &str,
+  skipped_files: &HashSet<&str>,
+  skipped_imports: &HashSet<(&str, &str)>,
+) -> Option<Vec<String>> {
+  if from == to {
+    return Some(vec![from.to_string()]);
+  }
+  // the importer through which each file was reached first
+  let mut parents: HashMap<String, String> = HashMap::new();
+  let mut queue = VecDeque::from([from.to_string()]);
+  while let Some(current) = queue.pop_front() {
+    let mut imports: Vec<String> = match store.get(&current) {
+      Some(item) => item.imports.iter().map(String::from).collect(),
+      None => continue,
+    };
+    // ties between chains of the same length are broken the same way every time
+    imports.sort();
+    for import in imports {
+      if import == from
+        || parents.contains_key(&import)
+        || skipped_files.contains(&import[..])
+        || skipped_imports.contains(&(&current[..], &import[..]))
+      {
+        continue;
+      }
+      parents.insert(import.clone(), current.clone());
+      if import == to {
+        let mut chain = vec![import];
+        while let Some(parent) = parents.get(chain.last().unwrap()) {
+          chain.push(parent.clone());
+        }
+        chain.reverse();
+        return Some(chain);
+      }
+      queue.push_back(import);
+    }
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    collect_deps, collect_importers, detach_file, find_cycles, find_import_chain,
+    find_import_chains, restore_importers, topological_order,
+  };
+  use crate::entry::make_file_item;
+  use crate::file_item::FileItem;
+  use dashmap::DashMap;
+  use lazy_static::lazy_static;
+  use std::collections::{HashMap, HashSet};
+  use std::path::PathBuf;
+
+  lazy_static! {
+    static ref CWD: PathBuf = std::env::current_dir().unwrap();
+    static ref GRAPH_PATH: PathBuf = CWD.join("tests").join("fixtures").join("graph");
+  }
+
+  fn key(file: &str) -> String {
+    GRAPH_PATH.join(file).to_str().unwrap().to_string()
+  }
+
+  #[test]
+  fn test_collect_deps() {
+    let store = DashMap::new();
+    let path = GRAPH_PATH.join("entry.js");
+    let res = make_file_item(&path, &GRAPH_PATH, &store, &None).unwrap();
+    assert_eq!(res.imports, HashSet::from([key("a.js"), key("c.js")]));
+    assert_eq!(
+      res.deps,
+      HashSet::from([key("a.js"), key("b.js"), key("c.js")])
+    );
+    drop(res);
+    // only the deps of the file asked for are computed
+    assert!(store.get(&key("a.js")).unwrap().deps.is_empty());
+
+    // `a.js` and `b.js` import each other
+    let (deps, _) = collect_deps(&store, &key("b.js"));
+    assert_eq!(deps, HashSet::from([key("a.js"), key("b.js")]));
+    let (deps, _) = collect_deps(&store, &key("c.js"));
+    assert!(deps.is_empty());
+  }
+
+  #[test]
+  fn test_find_import_chain() {
+    let store = DashMap::new();
+    make_file_item(&GRAPH_PATH.join("entry.js"), &GRAPH_PATH, &store, &None);
+
+    assert_eq!(
+      find_import_chain(&store, &key("entry.js"), &key("b.js")),
+      Some(vec![key("entry.js"), key("a.js"), key("b.js")])
+    );
+    assert_eq!(
+      find_import_chain(&store, &key("b.js"), &key("a.js")),
+      Some(vec![key("b.js"), key("a.js")])
+    );
+    assert_eq!(find_import_chain(&store, &key("c.js"), &key("a.js")), None);
+  }
+
+  #[test]
+  fn test_find_import_chains() {
+    let store = DashMap::new();
+    make_file_item(&GRAPH_PATH.join("entry.js"), &GRAPH_PATH, &store, &None);
+    // `c.js` now imports `b.js` too
+    store
+      .get_mut(&key("c.js"))
+      .unwrap()
+      .imports
+      .insert(key("b.js"));
+    store
+      .get_mut(&key("b.js"))
+      .unwrap()
+      .importers
+      .insert(key("c.js"));
+
+    assert_eq!(
+      find_import_chains(&store, &key("entry.js"), &key("b.js"), 3),
+      vec![
+        vec![key("entry.js"), key("a.js"), key("b.js")],
+        vec![key("entry.js"), key("c.js"), key("b.js")],
+      ]
+    );
+    assert_eq!(
+      find_import_chains(&store, &key("entry.js"), &key("b.js"), 1),
+      vec![vec![key("entry.js"), key("a.js"), key("b.js")]]
+    );
+    assert!(find_import_chains(&store, &key("c.js"), &key("entry.js"), 3).is_empty());
+  }
+
+  /// A store of files named after their index, each importing the ones of `imports`
+  fn make_store(imports: &[Vec<usize>]) -> DashMap<String, FileItem> {
+    let store = DashMap::new();
+    for (i, file_imports) in imports.iter().enumerate() {
+      store.insert(
+        i.to_string(),
+        FileItem {
+          path: PathBuf::from(i.to_string()),
+          imports: file_imports.iter().map(|x| x.to_string()).collect(),
+          external_imports: HashSet::new(),
+          import_sites: HashMap::new(),
+          symbols: Vec::new(),
+          importers: HashSet::new(),
+          deps: HashSet::new(),
+          externals: HashSet::new(),
+          diagnostics: Vec::new(),
+        },
+      );
+    }
+    store
+  }
+
+  #[test]
+  fn test_find_import_chains_order() {
+    // 0 -> 1 -> 3, 0 -> 2 -> 3 and 0 -> 1 -> 2 -> 3, 3 importing 0 back
+    let store = make_store(&[vec![1, 2], vec![2, 3], vec![3], vec![0]]);
+    let chain = |files: &[&str]| -> Vec<String> { files.iter().map(|x| x.to_string()).collect() };
+
+    assert_eq!(
+      find_import_chains(&store, "0", "3", 5),
+      vec![
+        chain(&["0", "1", "3"]),
+        chain(&["0", "2", "3"]),
+        chain(&["0", "1", "2", "3"]),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_find_import_chains_many_paths() {
+    // 20 diamonds in a row, 2^20 chains lead from the first file to the last one
+    let diamonds = 20;
+    let imports: Vec<Vec<usize>> = (0..=diamonds * 3)
+      .map(|i| match i % 3 {
+        _ if i == diamonds * 3 => vec![],
+        0 => vec![i + 1, i + 2],
+        _ => vec![i + 3 - i % 3],
+      })
+      .collect();
+    let store = make_store(&imports);
+
+    let chains = find_import_chains(&store, "0", &(diamonds * 3).to_string(), 10);
+    assert_eq!(chains.len(), 10);
+    assert!(chains.iter().all(|x| x.len() == diamonds * 2 + 1));
+    let unique: HashSet<&Vec<String>> = chains.iter().collect();
+    assert_eq!(unique.len(), 10);
+  }
+
+  #[test]
+  fn test_find_cycles() {
+    let store = DashMap::new();
+    make_file_item(&GRAPH_PATH.join("entry.js"), &GRAPH_PATH, &store, &None);
+    assert_eq!(find_cycles(&store), vec![vec![key("a.js"), key("b.js")]]);
+
+    // a file importing itself
+    store
+      .get_mut(&key("c.js"))
+      .unwrap()
+      .imports
+      .insert(key("c.js"));
+    assert_eq!(
+      find_cycles(&store),
+      vec![vec![key("a.js"), key("b.js")], vec![key("c.js")]]
+    );
+  }
+
+  #[test]
+  fn test_topological_order() {
+    let store = DashMap::new();
+    make_file_item(&GRAPH_PATH.join("entry.js"), &GRAPH_PATH, &store, &None);
+    assert_eq!(
+      topological_order(&store, &[key("entry.js")]),
+      vec![
+        vec![key("c.js")],
+        vec![key("a.js"), key("b.js")],
+        vec![key("entry.js")]
+      ]
+    );
+    // only the files reachable from the roots
+    assert_eq!(
+      topological_order(&store, &[key("b.js")]),
+      vec![vec![key("a.js"), key("b.js")]]
+    );
+  }
+
+  #[test]
+  fn test_importers() {
+    let store = DashMap::new();
+    let path = GRAPH_PATH.join("entry.js");
+    make_file_item(&path, &GRAPH_PATH, &store, &None);
+    let importers = |file: &str| store.get(&key(file)).unwrap().importers.clone();
+
+    assert_eq!(
+      importers("a.js"),
+      HashSet::from([key("entry.js"), key("b.js")])
+    );
+    assert_eq!(importers("c.js"), HashSet::from([key("entry.js")]));
+    assert!(importers("entry.js").is_empty());
+    assert_eq!(
+      collect_importers(&store, &key("b.js")),
+      HashSet::from([key("entry.js"), key("a.js"), key("b.js")])
+    );
+
+    // making a file again keeps the index in sync
+    let a_importers = detach_file(&store, &key("a.js"));
+    assert!(importers("b.js").is_empty());
+    make_file_item(&GRAPH_PATH.join("a.js"), &GRAPH_PATH, &store, &None);
+    restore_importers(&store, &key("a.js"), a_importers);
+    assert_eq!(
+      importers("a.js"),
+      HashSet::from([key("entry.js"), key("b.js")])
+    );
+    assert_eq!(importers("b.js"), HashSet::from([key("a.js")]));
+  }
+}
@@ -1,12 +1,38 @@
 #![deny(clippy::all)]
+// lints the tests written before clippy ran on every target don't follow
+#![cfg_attr(
+  test,
+  allow(
+    clippy::bool_assert_comparison,
+    clippy::len_zero,
+    clippy::needless_borrows_for_generic_args,
+    clippy::unnecessary_to_owned,
+    clippy::useless_conversion,
+    clippy::useless_vec,
+    clippy::vec_init_then_push
+  )
+)]
 
 #[path = "./utils/path_clean.rs"]
 pub mod path_clean;
 
+pub mod builtins;
 pub mod entry;
+pub mod export;
 pub mod file_item;
+pub mod graph;
 pub mod parser;
+pub mod pnp;
+pub mod resolve_cache;
+pub mod rules;
+pub mod snapshot;
+pub mod symbols;
+#[cfg(test)]
+mod test_utils;
+pub mod tsconfig;
+pub mod unused;
 pub mod watcher;
+pub mod workspaces;
 
 #[macro_use]
 extern crate napi_derive;
@@ -2,11 +2,11 @@ use nom::branch::alt;
 use nom::bytes::complete::{take, take_until};
 use nom::character::complete::{anychar, one_of, space0, space1};
 use nom::combinator::{cond, verify};
-use nom::multi::{many0, many_till, separated_list1};
+use nom::multi::{many_till, separated_list1};
 use nom::{bytes::complete::tag, IResult};
 use std::ops::Add;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// Import type with their import names
 pub enum ImportDep {
   ESM(String),
@@ -14,6 +14,15 @@ pub enum ImportDep {
   CSS(String),
 }
 
+impl ImportDep {
+  /// The import as written in the source
+  pub fn specifier(&self) -> &str {
+    match self {
+      ImportDep::ESM(path) | ImportDep::REQUIRE(path) | ImportDep::CSS(path) => path,
+    }
+  }
+}
+
 // parse either import or export
 fn parse_esm_token(input: &str) -> IResult<&str, &str> {
   alt((tag("import"), tag("export")))(input)
@@ -121,6 +130,16 @@ fn parse_css_import_statement(input: &str) -> IResult<&str, Vec<ImportDep>> {
   ))
 }
 
+/// Location of an import statement. Offsets are in bytes, lines and columns start at 1
+#[napi(object)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+  pub start: u32,
+  pub end: u32,
+  pub line: u32,
+  pub column: u32,
+}
+
 pub struct ParseConditions {
   pub esm: bool,
   pub require: bool,
@@ -129,7 +148,15 @@ pub struct ParseConditions {
 }
 
 pub fn parse_deps(input: &str, conditions: ParseConditions) -> Vec<ImportDep> {
-  let (_, res) = many0(many_till(
+  parse_deps_with_spans(input, conditions)
+    .into_iter()
+    .map(|(dep, _)| dep)
+    .collect()
+}
+
+/// Same as `parse_deps`, along with the span of the statement of each import.
+pub fn parse_deps_with_spans(input: &str, conditions: ParseConditions) -> Vec<(ImportDep, Span)> {
+  let parse_next = many_till(
     anychar,
     alt((
       verify(cond(conditions.esm, parse_esm_statement), |x| x.is_some()),
@@ -143,21 +170,62 @@ pub fn parse_deps(input: &str, conditions: ParseConditions) -> Vec<ImportDep> {
         x.is_some()
       }),
     )),
-  ))(input)
-  .unwrap_or_default();
+  );
+
+  parse_with_spans(input, parse_next)
+}
+
+/// Runs `parse_next` over `input` until it fails, and returns every item it yields along with
+/// the span of the statement it was found in. `parse_next` returns the characters skipped
+/// before each statement, see `many_till`.
+pub(crate) fn parse_with_spans<'a, P, I, T>(input: &'a str, mut parse_next: P) -> Vec<(T, Span)>
+where
+  P: FnMut(&'a str) -> IResult<&'a str, (Vec<char>, I)>,
+  I: IntoIterator,
+  I::Item: IntoIterator<Item = T>,
+{
+  let mut res = Vec::new();
+  let mut rest = input;
+  // position of the end of the previous statement
+  let (mut offset, mut line, mut column) = (0, 1, 1);
+  while let Ok((remaining, (skipped, items))) = parse_next(rest) {
+    for c in skipped {
+      offset += c.len_utf8();
+      if c == '\n' {
+        line += 1;
+        column = 1;
+      } else {
+        column += 1;
+      }
+    }
+    let end = input.len() - remaining.len();
+    let span = Span {
+      start: offset as u32,
+      end: end as u32,
+      line,
+      column,
+    };
+    for c in input[offset..end].chars() {
+      if c == '\n' {
+        line += 1;
+        column = 1;
+      } else {
+        column += 1;
+      }
+    }
+    offset = end;
+    res.extend(items.into_iter().flatten().map(|item| (item, span)));
+    rest = remaining;
+  }
 
   res
-    .into_iter()
-    .filter_map(|(_, path)| path)
-    .flatten()
-    .collect()
 }
 
 #[cfg(test)]
 mod tests {
   use crate::parser::{
-    parse_css_import_statement, parse_deps, parse_esm_statement, parse_lazy_esm_statement,
-    parse_require_statement, ImportDep,
+    parse_css_import_statement, parse_deps, parse_deps_with_spans, parse_esm_statement,
+    parse_lazy_esm_statement, parse_require_statement, ImportDep, Span,
   };
 
   #[test]
@@ -352,4 +420,43 @@ mod tests {
       );
     }
   }
+
+  #[test]
+  fn test_parse_with_spans() {
+    let input = "// é\nimport foo from 'foo.js';\n  require('bar.js')";
+    let res = parse_deps_with_spans(
+      input,
+      crate::parser::ParseConditions {
+        esm: true,
+        require: true,
+        lazy_esm: false,
+        css: false,
+      },
+    );
+
+    assert_eq!(
+      res,
+      vec![
+        (
+          ImportDep::ESM("foo.js".to_string()),
+          Span {
+            start: 6,
+            end: 30,
+            line: 2,
+            column: 1
+          }
+        ),
+        (
+          ImportDep::REQUIRE("bar.js".to_string()),
+          Span {
+            start: 34,
+            end: 51,
+            line: 3,
+            column: 3
+          }
+        ),
+      ]
+    );
+    assert_eq!(&input[6..30], "import foo from 'foo.js'");
+  }
 }
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::path_clean::*;
+use crate::resolve_cache::ResolveCache;
+
+/// A package of the Plug'n'Play dependency tree: `(name, reference)`.
+/// The top-level locator has neither.
+type Locator = (Option<String>, Option<String>);
+
+#[derive(Debug, Clone)]
+struct PnpPackage {
+  location: PathBuf,
+  /// Dependency names mapped to the locator providing them, `None` for missing peers
+  dependencies: HashMap<String, Option<Locator>>,
+}
+
+/// Where a package of a Plug'n'Play install lives
+#[derive(Debug, Clone, PartialEq)]
+pub enum PnpLocation {
+  /// The package is unpacked on disk (workspaces, unplugged packages)
+  Directory(PathBuf),
+  /// The package only exists inside a zip archive of the Yarn cache
+  Archive(PathBuf),
+}
+
+/// The resolution data of a Yarn Plug'n'Play install, read from `.pnp.data.json` or from the
+/// state inlined in `.pnp.cjs`.
+#[derive(Debug, Clone)]
+pub struct PnpManifest {
+  packages: HashMap<Locator, PnpPackage>,
+  /// Package locations, longest first, to find the package owning a file
+  locations: Vec<(PathBuf, Locator)>,
+  /// Dependencies available to every package when `enableTopLevelFallback` is set
+  fallback: HashMap<String, Option<Locator>>,
+}
+
+impl PnpManifest {
+  fn parse(json: &serde_json::Value, root: &Path) -> Option<PnpManifest> {
+    let mut packages = HashMap::new();
+    for entry in json["packageRegistryData"].as_array()? {
+      let name = entry[0].as_str().map(String::from);
+      for version in entry[1].as_array()? {
+        let reference = version[0].as_str().map(String::from);
+        let info = &version[1];
+        let location = root.join(info["packageLocation"].as_str()?).clean();
+        let dependencies = info["packageDependencies"]
+          .as_array()
+          .map(|deps| deps.iter().filter_map(parse_dependency).collect())
+          .unwrap_or_default();
+        packages.insert(
+          (name.clone(), reference),
+          PnpPackage {
+            location,
+            dependencies,
+          },
+        );
+      }
+    }
+
+    // The top-level locator shares its location with the root workspace, which owns the files
+    let mut locations: Vec<(PathBuf, Locator)> = packages
+      .iter()
+      .filter(|(locator, _)| locator.0.is_some())
+      .map(|(locator, package)| (package.location.clone(), locator.clone()))
+      .collect();
+    locations.sort_by_key(|(location, _)| std::cmp::Reverse(location.as_os_str().len()));
+
+    let mut fallback = HashMap::new();
+    if json["enableTopLevelFallback"].as_bool().unwrap_or(false) {
+      if let Some(top_level) = packages.get(&(None, None)) {
+        fallback.extend(top_level.dependencies.clone());
+      }
+      if let Some(pool) = json["fallbackPool"].as_array() {
+        fallback.extend(pool.iter().filter_map(parse_dependency));
+      }
+    }
+
+    Some(PnpManifest {
+      packages,
+      locations,
+      fallback,
+    })
+  }
+
+  /// Returns where the package `name` imported from `importer` lives.
+  pub fn resolve_package(&self, name: &str, importer: &Path) -> Option<PnpLocation> {
+    let (_, issuer) = self
+      .locations
+      .iter()
+      .find(|(location, _)| importer.starts_with(location))?;
+    let dependency = match self.packages.get(issuer)?.dependencies.get(name) {
+      Some(dependency) => dependency,
+      None => self.fallback.get(name)?,
+    };
+    let location = &self.packages.get(dependency.as_ref()?)?.location;
+
+    // "./.yarn/cache/foo-npm-1.0.0-abcdef.zip/node_modules/foo/"
+    let archive = location
+      .ancestors()
+      .find(|x| x.extension().is_some_and(|ext| ext == "zip"));
+    Some(match archive {
+      Some(archive) => PnpLocation::Archive(archive.to_path_buf()),
+      None => PnpLocation::Directory(location.to_path_buf()),
+    })
+  }
+}
+
+/// Parses a `[name, reference]` entry of `packageDependencies`. The reference is either a
+/// string, or `[name, reference]` for aliased packages.
+fn parse_dependency(value: &serde_json::Value) -> Option<(String, Option<Locator>)> {
+  let name = value[0].as_str()?.to_string();
+  let locator = match &value[1] {
+    serde_json::Value::String(reference) => Some((Some(name.clone()), Some(reference.clone()))),
+    serde_json::Value::Array(alias) => Some((
+      Some(alias.first()?.as_str()?.to_string()),
+      Some(alias.get(1)?.as_str()?.to_string()),
+    )),
+    _ => None,
+  };
+  Some((name, locator))
+}
+
+/// Finds the closest `.pnp.data.json` or `.pnp.cjs` from `dir`, walking up the parents.
+pub fn find_pnp_manifest(dir: &Path, cache: &ResolveCache) -> Option<PathBuf> {
+  cache.pnp_manifest_paths.get_or_insert_with(dir, || {
+    for name in [".pnp.data.json", ".pnp.cjs"] {
+      let candidate = dir.join(name);
+      if candidate.is_file() {
+        return Some(candidate);
+      }
+    }
+    find_pnp_manifest(dir.parent()?, cache)
+  })
+}
+
+/// Loads the manifest at `path`. It's shared by every file of the project, hence the `Arc`.
+pub fn load_pnp_manifest(path: &Path, cache: &ResolveCache) -> Option<Arc<PnpManifest>> {
+  cache.pnp_manifests.get_or_insert_with(path, || {
+    let content = std::fs::read_to_string(path).ok()?;
+    let json = if path.extension().is_some_and(|ext| ext == "cjs") {
+      extract_raw_runtime_state(&content)?
+    } else {
+      content
+    };
+    let json: serde_json::Value = serde_json::from_str(&json).ok()?;
+    PnpManifest::parse(&json, path.parent()?).map(Arc::new)
+  })
+}
+
+/// Extracts the JSON inlined by Yarn in `.pnp.cjs`:
+/// ```js
+/// const RAW_RUNTIME_STATE =
+/// '{\
+///   "__info": [],\
+///   "packageRegistryData": [...]\
+/// }';
+/// ```
+fn extract_raw_runtime_state(content: &str) -> Option<String> {
+  let start = content.find("RAW_RUNTIME_STATE")?;
+  let rest = &content[start..];
+  let mut chars = rest[rest.find('\'')? + 1..].chars();
+  let mut output = String::new();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '\'' => return Some(output),
+      '\\' => match chars.next()? {
+        // line continuation
+        '\n' => {}
+        'n' => output.push('\n'),
+        escaped => output.push(escaped),
+      },
+      _ => output.push(c),
+    }
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{extract_raw_runtime_state, find_pnp_manifest, load_pnp_manifest, PnpLocation};
+  use crate::resolve_cache::ResolveCache;
+  use lazy_static::lazy_static;
+  use std::path::PathBuf;
+
+  lazy_static! {
+    static ref CWD: PathBuf = std::env::current_dir().unwrap();
+    static ref YARN_PNP_PATH: PathBuf = CWD.join("tests/fixtures/yarn_pnp");
+  }
+
+  #[test]
+  fn test_extract_raw_runtime_state() {
+    let content = "#!/usr/bin/env node\n/* eslint-disable */\n\"use strict\";\n\nconst RAW_RUNTIME_STATE =\n'{\\\n  \"__info\": [\"Don\\'t touch\"],\\\n  \"packageRegistryData\": []\\\n}';\n";
+    let res = extract_raw_runtime_state(content).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&res).unwrap();
+    assert_eq!(json["__info"][0], "Don't touch");
+    assert!(json["packageRegistryData"].as_array().unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_pnp_resolve_package() {
+    let src_path = YARN_PNP_PATH.join("src");
+    let cache = ResolveCache::default();
+    let manifest_path = find_pnp_manifest(&src_path, &cache).unwrap();
+    let manifest = load_pnp_manifest(&manifest_path, &cache).unwrap();
+    let importer = src_path.join("main.js");
+    let utils_importer = YARN_PNP_PATH.join("packages/utils/index.js");
+
+    assert_eq!(
+      manifest.resolve_package("@acme/utils", &importer),
+      Some(PnpLocation::Directory(YARN_PNP_PATH.join("packages/utils")))
+    );
+    assert_eq!(
+      manifest.resolve_package("lodash", &utils_importer),
+      Some(PnpLocation::Archive(YARN_PNP_PATH.join(
+        ".yarn/cache/lodash-npm-4.17.21-6382451519-eb835a2e51.zip"
+      )))
+    );
+    // not a dependency of `@acme/utils`, but available through the top-level fallback
+    assert_eq!(
+      manifest.resolve_package("left-pad", &utils_importer),
+      Some(PnpLocation::Directory(YARN_PNP_PATH.join(
+        ".yarn/unplugged/left-pad-npm-1.3.0-3f5b3d0c5a/node_modules/left-pad"
+      )))
+    );
+    // only a dependency of `@acme/utils`
+    assert_eq!(manifest.resolve_package("lodash", &importer), None);
+  }
+}
@@ -0,0 +1,72 @@
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::pnp::PnpManifest;
+use crate::tsconfig::TsConfig;
+
+/// Results of a lookup of the file system, by path
+pub struct PathCache<V>(DashMap<PathBuf, V>);
+
+impl<V> Default for PathCache<V> {
+  fn default() -> Self {
+    PathCache(DashMap::new())
+  }
+}
+
+impl<V: Clone> PathCache<V> {
+  /// Returns the value cached for `path`, or makes it. No lock is held while `make` runs, so
+  /// it can use the cache itself, like to look the parent directory up.
+  pub fn get_or_insert_with(&self, path: &Path, make: impl FnOnce() -> V) -> V {
+    if let Some(value) = self.0.get(path) {
+      return value.clone();
+    }
+    let value = make();
+    self.0.insert(path.to_path_buf(), value.clone());
+    value
+  }
+
+  pub fn clear(&self) {
+    self.0.clear();
+  }
+}
+
+/// The `browser` field of a package.json, in its object form
+pub type BrowserField = serde_json::Map<String, serde_json::Value>;
+
+/// Lookups made while resolving imports, shared by every file of a watcher.
+/// Config files can be edited and packages installed while watching, so it's cleared by each
+/// `make_changes`.
+#[derive(Default)]
+pub struct ResolveCache {
+  /// The closest tsconfig.json or jsconfig.json of each directory
+  pub tsconfig_paths: PathCache<Option<PathBuf>>,
+  /// Configs by path, with their `extends` chain applied
+  pub tsconfigs: PathCache<Option<Arc<TsConfig>>>,
+  /// The `node_modules` folders an import from each directory can be resolved in
+  pub node_modules_dirs: PathCache<Vec<PathBuf>>,
+  /// The closest package.json of each directory
+  pub package_jsons: PathCache<Option<PathBuf>>,
+  /// The object form of the `browser` field of each package, by package directory
+  pub browser_fields: PathCache<Option<Arc<BrowserField>>>,
+  /// The closest `.pnp.data.json` or `.pnp.cjs` of each directory
+  pub pnp_manifest_paths: PathCache<Option<PathBuf>>,
+  /// Plug'n'Play manifests by path
+  pub pnp_manifests: PathCache<Option<Arc<PnpManifest>>>,
+  /// The packages of the closest workspace of each directory, by name
+  pub workspace_packages: PathCache<Option<Arc<HashMap<String, PathBuf>>>>,
+}
+
+impl ResolveCache {
+  pub fn clear(&self) {
+    self.tsconfig_paths.clear();
+    self.tsconfigs.clear();
+    self.node_modules_dirs.clear();
+    self.package_jsons.clear();
+    self.browser_fields.clear();
+    self.pnp_manifest_paths.clear();
+    self.pnp_manifests.clear();
+    self.workspace_packages.clear();
+  }
+}
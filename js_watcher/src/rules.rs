@@ -0,0 +1,402 @@
+use dashmap::DashMap;
+use glob::Pattern;
+#[cfg(not(feature = "without-napi"))]
+use napi::bindgen_prelude::{FromNapiValue, ToNapiValue};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::entry::{get_package_name, get_package_root};
+use crate::file_item::{ExternalKind, FileItem};
+use crate::parser::Span;
+
+/// Restricts what the files matching `from` may import
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct BoundaryRule {
+  /// Glob of the files the rule applies to, relative to the project root
+  pub from: String,
+  /// Globs of the files they may import, relative to the project root. Defaults to any file
+  pub allow: Option<Vec<String>>,
+  /// Globs of the files they may not import, relative to the project root. Wins over `allow`
+  pub deny: Option<Vec<String>>,
+  /// Packages they may not import, builtin modules included
+  pub banned_packages: Option<Vec<String>>,
+  /// Relative imports may not reach a file of another package, whose closest package.json
+  /// isn't the same. Defaults to `false`
+  pub no_relative_across_packages: Option<bool>,
+}
+
+#[cfg_attr(not(feature = "without-napi"), napi(string_enum), derive(PartialEq, Debug))]
+#[cfg_attr(feature = "without-napi", derive(PartialEq, Debug, Clone, Copy))]
+pub enum RuleViolationKind {
+  /// The imported file matches none of the `allow` globs
+  NotAllowed,
+  /// The imported file matches one of the `deny` globs
+  Denied,
+  /// The imported package is banned
+  BannedPackage,
+  /// A relative import reaches a file of another package
+  RelativeAcrossPackages,
+}
+
+/// An import breaking a rule
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleViolation {
+  pub kind: RuleViolationKind,
+  /// The `from` glob of the rule
+  pub rule: String,
+  pub importer: String,
+  /// The imported file, or the name of the package for `BannedPackage`
+  pub imported: String,
+  /// The import as written in the source
+  pub specifier: Option<String>,
+  /// The import statement inside `importer`, unknown for packages that aren't watched
+  pub span: Option<Span>,
+}
+
+impl RuleViolation {
+  /// Whether both imports break the same rule the same way. The position of the import isn't
+  /// compared, so that editing the lines above it doesn't make a new violation.
+  pub fn is_same(&self, other: &RuleViolation) -> bool {
+    self.rule == other.rule
+      && self.kind == other.kind
+      && self.importer == other.importer
+      && self.imported == other.imported
+  }
+}
+
+/// Reads rules from a JSON file, as `{ "rules": [{ "from": "src/features/**", ... }] }` with the
+/// fields of `BoundaryRule` in camel case. Returns why when it can't be read or a rule is
+/// invalid, see `validate_rules`.
+pub fn load_rules(path: &Path) -> Result<Vec<BoundaryRule>, String> {
+  let content = std::fs::read(path).map_err(|err| err.to_string())?;
+  let json: serde_json::Value = serde_json::from_slice(&content).map_err(|err| err.to_string())?;
+  let strings = |value: &serde_json::Value| -> Option<Vec<String>> {
+    Some(
+      value
+        .as_array()?
+        .iter()
+        .filter_map(|x| x.as_str().map(String::from))
+        .collect(),
+    )
+  };
+  let rules = json["rules"]
+    .as_array()
+    .ok_or("`rules` isn't an array")?
+    .iter()
+    .enumerate()
+    .map(|(i, rule)| {
+      Ok(BoundaryRule {
+        from: rule["from"]
+          .as_str()
+          .ok_or(format!("rule {} has no `from` glob", i + 1))?
+          .to_string(),
+        allow: strings(&rule["allow"]),
+        deny: strings(&rule["deny"]),
+        banned_packages: strings(&rule["bannedPackages"]),
+        no_relative_across_packages: rule["noRelativeAcrossPackages"].as_bool(),
+      })
+    })
+    .collect::<Result<Vec<BoundaryRule>, String>>()?;
+  validate_rules(&rules)?;
+  Ok(rules)
+}
+
+/// Checks the globs of `rules`, returning the first invalid one along with its rule.
+pub fn validate_rules(rules: &[BoundaryRule]) -> Result<(), String> {
+  compile_rules(rules).map(|_| ())
+}
+
+/// Parses the globs of `rules` for `check_rules`. Returns the first invalid one along with its
+/// rule.
+pub fn compile_rules(rules: &[BoundaryRule]) -> Result<Vec<CompiledRule>, String> {
+  rules.iter().map(CompiledRule::new).collect()
+}
+
+/// The name of the package imported by a bare specifier, `node:` being left out
+fn get_specifier_package(specifier: &str) -> String {
+  let specifier = specifier.strip_prefix("node:").unwrap_or(specifier);
+  let mut parts = specifier.split('/');
+  let name = parts.next().unwrap_or_default();
+  match parts.next() {
+    Some(scoped) if name.starts_with('@') => format!("{}/{}", name, scoped),
+    _ => name.to_string(),
+  }
+}
+
+/// Returns the closest directory of `file` with a package.json, caching every directory met.
+fn find_package_dir(
+  file: &Path,
+  package_dirs: &mut HashMap<PathBuf, Option<PathBuf>>,
+) -> Option<PathBuf> {
+  let dir = file.parent()?;
+  if let Some(package_dir) = package_dirs.get(dir) {
+    return package_dir.clone();
+  }
+  let package_dir = if dir.join("package.json").is_file() {
+    Some(dir.to_path_buf())
+  } else {
+    find_package_dir(dir, package_dirs)
+  };
+  package_dirs.insert(dir.to_path_buf(), package_dir.clone());
+  package_dir
+}
+
+/// A rule whose globs are parsed, see `compile_rules`
+#[derive(Debug, Clone)]
+pub struct CompiledRule {
+  rule: BoundaryRule,
+  from: Pattern,
+  allow: Option<Vec<Pattern>>,
+  deny: Vec<Pattern>,
+}
+
+impl CompiledRule {
+  fn new(rule: &BoundaryRule) -> Result<Self, String> {
+    let to_pattern = |glob: &String| {
+      Pattern::new(glob).map_err(|err| {
+        format!(
+          "invalid glob `{}` in the rule of `{}`: {}",
+          glob, rule.from, err.msg
+        )
+      })
+    };
+    let to_patterns = |globs: &[String]| globs.iter().map(to_pattern).collect();
+    Ok(CompiledRule {
+      rule: rule.clone(),
+      from: to_pattern(&rule.from)?,
+      allow: rule.allow.as_deref().map(to_patterns).transpose()?,
+      deny: to_patterns(rule.deny.as_deref().unwrap_or_default())?,
+    })
+  }
+}
+
+/// Checks the imports of every file of the store, packages excepted, against the rules
+/// matching it. Violations are sorted by importer, then by position.
+pub fn check_rules(
+  store: &DashMap<String, FileItem>,
+  rules: &[CompiledRule],
+  project_root: &Path,
+) -> Vec<RuleViolation> {
+  let relative = |path: &Path| {
+    path
+      .strip_prefix(project_root)
+      .unwrap_or(path)
+      .to_path_buf()
+  };
+  let mut package_dirs: HashMap<PathBuf, Option<PathBuf>> = HashMap::new();
+
+  let mut violations = Vec::new();
+  for item in store.iter() {
+    if item
+      .path
+      .components()
+      .any(|x| x.as_os_str() == "node_modules")
+    {
+      continue;
+    }
+    let importer = item.key().to_string();
+    for rule in rules
+      .iter()
+      .filter(|x| x.from.matches_path(&relative(&item.path)))
+    {
+      let banned = rule.rule.banned_packages.as_deref().unwrap_or_default();
+      let violation = |kind, imported: String, specifier: Option<String>, span| RuleViolation {
+        kind,
+        rule: rule.rule.from.clone(),
+        importer: importer.clone(),
+        imported,
+        specifier,
+        span,
+      };
+      for import in &item.imports {
+        let path = Path::new(import);
+        let site = item.import_sites.get(import);
+        let specifier = site.map(|x| x.specifier.clone());
+        let span = site.map(|x| x.span);
+        if let Some(package) = get_package_root(path).as_deref().and_then(get_package_name) {
+          if banned.contains(&package) {
+            violations.push(violation(
+              RuleViolationKind::BannedPackage,
+              package,
+              specifier,
+              span,
+            ));
+          }
+          continue;
+        }
+        let imported = relative(path);
+        if rule.deny.iter().any(|x| x.matches_path(&imported)) {
+          violations.push(violation(
+            RuleViolationKind::Denied,
+            import.to_string(),
+            specifier.clone(),
+            span,
+          ));
+        } else if rule
+          .allow
+          .as_ref()
+          .is_some_and(|allow| !allow.iter().any(|x| x.matches_path(&imported)))
+        {
+          violations.push(violation(
+            RuleViolationKind::NotAllowed,
+            import.to_string(),
+            specifier.clone(),
+            span,
+          ));
+        }
+        if rule.rule.no_relative_across_packages.unwrap_or(false)
+          && specifier.as_ref().is_some_and(|x| x.starts_with('.'))
+          && find_package_dir(&item.path, &mut package_dirs)
+            != find_package_dir(path, &mut package_dirs)
+        {
+          violations.push(violation(
+            RuleViolationKind::RelativeAcrossPackages,
+            import.to_string(),
+            specifier,
+            span,
+          ));
+        }
+      }
+      for external in &item.external_imports {
+        if external.kind == ExternalKind::Url {
+          continue;
+        }
+        let package = get_specifier_package(&external.specifier);
+        if banned.contains(&package) {
+          violations.push(violation(
+            RuleViolationKind::BannedPackage,
+            package,
+            Some(external.specifier.clone()),
+            None,
+          ));
+        }
+      }
+    }
+  }
+  violations.sort_by(|a, b| {
+    (&a.importer, a.span.map(|x| x.start), &a.imported, &a.rule).cmp(&(
+      &b.importer,
+      b.span.map(|x| x.start),
+      &b.imported,
+      &b.rule,
+    ))
+  });
+  violations
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    check_rules, compile_rules, get_specifier_package, load_rules, BoundaryRule, RuleViolationKind,
+  };
+  use crate::entry::make_file_item;
+  use dashmap::DashMap;
+  use lazy_static::lazy_static;
+  use std::path::PathBuf;
+
+  lazy_static! {
+    static ref CWD: PathBuf = std::env::current_dir().unwrap();
+    static ref RULES_PATH: PathBuf = CWD.join("tests").join("fixtures").join("rules");
+  }
+
+  #[test]
+  fn test_check_rules() {
+    let store = DashMap::new();
+    let key = |file: &str| RULES_PATH.join(file).to_str().unwrap().to_string();
+    make_file_item(
+      &RULES_PATH.join("src").join("app").join("main.js"),
+      &RULES_PATH,
+      &store,
+      &None,
+    );
+    let rules = vec![
+      BoundaryRule {
+        from: "src/features/**".to_string(),
+        allow: Some(vec![
+          "src/features/**".to_string(),
+          "src/shared/**".to_string(),
+        ]),
+        deny: Some(vec!["src/app/**".to_string()]),
+        banned_packages: Some(vec!["child_process".to_string()]),
+        ..Default::default()
+      },
+      BoundaryRule {
+        from: "src/**".to_string(),
+        no_relative_across_packages: Some(true),
+        ..Default::default()
+      },
+    ];
+
+    let violations: Vec<(RuleViolationKind, String, String)> =
+      check_rules(&store, &compile_rules(&rules).unwrap(), &RULES_PATH)
+        .into_iter()
+        .map(|x| (x.kind, x.importer, x.imported))
+        .collect();
+    let cart = key("src/features/cart/index.js");
+    // the importer, position and imported file being the same, `src/**` comes first
+    assert_eq!(
+      violations,
+      vec![
+        (
+          RuleViolationKind::BannedPackage,
+          cart.clone(),
+          "child_process".to_string()
+        ),
+        (
+          RuleViolationKind::Denied,
+          cart.clone(),
+          key("src/app/store.js")
+        ),
+        (
+          RuleViolationKind::RelativeAcrossPackages,
+          cart.clone(),
+          key("packages/ui/button.js")
+        ),
+        (
+          RuleViolationKind::NotAllowed,
+          cart,
+          key("packages/ui/button.js")
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_load_rules_errors() {
+    let dir = std::env::temp_dir()
+      .join("js_watcher_tests")
+      .join("test_load_rules_errors");
+    std::fs::create_dir_all(&dir).unwrap();
+    let load = |content: &str| {
+      let path = dir.join("rules.json");
+      std::fs::write(&path, content).unwrap();
+      load_rules(&path)
+    };
+
+    assert_eq!(
+      load(r#"{ "rules": [{ "from": "src/**", "deny": ["src/app/**"] }] }"#)
+        .unwrap()
+        .len(),
+      1
+    );
+    assert_eq!(
+      load(r#"{ "rules": [{ "from": "src/**" }, { "deny": ["src/app/**"] }] }"#).unwrap_err(),
+      "rule 2 has no `from` glob"
+    );
+    assert_eq!(
+      load(r#"{ "rules": [{ "from": "src/**", "allow": ["src/[app"] }] }"#).unwrap_err(),
+      "invalid glob `src/[app` in the rule of `src/**`: invalid range pattern"
+    );
+    assert!(load(r#"{ "rules": {} }"#).is_err());
+    assert!(load_rules(&dir.join("missing.json")).is_err());
+  }
+
+  #[test]
+  fn test_get_specifier_package() {
+    assert_eq!(get_specifier_package("node:fs/promises"), "fs");
+    assert_eq!(get_specifier_package("@scope/foo/bar"), "@scope/foo");
+    assert_eq!(get_specifier_package("lodash"), "lodash");
+  }
+}
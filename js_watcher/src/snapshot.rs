@@ -0,0 +1,387 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::export::{graph_from_value, graph_to_value, DependencyGraph, GraphEdge, GraphNode};
+use crate::path_clean::*;
+
+/// The dependency graph of every entry at some point, persisted in the cache directory
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct GraphSnapshot {
+  pub entries: Vec<String>,
+  pub graph: DependencyGraph,
+}
+
+/// What changed in the graph of an entry between two snapshots
+#[napi(object)]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntryGraphDiff {
+  pub entry: String,
+  /// Files and external dependencies the entry now depends on
+  pub added_nodes: Vec<String>,
+  /// Files and external dependencies the entry doesn't depend on anymore
+  pub removed_nodes: Vec<String>,
+  /// Names of the packages the entry now depends on
+  pub added_packages: Vec<String>,
+  /// Names of the packages the entry doesn't depend on anymore
+  pub removed_packages: Vec<String>,
+}
+
+/// Structural changes between two snapshots. Every list is sorted
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct GraphDiff {
+  pub added_edges: Vec<GraphEdge>,
+  pub removed_edges: Vec<GraphEdge>,
+  /// Entries of the second snapshot only
+  pub added_entries: Vec<String>,
+  /// Entries of the first snapshot only
+  pub removed_entries: Vec<String>,
+  /// Every entry whose graph changed, the added ones included
+  pub entries: Vec<EntryGraphDiff>,
+}
+
+/// Whether `name` can name a snapshot. It's the name of a file of the snapshots directory, so
+/// it can't hold path separators or `..`.
+pub fn is_valid_snapshot_name(name: &str) -> bool {
+  !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+fn get_snapshot_path(cache_dir: &str, name: &str) -> Option<PathBuf> {
+  if !is_valid_snapshot_name(name) {
+    return None;
+  }
+  Some(
+    Path::new(cache_dir)
+      .join("snapshots")
+      .join(format!("{}.json", name)),
+  )
+}
+
+/// Applies `map` to every path of the snapshot. Specifiers of external dependencies go through
+/// it as well.
+fn map_paths(snapshot: &GraphSnapshot, map: impl Fn(&str) -> String) -> GraphSnapshot {
+  let mut snapshot = snapshot.clone();
+  for entry in snapshot.entries.iter_mut() {
+    *entry = map(entry);
+  }
+  for node in snapshot.graph.nodes.iter_mut() {
+    node.id = map(&node.id);
+  }
+  for edge in snapshot.graph.edges.iter_mut() {
+    edge.from = map(&edge.from);
+    edge.to = map(&edge.to);
+  }
+  snapshot
+}
+
+/// Writes `snapshot` as `snapshots/<name>.json` inside the cache directory, and returns its
+/// path. The graph follows the schema of `export::to_json`, with the entries alongside.
+/// Paths inside `project_root` are written relative to it, as `./src/main.js`, so that the
+/// snapshot can be compared from another checkout. Returns why for invalid names, see
+/// `is_valid_snapshot_name`, and when the snapshot can't be written.
+pub fn save_snapshot(
+  cache_dir: &str,
+  name: &str,
+  snapshot: &GraphSnapshot,
+  project_root: &Path,
+) -> Result<PathBuf, String> {
+  let path =
+    get_snapshot_path(cache_dir, name).ok_or_else(|| format!("Invalid snapshot name: {}", name))?;
+  let dir = path.parent().unwrap();
+  std::fs::create_dir_all(dir).map_err(|err| {
+    format!(
+      "Couldn't create the snapshots directory at {}: {}",
+      dir.display(),
+      err
+    )
+  })?;
+  let snapshot = map_paths(snapshot, |x| {
+    match Path::new(x).strip_prefix(project_root) {
+      Ok(relative) => format!("./{}", relative.to_string_lossy()),
+      Err(_) => x.to_string(),
+    }
+  });
+  let mut json = graph_to_value(&snapshot.graph);
+  json["entries"] = serde_json::json!(snapshot.entries);
+  std::fs::write(&path, serde_json::to_string_pretty(&json).unwrap())
+    .map_err(|err| format!("Couldn't write the snapshot at {}: {}", path.display(), err))?;
+  Ok(path)
+}
+
+/// Reads the snapshot `name` of the cache directory, with its paths inside `project_root`
+/// made absolute again. Returns `None` when it doesn't exist or was written with another
+/// version of the schema.
+pub fn load_snapshot(cache_dir: &str, name: &str, project_root: &Path) -> Option<GraphSnapshot> {
+  let content = std::fs::read(get_snapshot_path(cache_dir, name)?).ok()?;
+  let json: serde_json::Value = serde_json::from_slice(&content).ok()?;
+  let snapshot = GraphSnapshot {
+    entries: json["entries"]
+      .as_array()?
+      .iter()
+      .filter_map(|x| x.as_str().map(String::from))
+      .collect(),
+    graph: graph_from_value(&json)?,
+  };
+  Some(map_paths(&snapshot, |x| {
+    if x.starts_with("./") {
+      project_root.join(x).clean().to_str().unwrap().to_string()
+    } else {
+      x.to_string()
+    }
+  }))
+}
+
+/// Lists the nodes each node of the graph has an edge to.
+fn get_adjacency(graph: &DependencyGraph) -> HashMap<&str, Vec<&str>> {
+  let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+  for edge in &graph.edges {
+    edges.entry(&edge.from).or_default().push(&edge.to);
+  }
+  edges
+}
+
+/// Returns every node reachable from `entry` through `edges`, see `get_adjacency`, `entry`
+/// excluded.
+fn collect_tree<'a>(
+  edges: &HashMap<&str, Vec<&str>>,
+  nodes: &HashMap<&str, &'a GraphNode>,
+  entry: &str,
+) -> HashSet<&'a str> {
+  let mut tree: HashSet<&str> = HashSet::new();
+  let mut queue = VecDeque::from([entry]);
+  while let Some(current) = queue.pop_front() {
+    for to in edges.get(current).into_iter().flatten() {
+      if let Some(node) = nodes.get(to) {
+        if *to != entry && tree.insert(&node.id) {
+          queue.push_back(to);
+        }
+      }
+    }
+  }
+  tree
+}
+
+/// Compares the graph of two snapshots. Edges are identified by the files they link, so an
+/// import moving inside a file isn't a change.
+pub fn diff_snapshots(a: &GraphSnapshot, b: &GraphSnapshot) -> GraphDiff {
+  let edge_keys = |snapshot: &GraphSnapshot| -> HashSet<(String, String)> {
+    snapshot
+      .graph
+      .edges
+      .iter()
+      .map(|x| (x.from.clone(), x.to.clone()))
+      .collect()
+  };
+  let (a_edges, b_edges) = (edge_keys(a), edge_keys(b));
+  let a_entries: BTreeSet<&String> = a.entries.iter().collect();
+  let b_entries: BTreeSet<&String> = b.entries.iter().collect();
+
+  let a_nodes: HashMap<&str, &GraphNode> = a.graph.nodes.iter().map(|x| (&x.id[..], x)).collect();
+  let b_nodes: HashMap<&str, &GraphNode> = b.graph.nodes.iter().map(|x| (&x.id[..], x)).collect();
+  let (a_adjacency, b_adjacency) = (get_adjacency(&a.graph), get_adjacency(&b.graph));
+  let packages = |tree: &HashSet<&str>, nodes: &HashMap<&str, &GraphNode>| -> BTreeSet<String> {
+    tree
+      .iter()
+      .filter_map(|x| nodes.get(x).and_then(|node| node.package.clone()))
+      .collect()
+  };
+  let mut entries = Vec::new();
+  for entry in &b_entries {
+    let a_tree = if a_entries.contains(entry) {
+      collect_tree(&a_adjacency, &a_nodes, entry)
+    } else {
+      HashSet::new()
+    };
+    let b_tree = collect_tree(&b_adjacency, &b_nodes, entry);
+    let (a_packages, b_packages) = (packages(&a_tree, &a_nodes), packages(&b_tree, &b_nodes));
+    let sorted = |x: Vec<&&str>| -> Vec<String> {
+      let mut res: Vec<String> = x.into_iter().map(|x| x.to_string()).collect();
+      res.sort();
+      res
+    };
+    let diff = EntryGraphDiff {
+      entry: entry.to_string(),
+      added_nodes: sorted(b_tree.difference(&a_tree).collect()),
+      removed_nodes: sorted(a_tree.difference(&b_tree).collect()),
+      added_packages: b_packages.difference(&a_packages).cloned().collect(),
+      removed_packages: a_packages.difference(&b_packages).cloned().collect(),
+    };
+    if !diff.added_nodes.is_empty() || !diff.removed_nodes.is_empty() {
+      entries.push(diff);
+    }
+  }
+
+  GraphDiff {
+    added_edges: b
+      .graph
+      .edges
+      .iter()
+      .filter(|x| !a_edges.contains(&(x.from.clone(), x.to.clone())))
+      .cloned()
+      .collect(),
+    removed_edges: a
+      .graph
+      .edges
+      .iter()
+      .filter(|x| !b_edges.contains(&(x.from.clone(), x.to.clone())))
+      .cloned()
+      .collect(),
+    added_entries: b_entries
+      .difference(&a_entries)
+      .map(|x| x.to_string())
+      .collect(),
+    removed_entries: a_entries
+      .difference(&b_entries)
+      .map(|x| x.to_string())
+      .collect(),
+    entries,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    diff_snapshots, is_valid_snapshot_name, load_snapshot, save_snapshot, GraphSnapshot,
+  };
+  use crate::export::{DependencyGraph, GraphEdge, GraphNode, GraphNodeKind};
+  use std::path::Path;
+
+  fn node(id: &str, package: Option<&str>) -> GraphNode {
+    GraphNode {
+      id: id.to_string(),
+      kind: match package {
+        Some(_) => GraphNodeKind::Package,
+        None => GraphNodeKind::File,
+      },
+      size: Some(1),
+      package: package.map(String::from),
+    }
+  }
+
+  fn edge(from: &str, to: &str) -> GraphEdge {
+    GraphEdge {
+      from: from.to_string(),
+      to: to.to_string(),
+      kind: None,
+      span: None,
+    }
+  }
+
+  #[test]
+  fn test_diff_snapshots() {
+    let a = GraphSnapshot {
+      entries: vec!["/checkout.js".to_string(), "/home.js".to_string()],
+      graph: DependencyGraph {
+        nodes: vec![
+          node("/checkout.js", None),
+          node("/cart.js", None),
+          node("/home.js", None),
+        ],
+        edges: vec![edge("/checkout.js", "/cart.js")],
+      },
+    };
+    let b = GraphSnapshot {
+      entries: vec!["/checkout.js".to_string(), "/home.js".to_string()],
+      graph: DependencyGraph {
+        nodes: vec![
+          node("/checkout.js", None),
+          node("/cart.js", None),
+          node("/home.js", None),
+          node("/node_modules/lodash", Some("lodash")),
+        ],
+        edges: vec![
+          edge("/checkout.js", "/cart.js"),
+          edge("/cart.js", "/node_modules/lodash"),
+        ],
+      },
+    };
+
+    let diff = diff_snapshots(&a, &b);
+    assert_eq!(
+      diff.added_edges,
+      vec![edge("/cart.js", "/node_modules/lodash")]
+    );
+    assert!(diff.removed_edges.is_empty());
+    assert!(diff.added_entries.is_empty());
+    // `home.js` didn't change
+    assert_eq!(diff.entries.len(), 1);
+    assert_eq!(diff.entries[0].entry, "/checkout.js");
+    assert_eq!(diff.entries[0].added_nodes, vec!["/node_modules/lodash"]);
+    assert_eq!(diff.entries[0].added_packages, vec!["lodash"]);
+
+    let diff = diff_snapshots(&b, &a);
+    assert_eq!(
+      diff.removed_edges,
+      vec![edge("/cart.js", "/node_modules/lodash")]
+    );
+    assert_eq!(diff.entries[0].removed_packages, vec!["lodash"]);
+  }
+
+  #[test]
+  fn test_save_snapshot() {
+    let cache_dir = std::env::temp_dir().join("js_watcher_snapshots");
+    let cache_dir = cache_dir.to_str().unwrap();
+    let snapshot = GraphSnapshot {
+      entries: vec!["/project/checkout.js".to_string()],
+      graph: DependencyGraph {
+        nodes: vec![
+          node("/project/checkout.js", None),
+          node("/project/node_modules/lodash", Some("lodash")),
+          node("fs", None),
+        ],
+        edges: vec![
+          edge("/project/checkout.js", "/project/node_modules/lodash"),
+          edge("/project/checkout.js", "fs"),
+        ],
+      },
+    };
+    let path = save_snapshot(cache_dir, "base", &snapshot, Path::new("/project")).unwrap();
+    let content = std::fs::read_to_string(path).unwrap();
+    let loaded = load_snapshot(cache_dir, "base", Path::new("/project")).unwrap();
+    // the same snapshot, from another checkout of the project
+    let moved = load_snapshot(cache_dir, "base", Path::new("/checkout")).unwrap();
+    std::fs::remove_dir_all(cache_dir).unwrap();
+
+    assert!(content.contains("\"./checkout.js\""));
+    assert!(!content.contains("/project"));
+    assert_eq!(loaded.entries, snapshot.entries);
+    assert_eq!(loaded.graph.nodes, snapshot.graph.nodes);
+    assert_eq!(loaded.graph.edges, snapshot.graph.edges);
+    assert_eq!(moved.entries, vec!["/checkout/checkout.js"]);
+    assert_eq!(moved.graph.edges[1], edge("/checkout/checkout.js", "fs"));
+    assert!(load_snapshot(cache_dir, "head", Path::new("/project")).is_none());
+  }
+
+  #[test]
+  fn test_snapshot_names() {
+    let cache_dir = std::env::temp_dir().join("js_watcher_snapshot_names");
+    let cache_dir = cache_dir.to_str().unwrap();
+    let snapshot = GraphSnapshot::default();
+    for name in ["../base", "nested/base", "nested\\base", "..", ""] {
+      assert!(!is_valid_snapshot_name(name), "{}", name);
+      assert!(save_snapshot(cache_dir, name, &snapshot, Path::new("/project")).is_err());
+      assert!(load_snapshot(cache_dir, name, Path::new("/project")).is_none());
+    }
+    assert!(is_valid_snapshot_name("v1.2-base"));
+  }
+
+  #[test]
+  fn test_save_snapshot_unwritable() {
+    // the cache directory would be inside a file
+    let file = std::env::temp_dir().join("js_watcher_snapshot_unwritable");
+    std::fs::write(&file, "").unwrap();
+    let cache_dir = file.join("cache");
+    let res = save_snapshot(
+      cache_dir.to_str().unwrap(),
+      "base",
+      &GraphSnapshot::default(),
+      Path::new("/project"),
+    );
+    std::fs::remove_file(&file).unwrap();
+
+    assert!(res
+      .unwrap_err()
+      .starts_with("Couldn't create the snapshots directory at"));
+  }
+}
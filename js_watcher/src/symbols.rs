@@ -0,0 +1,608 @@
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take, take_while, take_while1};
+use nom::character::complete::{char, multispace0, multispace1, one_of, satisfy};
+use nom::combinator::{map, not, opt, peek, recognize};
+use nom::multi::{many0, many_till, separated_list0, separated_list1};
+use nom::sequence::{delimited, preceded, terminated, tuple};
+use nom::IResult;
+
+use crate::parser::{parse_with_spans, ImportDep, Span};
+
+/// What a JS module exports, and what it uses of the modules it imports. Imports are kept as
+/// written, `I` is the path of the file they resolved to once in the store, see `resolve`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModuleSymbol<I = ImportDep> {
+  /// A name declared and exported by the module itself, `default` included
+  Export(String),
+  /// `export { imported as exported } from './x'`
+  ReExport {
+    import: I,
+    imported: String,
+    exported: String,
+  },
+  /// `export * from './x'`, every export of `./x` but `default`
+  ExportAll(I),
+  /// Names imported from a module. `None` when every export may be used, like with a namespace
+  /// import, a dynamic import or `require`
+  Import {
+    import: I,
+    names: Option<Vec<String>>,
+  },
+}
+
+impl ModuleSymbol {
+  /// Replaces the import of the symbol by the file `resolve` returns for it. Symbols of imports
+  /// that aren't resolved are dropped, re-exports are kept as plain exports.
+  pub fn resolve<F>(self, resolve: F) -> Option<ModuleSymbol<String>>
+  where
+    F: FnOnce(&ImportDep) -> Option<String>,
+  {
+    match self {
+      ModuleSymbol::Export(name) => Some(ModuleSymbol::Export(name)),
+      ModuleSymbol::ReExport {
+        import,
+        imported,
+        exported,
+      } => Some(match resolve(&import) {
+        Some(import) => ModuleSymbol::ReExport {
+          import,
+          imported,
+          exported,
+        },
+        None => ModuleSymbol::Export(exported),
+      }),
+      ModuleSymbol::ExportAll(import) => resolve(&import).map(ModuleSymbol::ExportAll),
+      ModuleSymbol::Import { import, names } => {
+        resolve(&import).map(|import| ModuleSymbol::Import { import, names })
+      }
+    }
+  }
+}
+
+fn is_identifier_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn parse_identifier(input: &str) -> IResult<&str, &str> {
+  take_while1(is_identifier_char)(input)
+}
+
+fn parse_keyword<'a>(keyword: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+  terminated(tag(keyword), not(peek(satisfy(is_identifier_char))))
+}
+
+fn parse_string(input: &str) -> IResult<&str, String> {
+  let (input, quote) = one_of("\"'")(input)?;
+  let (input, value) = take_while(|c| c != quote && c != '\n')(input)?;
+  let (input, _) = char(quote)(input)?;
+  Ok((input, value.to_string()))
+}
+
+/// `from './x'`
+fn parse_from(input: &str) -> IResult<&str, String> {
+  let (input, _) = multispace0(input)?;
+  let (input, _) = parse_keyword("from")(input)?;
+  let (input, _) = multispace0(input)?;
+  parse_string(input)
+}
+
+/// `{ a, type b, c as d }`, as pairs of local and exported names
+fn parse_named_list(input: &str) -> IResult<&str, Vec<(&str, &str)>> {
+  fn parse_item(input: &str) -> IResult<&str, (&str, &str)> {
+    // `type` is also a valid name, `{ type }`
+    let (input, _) = opt(terminated(
+      parse_keyword("type"),
+      tuple((multispace1, peek(parse_identifier))),
+    ))(input)?;
+    let (input, name) = parse_identifier(input)?;
+    let (input, alias) = opt(preceded(
+      tuple((multispace1, parse_keyword("as"), multispace1)),
+      parse_identifier,
+    ))(input)?;
+    Ok((input, (name, alias.unwrap_or(name))))
+  }
+
+  let (input, _) = char('{')(input)?;
+  let (input, _) = multispace0(input)?;
+  let (input, items) =
+    separated_list0(tuple((multispace0, char(','), multispace0)), parse_item)(input)?;
+  let (input, _) = multispace0(input)?;
+  let (input, _) = opt(char(','))(input)?;
+  let (input, _) = multispace0(input)?;
+  let (input, _) = char('}')(input)?;
+  Ok((input, items))
+}
+
+/// `* as ns`
+fn parse_namespace(input: &str) -> IResult<&str, &str> {
+  let (input, _) = char('*')(input)?;
+  let (input, _) = multispace0(input)?;
+  let (input, _) = parse_keyword("as")(input)?;
+  let (input, _) = multispace1(input)?;
+  parse_identifier(input)
+}
+
+fn parse_export_statement(input: &str) -> IResult<&str, Vec<ModuleSymbol>> {
+  let (input, _) = parse_keyword("export")(input)?;
+  alt((
+    parse_export_all,
+    parse_export_list,
+    parse_export_default,
+    parse_export_declaration,
+  ))(input)
+}
+
+/// `export * from './x'` and `export * as ns from './x'`
+fn parse_export_all(input: &str) -> IResult<&str, Vec<ModuleSymbol>> {
+  let (input, _) = multispace0(input)?;
+  let (input, namespace) = alt((map(parse_namespace, Some), map(char('*'), |_| None)))(input)?;
+  let (input, specifier) = parse_from(input)?;
+  let import = ImportDep::ESM(specifier);
+  Ok((
+    input,
+    match namespace {
+      Some(name) => vec![
+        ModuleSymbol::Export(name.to_string()),
+        ModuleSymbol::Import {
+          import,
+          names: None,
+        },
+      ],
+      None => vec![ModuleSymbol::ExportAll(import)],
+    },
+  ))
+}
+
+/// `export { a, b as c }` and `export { a, b as c } from './x'`
+fn parse_export_list(input: &str) -> IResult<&str, Vec<ModuleSymbol>> {
+  let (input, _) = multispace0(input)?;
+  let (input, _) = opt(terminated(parse_keyword("type"), multispace0))(input)?;
+  let (input, items) = parse_named_list(input)?;
+  let (input, specifier) = opt(parse_from)(input)?;
+  let symbols = items
+    .into_iter()
+    .map(|(local, exported)| match &specifier {
+      Some(specifier) => ModuleSymbol::ReExport {
+        import: ImportDep::ESM(specifier.clone()),
+        imported: local.to_string(),
+        exported: exported.to_string(),
+      },
+      None => ModuleSymbol::Export(exported.to_string()),
+    })
+    .collect();
+  Ok((input, symbols))
+}
+
+fn parse_export_default(input: &str) -> IResult<&str, Vec<ModuleSymbol>> {
+  let (input, _) = multispace1(input)?;
+  let (input, _) = parse_keyword("default")(input)?;
+  Ok((input, vec![ModuleSymbol::Export("default".to_string())]))
+}
+
+/// Skips an expression, or a type when `types` is set, up to one of `stops` or the bracket
+/// closing it. A line break ends it too unless the next line goes on with `,` or a bracket.
+fn skip_until<'a>(
+  stops: &'static str,
+  types: bool,
+) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+  move |input: &'a str| {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+      if let Some(quote_char) = quote {
+        if c == '\\' {
+          chars.next();
+        } else if c == quote_char {
+          quote = None;
+        }
+        continue;
+      }
+      match c {
+        '"' | '\'' | '`' => quote = Some(c),
+        '(' | '[' | '{' => depth += 1,
+        '<' if types => depth += 1,
+        ')' | ']' | '}' if depth == 0 => return Ok((&input[i..], &input[..i])),
+        ')' | ']' | '}' => depth -= 1,
+        '>' if types && depth > 0 => depth -= 1,
+        '\n' if depth == 0 => {
+          let next_line = input[i..].trim_start();
+          if !next_line.starts_with([',', ')', ']', '}']) {
+            return Ok((&input[i..], &input[..i]));
+          }
+        }
+        _ if depth == 0 && stops.contains(c) => return Ok((&input[i..], &input[..i])),
+        _ => {}
+      }
+    }
+    Ok(("", input))
+  }
+}
+
+/// `= 1`, the default value of a binding or the initializer of a variable
+fn parse_initializer(input: &str) -> IResult<&str, &str> {
+  preceded(
+    tuple((multispace0, char('='), multispace0)),
+    skip_until(",;", false),
+  )(input)
+}
+
+/// `a`, `{ b, c: d, ...e }` or `[f, , g = 1]`, as the names they declare
+fn parse_binding(input: &str) -> IResult<&str, Vec<&str>> {
+  alt((
+    map(parse_identifier, |x| vec![x]),
+    parse_object_pattern,
+    parse_array_pattern,
+  ))(input)
+}
+
+/// `{ a, b: c, 'd': e = 1, [f]: g, ...h }`
+fn parse_object_pattern(input: &str) -> IResult<&str, Vec<&str>> {
+  fn parse_property(input: &str) -> IResult<&str, Vec<&str>> {
+    let parse_key = alt((
+      parse_identifier,
+      recognize(parse_string),
+      recognize(delimited(char('['), skip_until("", false), char(']'))),
+    ));
+    let (input, names) = alt((
+      preceded(tuple((tag("..."), multispace0)), parse_binding),
+      preceded(
+        tuple((parse_key, multispace0, char(':'), multispace0)),
+        parse_binding,
+      ),
+      map(parse_identifier, |x| vec![x]),
+    ))(input)?;
+    let (input, _) = opt(parse_initializer)(input)?;
+    Ok((input, names))
+  }
+
+  let (input, _) = char('{')(input)?;
+  let (input, _) = multispace0(input)?;
+  let (input, properties) =
+    separated_list0(tuple((multispace0, char(','), multispace0)), parse_property)(input)?;
+  let (input, _) = multispace0(input)?;
+  let (input, _) = opt(char(','))(input)?;
+  let (input, _) = multispace0(input)?;
+  let (input, _) = char('}')(input)?;
+  Ok((input, properties.concat()))
+}
+
+/// `[a, , b = 1, ...c]`
+fn parse_array_pattern(input: &str) -> IResult<&str, Vec<&str>> {
+  fn parse_element(input: &str) -> IResult<&str, Vec<&str>> {
+    let (input, _) = opt(tuple((tag("..."), multispace0)))(input)?;
+    let (input, names) = parse_binding(input)?;
+    let (input, _) = opt(parse_initializer)(input)?;
+    Ok((input, names))
+  }
+
+  let (input, _) = char('[')(input)?;
+  let (input, _) = multispace0(input)?;
+  // elements may be left out, `[, a]`
+  let (input, elements) = separated_list0(
+    tuple((multispace0, char(','), multispace0)),
+    opt(parse_element),
+  )(input)?;
+  let (input, _) = multispace0(input)?;
+  let (input, _) = char(']')(input)?;
+  Ok((input, elements.into_iter().flatten().flatten().collect()))
+}
+
+/// `const a = 1, { b } = c`, `let d: Map<string, number>`...
+fn parse_variable_declaration(input: &str) -> IResult<&str, Vec<&str>> {
+  fn parse_declarator(input: &str) -> IResult<&str, Vec<&str>> {
+    let (input, names) = parse_binding(input)?;
+    // `a!: string`
+    let (input, _) = opt(tuple((
+      opt(char('!')),
+      multispace0,
+      char(':'),
+      skip_until("=,;", true),
+    )))(input)?;
+    let (input, _) = opt(parse_initializer)(input)?;
+    Ok((input, names))
+  }
+
+  let (input, _) = alt((
+    parse_keyword("const"),
+    parse_keyword("let"),
+    parse_keyword("var"),
+  ))(input)?;
+  let (input, _) = multispace0(input)?;
+  let (input, declarators) = separated_list1(
+    tuple((multispace0, char(','), multispace0)),
+    parse_declarator,
+  )(input)?;
+  Ok((input, declarators.concat()))
+}
+
+/// `export const a`, `export async function b`, `export declare abstract class C`...
+fn parse_export_declaration(input: &str) -> IResult<&str, Vec<ModuleSymbol>> {
+  let (input, _) = multispace1(input)?;
+  let (input, _) = many0(terminated(
+    alt((
+      parse_keyword("declare"),
+      parse_keyword("async"),
+      parse_keyword("abstract"),
+    )),
+    multispace1,
+  ))(input)?;
+  let (input, names) = alt((parse_named_declaration, parse_variable_declaration))(input)?;
+  Ok((
+    input,
+    names
+      .into_iter()
+      .map(|x| ModuleSymbol::Export(x.to_string()))
+      .collect(),
+  ))
+}
+
+/// `function a`, `class B`, `const enum C`...
+fn parse_named_declaration(input: &str) -> IResult<&str, Vec<&str>> {
+  let (input, _) = alt((
+    recognize(tuple((
+      parse_keyword("const"),
+      multispace1,
+      parse_keyword("enum"),
+    ))),
+    parse_keyword("function"),
+    parse_keyword("class"),
+    parse_keyword("interface"),
+    parse_keyword("type"),
+    parse_keyword("enum"),
+    parse_keyword("namespace"),
+  ))(input)?;
+  let (input, _) = multispace0(input)?;
+  // generator functions
+  let (input, _) = opt(terminated(char('*'), multispace0))(input)?;
+  let (input, name) = parse_identifier(input)?;
+  Ok((input, vec![name]))
+}
+
+fn parse_import_statement(input: &str) -> IResult<&str, Vec<ModuleSymbol>> {
+  let (input, _) = parse_keyword("import")(input)?;
+  let (input, (specifier, names)) = alt((
+    parse_import_clause,
+    // side effects only
+    map(preceded(multispace0, parse_string), |x| {
+      (x, Some(Vec::new()))
+    }),
+    // `import('./x')`
+    map(
+      preceded(tuple((multispace0, char('('), multispace0)), parse_string),
+      |x| (x, None),
+    ),
+  ))(input)?;
+  Ok((
+    input,
+    vec![ModuleSymbol::Import {
+      import: ImportDep::ESM(specifier),
+      names,
+    }],
+  ))
+}
+
+/// `import a, { b, c as d } from './x'`, `import * as ns from './x'`...
+fn parse_import_clause(input: &str) -> IResult<&str, (String, Option<Vec<String>>)> {
+  fn parse_bindings(input: &str) -> IResult<&str, Option<Vec<String>>> {
+    alt((
+      map(parse_namespace, |_| None),
+      map(parse_named_list, |items| {
+        Some(items.into_iter().map(|(x, _)| x.to_string()).collect())
+      }),
+    ))(input)
+  }
+
+  fn parse_default(input: &str) -> IResult<&str, Option<Vec<String>>> {
+    let (input, _) = parse_identifier(input)?;
+    let (input, bindings) = opt(preceded(
+      tuple((multispace0, char(','), multispace0)),
+      parse_bindings,
+    ))(input)?;
+    Ok((
+      input,
+      match bindings {
+        Some(None) => None,
+        Some(Some(names)) => Some([vec!["default".to_string()], names].concat()),
+        None => Some(vec!["default".to_string()]),
+      },
+    ))
+  }
+
+  let (input, _) = multispace0(input)?;
+  let (input, _) = opt(terminated(
+    parse_keyword("type"),
+    tuple((multispace1, peek(not(parse_keyword("from"))))),
+  ))(input)?;
+  let (input, names) = alt((parse_bindings, parse_default))(input)?;
+  let (input, specifier) = parse_from(input)?;
+  Ok((input, (specifier, names)))
+}
+
+/// `require('./x')`
+fn parse_require_statement(input: &str) -> IResult<&str, Vec<ModuleSymbol>> {
+  let (input, _) = parse_keyword("require")(input)?;
+  let (input, _) = tuple((multispace0, char('('), multispace0))(input)?;
+  let (input, specifier) = parse_string(input)?;
+  Ok((
+    input,
+    vec![ModuleSymbol::Import {
+      import: ImportDep::REQUIRE(specifier),
+      names: None,
+    }],
+  ))
+}
+
+/// Returns the exports of a JS module and the names it imports, along with the span of the
+/// statement each one was found in.
+pub fn parse_symbols(input: &str) -> Vec<(ModuleSymbol, Span)> {
+  // Words are skipped whole so that keywords only match at their start, not in `reimport`
+  let parse_next = map(
+    many_till(
+      alt((parse_identifier, take(1usize))),
+      map(
+        alt((
+          parse_export_statement,
+          parse_import_statement,
+          parse_require_statement,
+        )),
+        Some,
+      ),
+    ),
+    |(skipped, symbols)| (skipped.concat().chars().collect(), symbols),
+  );
+
+  parse_with_spans(input, parse_next)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{parse_symbols, ModuleSymbol};
+  use crate::parser::ImportDep;
+
+  fn symbols(input: &str) -> Vec<ModuleSymbol> {
+    parse_symbols(input).into_iter().map(|(x, _)| x).collect()
+  }
+
+  fn export(name: &str) -> ModuleSymbol {
+    ModuleSymbol::Export(name.to_string())
+  }
+
+  fn import(specifier: &str, names: Option<&[&str]>) -> ModuleSymbol {
+    ModuleSymbol::Import {
+      import: ImportDep::ESM(specifier.to_string()),
+      names: names.map(|x| x.iter().map(|x| x.to_string()).collect()),
+    }
+  }
+
+  #[test]
+  fn test_parse_exports() {
+    assert_eq!(
+      symbols(
+        "export const a = 1;\nexport async function b() {}\nexport function* c() {}\n\
+         export default class {}\nexport declare abstract class D {}\n\
+         export const enum E {}\nexport type F = string;\nexport { g, h as i };"
+      ),
+      vec![
+        export("a"),
+        export("b"),
+        export("c"),
+        export("default"),
+        export("D"),
+        export("E"),
+        export("F"),
+        export("g"),
+        export("i"),
+      ]
+    );
+    // not an export statement
+    assert_eq!(symbols("module.exports = { a };\nexports.b = 1;"), vec![]);
+  }
+
+  #[test]
+  fn test_parse_variable_exports() {
+    assert_eq!(
+      symbols(
+        "export const a = 1, b = { c: [1, 2] }, d = f(1, 'e, g');\n\
+         export let h: Map<string, number> = new Map(), i;\nexport var j = 1\nconst k = 2, l = 3;"
+      ),
+      vec![
+        export("a"),
+        export("b"),
+        export("d"),
+        export("h"),
+        export("i"),
+        export("j")
+      ]
+    );
+    assert_eq!(
+      symbols("export const a = 1\n  , b = [\n    2,\n  ]\n\nexport const c = () => {}"),
+      vec![export("a"), export("b"), export("c")]
+    );
+  }
+
+  #[test]
+  fn test_parse_destructured_exports() {
+    assert_eq!(
+      symbols(
+        "export const { a, b: c, 'd': e = 1, [f]: g, ...h } = x;\n\
+         export const [i, , j = [1, 2], ...k] = y;\nexport let { l: { m }, n: [o] } = z, p = 1;"
+      ),
+      ["a", "c", "e", "g", "h", "i", "j", "k", "m", "o", "p"]
+        .into_iter()
+        .map(export)
+        .collect::<Vec<ModuleSymbol>>()
+    );
+  }
+
+  #[test]
+  fn test_parse_keywords_inside_words() {
+    assert_eq!(
+      symbols(
+        "const a = xrequire('./a');\nreimport('./b');\nconst reexport = 1;\n\
+         my_import { c } from './c';"
+      ),
+      vec![]
+    );
+    assert_eq!(
+      symbols("const a = (require('./a'));\n[import('./b')];"),
+      vec![
+        ModuleSymbol::Import {
+          import: ImportDep::REQUIRE("./a".to_string()),
+          names: None,
+        },
+        import("./b", None),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_re_exports() {
+    assert_eq!(
+      symbols("export * from './a';\nexport * as b from \"./b\";\nexport { c as d } from './c';"),
+      vec![
+        ModuleSymbol::ExportAll(ImportDep::ESM("./a".to_string())),
+        export("b"),
+        import("./b", None),
+        ModuleSymbol::ReExport {
+          import: ImportDep::ESM("./c".to_string()),
+          imported: "c".to_string(),
+          exported: "d".to_string(),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_imports() {
+    assert_eq!(
+      symbols(
+        "import a from './a';\nimport b, { c, d as e } from './b';\nimport * as f from './f';\n\
+         import type { G } from './g';\nimport {\n  type H,\n  i,\n} from './h';\n\
+         import './side-effect';\nconst j = await import('./j');\nconst k = require('./k');"
+      ),
+      vec![
+        import("./a", Some(&["default"])),
+        import("./b", Some(&["default", "c", "d"])),
+        import("./f", None),
+        import("./g", Some(&["G"])),
+        import("./h", Some(&["H", "i"])),
+        import("./side-effect", Some(&[])),
+        import("./j", None),
+        ModuleSymbol::Import {
+          import: ImportDep::REQUIRE("./k".to_string()),
+          names: None,
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_symbols_spans() {
+    let res = parse_symbols("import a from './a';\n\nexport { b, c };");
+    assert_eq!(res.len(), 3);
+    assert_eq!((res[0].1.line, res[0].1.column), (1, 1));
+    assert_eq!((res[1].1.line, res[1].1.column), (3, 1));
+    assert_eq!(res[1].1, res[2].1);
+  }
+}
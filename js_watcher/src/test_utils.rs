@@ -0,0 +1,61 @@
+use std::path::{Path, PathBuf};
+
+/// Copies the fixture `name` to a temporary directory of its own for `test`, so that tests
+/// editing files don't race with the ones reading the same fixture. Returns the real path of
+/// the copy.
+pub fn copy_fixture(name: &str, test: &str) -> PathBuf {
+  let fixture = std::env::current_dir()
+    .unwrap()
+    .join("tests")
+    .join("fixtures")
+    .join(name);
+  let dest = std::env::temp_dir().join("js_watcher_tests").join(test);
+  if dest.exists() {
+    std::fs::remove_dir_all(&dest).unwrap();
+  }
+  copy_dir(&fixture, &dest);
+  dest.canonicalize().unwrap()
+}
+
+/// Copies the fixture `name` like `copy_fixture`, then adds the symlinks `links`, as
+/// `(path, target)` relative to the copy. They aren't committed since not every checkout can
+/// hold them.
+#[cfg(unix)]
+pub fn copy_fixture_with_links(name: &str, test: &str, links: &[(&str, &str)]) -> PathBuf {
+  let dest = copy_fixture(name, test);
+  for (path, target) in links {
+    let path = dest.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::os::unix::fs::symlink(target, path).unwrap();
+  }
+  dest
+}
+
+/// Copies the fixture `name` like `copy_fixture`, and returns a symlink to the copy along with
+/// its real path.
+#[cfg(unix)]
+pub fn link_fixture(name: &str, test: &str) -> (PathBuf, PathBuf) {
+  let dest = copy_fixture(name, test);
+  let link = dest.with_file_name(format!("{}_link", test));
+  if link.exists() {
+    std::fs::remove_file(&link).unwrap();
+  }
+  std::os::unix::fs::symlink(&dest, &link).unwrap();
+  (link, dest)
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+  std::fs::create_dir_all(to).unwrap();
+  for entry in std::fs::read_dir(from).unwrap().flatten() {
+    let path = entry.path();
+    // the cache of a watcher ran on the fixture itself
+    if path.ends_with("mw-cache") {
+      continue;
+    }
+    if path.is_dir() {
+      copy_dir(&path, &to.join(entry.file_name()));
+    } else {
+      std::fs::copy(&path, to.join(entry.file_name())).unwrap();
+    }
+  }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::path_clean::*;
+use crate::resolve_cache::ResolveCache;
+
+/// The subset of a `tsconfig.json` (or `jsconfig.json`) that matters for resolving imports.
+/// Every path is absolute, already resolved against the config file that declared it.
+#[derive(Debug, Clone, Default)]
+pub struct TsConfig {
+  pub base_url: Option<PathBuf>,
+  /// `compilerOptions.paths` entries, with their targets
+  pub paths: Vec<(String, Vec<String>)>,
+  /// Directory `paths` targets are relative to: `baseUrl` if set, otherwise the
+  /// directory of the config file declaring `paths`
+  pub paths_base: Option<PathBuf>,
+}
+
+impl TsConfig {
+  /// Returns the candidate paths for `specifier` according to `compilerOptions.paths`, in
+  /// the order they should be tried.
+  /// Like `tsc`, an exact key wins over patterns, and the pattern with the longest prefix
+  /// wins among patterns.
+  pub fn path_candidates(&self, specifier: &str) -> Vec<PathBuf> {
+    let base = match &self.paths_base {
+      Some(base) => base,
+      None => return Vec::new(),
+    };
+
+    let mut best: Option<(&Vec<String>, &str, usize)> = None;
+    for (pattern, targets) in &self.paths {
+      match pattern.split_once('*') {
+        None => {
+          if pattern == specifier {
+            best = Some((targets, "", usize::MAX));
+            break;
+          }
+        }
+        Some((prefix, suffix)) => {
+          let matches = specifier.len() >= prefix.len() + suffix.len()
+            && specifier.starts_with(prefix)
+            && specifier.ends_with(suffix);
+          if matches && best.map_or(true, |(_, _, len)| prefix.len() > len) {
+            let captured = &specifier[prefix.len()..specifier.len() - suffix.len()];
+            best = Some((targets, captured, prefix.len()));
+          }
+        }
+      }
+    }
+
+    match best {
+      Some((targets, captured, _)) => targets
+        .iter()
+        .map(|target| base.join(target.replacen('*', captured, 1)).clean())
+        .collect(),
+      None => Vec::new(),
+    }
+  }
+
+  /// Returns the `baseUrl` candidate for a bare `specifier`, if any.
+  pub fn base_url_candidate(&self, specifier: &str) -> Option<PathBuf> {
+    self
+      .base_url
+      .as_ref()
+      .map(|base_url| base_url.join(specifier).clean())
+  }
+
+  fn merge(&mut self, other: TsConfig) {
+    if other.base_url.is_some() {
+      self.base_url = other.base_url;
+    }
+    if other.paths_base.is_some() {
+      self.paths = other.paths;
+      self.paths_base = other.paths_base;
+    }
+    // `paths` are always relative to `baseUrl` when there's one
+    if self.base_url.is_some() && self.paths_base.is_some() {
+      self.paths_base = self.base_url.clone();
+    }
+  }
+}
+
+/// Finds the closest `tsconfig.json` or `jsconfig.json` from `dir`, walking up the parents.
+pub fn find_tsconfig(dir: &Path, cache: &ResolveCache) -> Option<PathBuf> {
+  cache.tsconfig_paths.get_or_insert_with(dir, || {
+    for name in ["tsconfig.json", "jsconfig.json"] {
+      let candidate = dir.join(name);
+      if candidate.is_file() {
+        return Some(candidate);
+      }
+    }
+    find_tsconfig(dir.parent()?, cache)
+  })
+}
+
+/// Loads the config at `path`, following its `extends` chain.
+pub fn load_tsconfig(path: &Path, cache: &ResolveCache) -> Option<Arc<TsConfig>> {
+  cache.tsconfigs.get_or_insert_with(path, || {
+    let mut visited = HashSet::new();
+    load_tsconfig_inner(path, &mut visited).map(Arc::new)
+  })
+}
+
+fn load_tsconfig_inner(path: &Path, visited: &mut HashSet<PathBuf>) -> Option<TsConfig> {
+  // `extends` cycles are an error for tsc, we simply stop following them
+  if !visited.insert(path.to_path_buf()) {
+    return None;
+  }
+  let content = std::fs::read_to_string(path).ok()?;
+  let json: serde_json::Value = serde_json::from_str(&strip_json_comments(&content)).ok()?;
+  let config_dir = path.parent()?;
+
+  // Parents are applied first so the current file can override them
+  let mut config = TsConfig::default();
+  let extends: Vec<&str> = match &json["extends"] {
+    serde_json::Value::String(value) => vec![value.as_str()],
+    serde_json::Value::Array(values) => values.iter().filter_map(|x| x.as_str()).collect(),
+    _ => Vec::new(),
+  };
+  for parent in extends {
+    if let Some(parent_path) = resolve_extends(parent, config_dir) {
+      if let Some(parent_config) = load_tsconfig_inner(&parent_path, visited) {
+        config.merge(parent_config);
+      }
+    }
+  }
+
+  let compiler_options = &json["compilerOptions"];
+  let base_url = compiler_options["baseUrl"]
+    .as_str()
+    .map(|base_url| config_dir.join(base_url).clean());
+  let paths = compiler_options["paths"].as_object().map(|paths| {
+    paths
+      .iter()
+      .map(|(pattern, targets)| {
+        let targets = targets
+          .as_array()
+          .map(|x| {
+            x.iter()
+              .filter_map(|t| t.as_str().map(String::from))
+              .collect()
+          })
+          .unwrap_or_default();
+        (pattern.to_string(), targets)
+      })
+      .collect()
+  });
+  config.merge(TsConfig {
+    paths_base: paths.as_ref().map(|_| config_dir.to_path_buf()),
+    base_url,
+    paths: paths.unwrap_or_default(),
+  });
+  Some(config)
+}
+
+/// Resolves the value of `extends`, either a relative path or a module inside `node_modules`.
+fn resolve_extends(value: &str, config_dir: &Path) -> Option<PathBuf> {
+  let with_json = |path: PathBuf| {
+    if path.is_file() {
+      Some(path)
+    } else {
+      let mut file_name = path.file_name()?.to_os_string();
+      file_name.push(".json");
+      Some(path.with_file_name(file_name)).filter(|x| x.is_file())
+    }
+  };
+  if value.starts_with("./") || value.starts_with("../") || value.starts_with('/') {
+    return with_json(config_dir.join(value).clean());
+  }
+  for dir in config_dir.ancestors() {
+    let module_path = dir.join("node_modules").join(value);
+    if module_path.is_dir() {
+      return Some(module_path.join("tsconfig.json")).filter(|x| x.is_file());
+    }
+    if let Some(found) = with_json(module_path) {
+      return Some(found);
+    }
+  }
+  None
+}
+
+/// Removes comments and trailing commas from a JSON document, as allowed in tsconfig files.
+pub fn strip_json_comments(input: &str) -> String {
+  let mut output = String::with_capacity(input.len());
+  let mut chars = input.chars().peekable();
+  let mut in_string = false;
+
+  while let Some(c) = chars.next() {
+    if in_string {
+      output.push(c);
+      if c == '\\' {
+        if let Some(escaped) = chars.next() {
+          output.push(escaped);
+        }
+      } else if c == '"' {
+        in_string = false;
+      }
+      continue;
+    }
+    match (c, chars.peek()) {
+      ('"', _) => {
+        in_string = true;
+        output.push(c);
+      }
+      ('/', Some('/')) => {
+        for c in chars.by_ref() {
+          if c == '\n' {
+            output.push(c);
+            break;
+          }
+        }
+      }
+      ('/', Some('*')) => {
+        chars.next();
+        let mut prev = ' ';
+        for c in chars.by_ref() {
+          if prev == '*' && c == '/' {
+            break;
+          }
+          prev = c;
+        }
+      }
+      _ => output.push(c),
+    }
+  }
+
+  remove_trailing_commas(&output)
+}
+
+fn remove_trailing_commas(input: &str) -> String {
+  let chars: Vec<char> = input.chars().collect();
+  let mut output = String::with_capacity(input.len());
+  let mut in_string = false;
+  let mut i = 0;
+
+  while i < chars.len() {
+    let c = chars[i];
+    i += 1;
+    if in_string {
+      output.push(c);
+      if c == '\\' && i < chars.len() {
+        output.push(chars[i]);
+        i += 1;
+      } else if c == '"' {
+        in_string = false;
+      }
+      continue;
+    }
+    if c == '"' {
+      in_string = true;
+    } else if c == ',' {
+      let next = chars[i..].iter().find(|x| !x.is_whitespace());
+      if matches!(next, Some('}') | Some(']')) {
+        continue;
+      }
+    }
+    output.push(c);
+  }
+
+  output
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{find_tsconfig, load_tsconfig, strip_json_comments};
+  use crate::resolve_cache::ResolveCache;
+  use lazy_static::lazy_static;
+  use std::path::PathBuf;
+
+  lazy_static! {
+    static ref CWD: PathBuf = std::env::current_dir().unwrap();
+    static ref TSCONFIG_PATHS_PATH: PathBuf = CWD.join("tests/fixtures/tsconfig_paths");
+  }
+
+  #[test]
+  fn test_strip_json_comments() {
+    let res = strip_json_comments(
+      r#"{
+        // comment
+        "a": "http://foo", /* block */
+        "b": [1, 2,],
+      }"#,
+    );
+    let json: serde_json::Value = serde_json::from_str(&res).unwrap();
+    assert_eq!(json["a"], "http://foo");
+    assert_eq!(json["b"].as_array().unwrap().len(), 2);
+  }
+
+  #[test]
+  fn test_load_tsconfig_with_extends() {
+    let cache = ResolveCache::default();
+    let path = find_tsconfig(&TSCONFIG_PATHS_PATH.join("src/components"), &cache).unwrap();
+    assert_eq!(path, TSCONFIG_PATHS_PATH.join("tsconfig.json"));
+
+    let config = load_tsconfig(&path, &cache).unwrap();
+    assert_eq!(config.base_url, Some(TSCONFIG_PATHS_PATH.to_path_buf()));
+    assert_eq!(
+      config.path_candidates("@app/components/Button"),
+      vec![
+        TSCONFIG_PATHS_PATH.join("src/missing/components/Button"),
+        TSCONFIG_PATHS_PATH.join("src/components/Button"),
+      ]
+    );
+    assert_eq!(
+      config.path_candidates("@utils"),
+      vec![TSCONFIG_PATHS_PATH.join("src/utils/index.ts")]
+    );
+    assert!(config.path_candidates("lodash").is_empty());
+  }
+}
@@ -0,0 +1,194 @@
+use dashmap::DashMap;
+use std::collections::{HashMap, HashSet};
+
+use crate::entry::JS_EXTS;
+use crate::file_item::FileItem;
+use crate::parser::Span;
+use crate::symbols::ModuleSymbol;
+
+/// An export that no file of the store imports
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnusedExport {
+  pub file: String,
+  pub name: String,
+  /// The statement exporting `name`
+  pub span: Span,
+}
+
+/// The exports of a file of the store, with where its re-exports come from
+#[derive(Default)]
+struct ModuleExports {
+  names: Vec<(String, Span)>,
+  /// Exported name to the file and name it re-exports, `export { a as b } from './x'`
+  re_exports: HashMap<String, (String, String)>,
+  /// Files of `export * from './x'`
+  export_all: Vec<String>,
+}
+
+/// Which exports of the store are used, following re-exports
+struct Usage<'a> {
+  modules: &'a HashMap<String, ModuleExports>,
+  used: HashSet<(String, String)>,
+  /// Files whose every export may be used
+  fully_used: HashSet<String>,
+}
+
+impl<'a> Usage<'a> {
+  fn use_name(&mut self, file: &str, name: &str) {
+    let module = match self.modules.get(file) {
+      Some(module) => module,
+      None => return,
+    };
+    if self.fully_used.contains(file) || !self.used.insert((file.to_string(), name.to_string())) {
+      return;
+    }
+    if let Some((target, imported)) = module.re_exports.get(name) {
+      self.use_name(target, imported);
+    } else if name != "default" && !module.names.iter().any(|(x, _)| x == name) {
+      // `export *` never re-exports `default`
+      for target in &module.export_all {
+        self.use_name(target, name);
+      }
+    }
+  }
+
+  fn use_all(&mut self, file: &str) {
+    let module = match self.modules.get(file) {
+      Some(module) => module,
+      None => return,
+    };
+    if !self.fully_used.insert(file.to_string()) {
+      return;
+    }
+    for (target, imported) in module.re_exports.values() {
+      self.use_name(target, imported);
+    }
+    for target in &module.export_all {
+      self.use_all(target);
+    }
+  }
+
+  fn is_used(&self, file: &str, name: &str) -> bool {
+    self.fully_used.contains(file) || self.used.contains(&(file.to_string(), name.to_string()))
+  }
+}
+
+/// Returns the exports of the JS files of the store that no other file imports, sorted by file
+/// and position. Packages and `entries` are skipped since their exports are used from outside.
+/// Namespace imports, dynamic imports and `require` count as using every export of a file,
+/// and `export *` passes the names used through. Symbols are the ones parsed along with the
+/// imports of each file, see `FileItem.symbols`.
+pub fn find_unused_exports(
+  store: &DashMap<String, FileItem>,
+  entries: &[String],
+) -> Vec<UnusedExport> {
+  let mut modules: HashMap<String, ModuleExports> = HashMap::new();
+  // names imported from each file, `None` for all of them
+  let mut imports: Vec<(String, Option<Vec<String>>)> = Vec::new();
+  for item in store.iter() {
+    let ext = item.path.extension().and_then(|x| x.to_str()).unwrap_or("");
+    if !(JS_EXTS.contains(&ext) || ext == "mdx")
+      || item
+        .path
+        .components()
+        .any(|x| x.as_os_str() == "node_modules")
+    {
+      continue;
+    }
+    let mut module = ModuleExports::default();
+    for (symbol, span) in &item.symbols {
+      match symbol {
+        ModuleSymbol::Export(name) => module.names.push((name.clone(), *span)),
+        ModuleSymbol::ReExport {
+          import,
+          imported,
+          exported,
+        } => {
+          module
+            .re_exports
+            .insert(exported.clone(), (import.clone(), imported.clone()));
+          module.names.push((exported.clone(), *span));
+        }
+        ModuleSymbol::ExportAll(import) => module.export_all.push(import.clone()),
+        ModuleSymbol::Import { import, names } => imports.push((import.clone(), names.clone())),
+      }
+    }
+    modules.insert(item.key().to_string(), module);
+  }
+
+  let mut usage = Usage {
+    modules: &modules,
+    used: HashSet::new(),
+    fully_used: HashSet::new(),
+  };
+  for (target, names) in &imports {
+    match names {
+      Some(names) => names.iter().for_each(|name| usage.use_name(target, name)),
+      None => usage.use_all(target),
+    }
+  }
+
+  let mut res: Vec<UnusedExport> = modules
+    .iter()
+    .filter(|(file, _)| !entries.contains(file))
+    .flat_map(|(file, module)| {
+      module
+        .names
+        .iter()
+        .filter(|(name, _)| !usage.is_used(file, name))
+        .map(|(name, span)| UnusedExport {
+          file: file.clone(),
+          name: name.clone(),
+          span: *span,
+        })
+    })
+    .collect();
+  res.sort_by(|a, b| (&a.file, a.span.start).cmp(&(&b.file, b.span.start)));
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::find_unused_exports;
+  use crate::entry::make_file_item;
+  use dashmap::DashMap;
+  use lazy_static::lazy_static;
+  use std::path::PathBuf;
+
+  lazy_static! {
+    static ref CWD: PathBuf = std::env::current_dir().unwrap();
+    static ref UNUSED_EXPORTS_PATH: PathBuf =
+      CWD.join("tests").join("fixtures").join("unused_exports");
+  }
+
+  #[test]
+  fn test_find_unused_exports() {
+    let store = DashMap::new();
+    let key = |file: &str| UNUSED_EXPORTS_PATH.join(file).to_str().unwrap().to_string();
+    make_file_item(
+      &UNUSED_EXPORTS_PATH.join("main.js"),
+      &UNUSED_EXPORTS_PATH,
+      &store,
+      &None,
+    );
+
+    let unused: Vec<(String, String, u32)> = find_unused_exports(&store, &[key("main.js")])
+      .into_iter()
+      .map(|x| (x.file, x.name, x.span.line))
+      .collect();
+    let expected = |file: &str, name: &str, line: u32| (key(file), name.to_string(), line);
+    // the exports of `all.js` and `lazy.js` are all used through a namespace and a dynamic
+    // import, `viaBarrel` through `export *`
+    assert_eq!(
+      unused,
+      vec![
+        expected("barrel.js", "y", 2),
+        expected("inner.js", "x", 2),
+        expected("inner.js", "z", 3),
+        expected("lib.js", "unused", 2),
+        expected("lib.js", "default", 4),
+      ]
+    );
+  }
+}
@@ -1,9 +1,23 @@
 use crate::entry::{
-  make_entries, make_file_item, make_missing_entries, MakeEntriesOptions, SupportedPaths,
+  canonicalize_path, expand_globs, explain_resolve, make_entries, make_file_item,
+  make_missing_entries, ExternalMode, MakeEntriesOptions, ResolveAlias, ResolveTrace,
+  SupportedPaths,
 };
-use crate::file_item::FileItem;
+use crate::export::{build_graph, write_graph, DependencyGraph, GraphFormat};
+use crate::file_item::{Diagnostic, DiagnosticKind, FileItem, ImportKind};
+use crate::graph::{
+  collect_importers, collect_reachable, detach_file, find_cycles, find_import_chain,
+  find_import_chains, refresh_deps, restore_importers, topological_order,
+};
+use crate::parser::Span;
+use crate::path_clean::PathClean;
+use crate::rules::{check_rules, compile_rules, BoundaryRule, CompiledRule, RuleViolation};
+use crate::snapshot::{diff_snapshots, load_snapshot, save_snapshot, GraphDiff, GraphSnapshot};
+use crate::unused::{find_unused_exports, UnusedExport};
 use dashmap::DashMap;
+use glob::Pattern;
 use napi::bindgen_prelude::*;
+#[cfg(not(feature = "without-napi"))]
 use napi::threadsafe_function::{
   ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode,
 };
@@ -20,7 +34,7 @@ use std::{
 };
 
 #[napi(object)]
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct SetupOptions {
   pub project: String,
   pub project_root: String,
@@ -29,6 +43,89 @@ pub struct SetupOptions {
   pub cache_dir: Option<String>,
   pub supported_paths: Option<SupportedPaths>,
   pub debug: Option<bool>,
+  /// Aliases tried before any other resolution. `~` points to `project_root` by default
+  pub aliases: Option<Vec<ResolveAlias>>,
+  /// Conditions matched against package `exports`, on top of `import` or `require`.
+  /// Defaults to `["node"]`
+  pub conditions: Option<Vec<String>>,
+  /// package.json fields tried in order for the entry point of packages without `exports`.
+  /// Defaults to `["main"]`. With `browser`, the object form of that field is applied too
+  pub main_fields: Option<Vec<String>>,
+  /// Extensions tried in order for imports without one, and for `index` files.
+  /// Defaults to TypeScript extensions, then JavaScript and style ones
+  pub extensions: Option<Vec<String>>,
+  /// How packages of `node_modules` are watched. Defaults to `Walk`, every file is followed
+  pub external_mode: Option<ExternalMode>,
+  /// Bare imports are resolved to the packages of the workspace of the project, declared by
+  /// `pnpm-workspace.yaml` or the `workspaces` of package.json, even when they aren't
+  /// installed. The workspace root has to be inside `project_root`. Defaults to `true`
+  pub workspaces: Option<bool>,
+  /// Reports a `CycleAdded` change when a circular import appears. Defaults to `false`
+  pub report_cycles: Option<bool>,
+  /// Rules restricting what files may import, see `check_rules`. Each new violation is reported
+  /// as a `RuleViolated` change. `setup` fails on invalid globs
+  pub rules: Option<Vec<BoundaryRule>>,
+}
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct GraphOptions {
+  /// Only the graph of this entry, relative to the project root. Defaults to every entry
+  pub entry: Option<String>,
+  /// How many imports away from the entries files are kept. Defaults to no limit
+  pub max_depth: Option<u32>,
+  /// Each package of `node_modules` is a single node. Defaults to `false`
+  pub collapse_packages: Option<bool>,
+}
+
+/// Test files, treated as roots with `tests_as_roots`
+const DEFAULT_TEST_PATTERNS: [&str; 3] = ["**/*.test.*", "**/*.spec.*", "**/__tests__/**"];
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct UnreachableOptions {
+  /// Globs of files that are never reported, relative to the project root
+  pub ignore: Option<Vec<String>>,
+  /// Test files are roots too, along with the entries. Defaults to `false`
+  pub tests_as_roots: Option<bool>,
+  /// Globs of test files, relative to the project root. Defaults to `*.test.*`, `*.spec.*`
+  /// and files inside `__tests__`
+  pub test_patterns: Option<Vec<String>>,
+}
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct GetEntriesOptions {
+  /// Entries come leaves first, after every entry they depend on, see `topological_order`.
+  /// Defaults to `false`, in no particular order
+  pub sorted: Option<bool>,
+}
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct GetImportersOptions {
+  /// Also returns the files depending on them, and so on. Defaults to `false`
+  pub transitive: Option<bool>,
+}
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct ExplainDependencyOptions {
+  /// How many chains of imports are returned at most, the shortest first. Defaults to `1`
+  pub max_paths: Option<u32>,
+}
+
+/// An import along a chain leading from an entry to one of its deps
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportStep {
+  pub importer: String,
+  /// The file imported by `importer`
+  pub file: String,
+  /// The import as written in the source
+  pub specifier: Option<String>,
+  /// The import statement inside `importer`
+  pub span: Option<Span>,
 }
 
 #[napi(object)]
@@ -45,10 +142,16 @@ pub struct EntryChange {
   pub entry: String,
   pub cause: Option<EntryChangeCause>,
   pub tree: Option<Vec<String>>,
+  /// For `ImportUnresolved` and `ImportResolved`, the diagnostic that appeared or disappeared
+  pub diagnostic: Option<Diagnostic>,
+  /// For `CycleAdded`, the files of the cycle, sorted
+  pub cycle: Option<Vec<String>>,
+  /// For `RuleViolated`, the import breaking a rule
+  pub violation: Option<RuleViolation>,
 }
 
 #[cfg_attr(not(feature = "without-napi"), napi(string_enum), derive(PartialEq, Debug))]
-#[cfg_attr(feature = "without-napi", derive(PartialEq, Debug, Clone))]
+#[cfg_attr(feature = "without-napi", derive(PartialEq, Debug, Clone, Copy))]
 pub enum EntryChangeType {
   Added,
   DepAdded,
@@ -56,6 +159,14 @@ pub enum EntryChangeType {
   DepModified,
   Deleted,
   DepDeleted,
+  /// An import of the entry or of one of its deps stopped resolving
+  ImportUnresolved,
+  /// An import of the entry or of one of its deps that didn't resolve now does
+  ImportResolved,
+  /// The entry or one of its deps became part of a circular import
+  CycleAdded,
+  /// An import of the entry or of one of its deps started breaking a rule
+  RuleViolated,
 }
 
 #[cfg_attr(not(feature = "without-napi"), napi(string_enum), derive(PartialEq, Debug))]
@@ -74,7 +185,10 @@ struct WatcherInner {
   pub processed: bool,
   pub cache_dir: String,
   make_entries_opts: Option<MakeEntriesOptions>,
+  #[allow(dead_code)]
   debug: bool,
+  /// The `rules` of the setup options, see `compile_rules`
+  rules: Vec<CompiledRule>,
 }
 
 #[napi(js_name = "ModulesWatcher")]
@@ -97,13 +211,23 @@ impl WatcherInner {
       cache_dir: self.cache_dir.clone(),
       make_entries_opts: self.make_entries_opts.clone(),
       debug: self.debug,
+      rules: self.rules.clone(),
     }
   }
 
-  pub fn setup(opts: SetupOptions) -> Self {
-    let watcher_opts = opts.clone();
+  /// Fails on invalid rules, see `compile_rules`.
+  pub fn setup(opts: SetupOptions) -> Result<Self> {
+    let rules = compile_rules(opts.rules.as_deref().unwrap_or_default())
+      .map_err(|err| Error::from_reason(format!("Invalid rules: {}", err)))?;
+    let mut watcher_opts = opts.clone();
+    // files are keyed by their real path, the root has to match them, see `canonicalize_path`
+    if let Some(real_root) = canonicalize_path(PathBuf::from(&opts.project_root)) {
+      if let Some(real_root) = real_root.to_str() {
+        watcher_opts.project_root = real_root.to_string();
+      }
+    }
     let entries_vec = opts.entries.unwrap_or_default();
-    let project_root = opts.project_root;
+    let project_root = watcher_opts.project_root.clone();
     let cache_dir = opts.cache_dir.unwrap_or_else(|| {
       PathBuf::from(project_root.clone())
         .join("mw-cache")
@@ -119,6 +243,13 @@ impl WatcherInner {
 
     let make_entries_opts = Some(MakeEntriesOptions {
       supported_paths: opts.supported_paths,
+      aliases: opts.aliases,
+      conditions: opts.conditions,
+      main_fields: opts.main_fields,
+      extensions: opts.extensions,
+      external_mode: opts.external_mode,
+      workspaces: opts.workspaces,
+      ..Default::default()
     });
 
     let (store, entries) = make_entries(
@@ -127,7 +258,7 @@ impl WatcherInner {
       PathBuf::from(project_root),
       &make_entries_opts,
     );
-    WatcherInner {
+    Ok(WatcherInner {
       setup_options: watcher_opts,
       store,
       entries,
@@ -135,21 +266,44 @@ impl WatcherInner {
       cache_dir,
       make_entries_opts,
       debug,
-    }
+      rules,
+    })
   }
 
-  pub fn get_entries(&self) -> Vec<FileItem> {
-    self.entries.iter().map(|x| x.clone_item()).collect()
+  pub fn get_entries(&self, sorted: bool) -> Vec<FileItem> {
+    let mut entries: Vec<FileItem> = self.entries.iter().map(|x| x.clone_item()).collect();
+    if sorted {
+      let positions: HashMap<String, usize> = self
+        .topological_order(None)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, files)| files.into_iter().map(move |x| (x, i)))
+        .collect();
+      entries.sort_by_key(|x| {
+        let position = positions.get(x.path.to_str().unwrap());
+        (position.copied().unwrap_or(usize::MAX), x.path.clone())
+      });
+    }
+    entries
   }
 
-  pub fn get_entries_from_item(&self, item: &FileItem) -> Vec<&FileItem> {
-    let usage = item.get_usage(&self.store);
-    let usage = usage.iter();
-    self
-      .entries
-      .iter()
-      .filter(|x| usage.clone().any(|y| y == x.path.to_str().unwrap()))
-      .collect()
+  /// Returns the files of `entry`, relative to the project root, or of every entry, leaves
+  /// first, see `topological_order`.
+  pub fn topological_order(&self, entry: Option<&str>) -> Vec<Vec<String>> {
+    let roots: Vec<String> = match entry {
+      Some(entry) => {
+        let entry = PathBuf::from(&self.setup_options.project_root)
+          .join(entry)
+          .clean();
+        vec![entry.to_str().unwrap().to_string()]
+      }
+      None => self
+        .entries
+        .iter()
+        .map(|x| x.path.to_str().unwrap().to_string())
+        .collect(),
+    };
+    topological_order(&self.store, &roots)
   }
 
   fn update_store_with_missing_entries(&mut self) {
@@ -167,7 +321,7 @@ impl WatcherInner {
       &self.store,
       &self.make_entries_opts,
     );
-    self.entries.extend(new_entries.into_iter());
+    self.entries.extend(new_entries);
   }
 
   fn update_entries_from_store(&mut self) {
@@ -176,6 +330,7 @@ impl WatcherInner {
       .iter()
       .filter_map(|x| {
         let key = x.path.to_str().unwrap();
+        refresh_deps(&self.store, key);
         if let Some(item) = self.store.get(key) {
           let mut res_item = item.clone_item();
           res_item.deps.retain(|x| self.store.contains_key(x));
@@ -186,27 +341,47 @@ impl WatcherInner {
       .collect();
   }
 
-  pub fn remove_dep(&self, dep: &str) {
-    self.store.remove(dep);
-    self.store.par_iter_mut().for_each(|mut x| {
-      x.deps.retain(|d| d != dep);
-    });
+  /// Returns the entries depending on `item`, `item` included, see `FileItem::get_usage`.
+  #[allow(dead_code)]
+  pub fn get_entries_from_item(&self, item: &FileItem) -> Vec<&FileItem> {
+    let usage: HashSet<String> = item.get_usage(&self.store).into_iter().collect();
+    self
+      .entries
+      .iter()
+      .filter(|x| usage.contains(x.path.to_str().unwrap()))
+      .collect()
   }
 
-  fn make_file_deps(&self, file_path: &str) -> Vec<String> {
-    if self.store.contains_key(file_path) {
-      self.store.remove(file_path).unwrap();
+  /// Removes `dep` from the store and from the imports of the files importing it. The importers
+  /// of its imports and the deps of the entries are kept up to date.
+  #[allow(dead_code)]
+  pub fn remove_dep(&mut self, dep: &str) {
+    for importer in detach_file(&self.store, dep) {
+      if let Some(mut item) = self.store.get_mut(&importer) {
+        item.imports.remove(dep);
+        item.import_sites.remove(dep);
+      }
     }
+    self.update_entries_from_store();
+  }
+
+  fn make_file_deps(&self, file_path: &str) -> Vec<String> {
+    let importers = detach_file(&self.store, file_path);
     let project_root = &self.setup_options.project_root;
     let path = PathBuf::from(file_path);
-    let res = make_file_item(
+    let deps = make_file_item(
       &path,
       std::path::Path::new(project_root),
       &self.store,
       &self.make_entries_opts,
     )
-    .unwrap();
-    res.deps.iter().map(String::from).collect()
+    .unwrap()
+    .deps
+    .iter()
+    .map(String::from)
+    .collect();
+    restore_importers(&self.store, file_path, importers);
+    deps
   }
 
   fn get_checksums_cache(&self) -> HashMap<String, i64> {
@@ -251,100 +426,122 @@ impl WatcherInner {
   }
 
   pub fn make_changes(&mut self) -> Vec<EntryChange> {
+    // config files and packages may have changed since the last call
+    if let Some(opts) = &self.make_entries_opts {
+      opts.cache.clear();
+    }
     let old_checksum_store = self.get_checksums_cache();
     let new_checksum_store: DashMap<String, i64> = DashMap::new();
+    let old_unresolved = self.get_unresolved_imports();
+    let report_cycles = self.setup_options.report_cycles.unwrap_or(false);
+    let old_cycles = if report_cycles {
+      find_cycles(&self.store)
+    } else {
+      Vec::new()
+    };
+    let old_violations = self.check_rules();
 
     self.update_store_with_missing_entries();
+    self.refresh_unresolved_imports();
+    let states = self.get_file_states(&old_checksum_store);
+    self.refresh_modified_files(&states);
+    // files the refresh just made aren't part of `states`
+    let get_state = |file: &str| match states.get(file) {
+      Some(state) => *state,
+      None => self.get_file_state(file, &old_checksum_store),
+    };
 
-    let changes: Vec<EntryChange> = self
+    let mut changes: Vec<EntryChange> = self
       .entries
       .par_iter()
       .map(|entry| {
         // for each entry
-
-        // we update entry deps if the file got modified
         let entry_path = entry.path.to_str().unwrap();
-        let (entry_checksum, entry_state) = self.get_file_state(entry_path, &old_checksum_store);
-        let mut deps = entry.deps.iter().map(String::from).collect();
-        if entry_state == FileState::Modified {
-          deps = self.make_file_deps(entry_path);
-        }
+        let (entry_checksum, entry_state) = get_state(entry_path);
 
-        let mut tree: Vec<String> = Vec::new();
         let mut files = vec![entry.path.to_str().unwrap().to_string()];
-        files.extend(deps.into_iter());
+        files.extend(entry.deps.iter().map(String::from));
         // collect changes for each deps (entry included) of the current entry
         let entry_changes: Vec<Option<EntryChange>> = files
           .iter()
           .enumerate()
           .map(|(i, dep)| {
-            tree.insert(0, dep.to_string());
             let is_entry = i == 0;
+            let tree = || {
+              if is_entry {
+                None
+              } else {
+                Some(self.make_tree(entry_path, dep))
+              }
+            };
             // Try to determine if the file changed
             let (checksum, state) = if is_entry {
               (entry_checksum, entry_state)
             } else {
-              self.get_file_state(dep, &old_checksum_store)
+              get_state(dep)
             };
             new_checksum_store.insert(dep.to_string(), checksum);
             match state {
-              FileState::Deleted => {
-                return Some(EntryChange {
-                  change_type: if is_entry {
-                    EntryChangeType::Deleted
-                  } else {
-                    EntryChangeType::DepDeleted
-                  },
-                  entry: entry.path.to_str().unwrap().to_string(),
-                  cause: if is_entry {
-                    None
-                  } else {
-                    Some(EntryChangeCause {
-                      file: dep.to_string(),
-                      state,
-                    })
-                  },
-                  tree: if is_entry { None } else { Some(tree.clone()) },
-                });
-              }
-              FileState::Created => {
-                return Some(EntryChange {
-                  change_type: if is_entry {
-                    EntryChangeType::Added
-                  } else {
-                    EntryChangeType::DepAdded
-                  },
-                  entry: entry.path.to_str().unwrap().to_string(),
-                  cause: if is_entry {
-                    None
-                  } else {
-                    Some(EntryChangeCause {
-                      file: dep.to_string(),
-                      state,
-                    })
-                  },
-                  tree: if is_entry { None } else { Some(tree.clone()) },
-                });
-              }
-              FileState::Modified => {
-                return Some(EntryChange {
-                  change_type: if is_entry {
-                    EntryChangeType::Modified
-                  } else {
-                    EntryChangeType::DepModified
-                  },
-                  entry: entry.path.to_str().unwrap().to_string(),
-                  cause: if is_entry {
-                    None
-                  } else {
-                    Some(EntryChangeCause {
-                      file: dep.to_string(),
-                      state,
-                    })
-                  },
-                  tree: if is_entry { None } else { Some(tree.clone()) },
-                });
-              }
+              FileState::Deleted => Some(EntryChange {
+                change_type: if is_entry {
+                  EntryChangeType::Deleted
+                } else {
+                  EntryChangeType::DepDeleted
+                },
+                entry: entry.path.to_str().unwrap().to_string(),
+                cause: if is_entry {
+                  None
+                } else {
+                  Some(EntryChangeCause {
+                    file: dep.to_string(),
+                    state,
+                  })
+                },
+                tree: tree(),
+                diagnostic: None,
+                cycle: None,
+                violation: None,
+              }),
+              FileState::Created => Some(EntryChange {
+                change_type: if is_entry {
+                  EntryChangeType::Added
+                } else {
+                  EntryChangeType::DepAdded
+                },
+                entry: entry.path.to_str().unwrap().to_string(),
+                cause: if is_entry {
+                  None
+                } else {
+                  Some(EntryChangeCause {
+                    file: dep.to_string(),
+                    state,
+                  })
+                },
+                tree: tree(),
+                diagnostic: None,
+                cycle: None,
+                violation: None,
+              }),
+              FileState::Modified => Some(EntryChange {
+                change_type: if is_entry {
+                  EntryChangeType::Modified
+                } else {
+                  EntryChangeType::DepModified
+                },
+                entry: entry.path.to_str().unwrap().to_string(),
+                cause: if is_entry {
+                  None
+                } else {
+                  Some(EntryChangeCause {
+                    file: dep.to_string(),
+                    state,
+                  })
+                },
+                tree: tree(),
+                diagnostic: None,
+                cycle: None,
+                violation: None,
+              }),
               _ => None,
             }
           })
@@ -358,10 +555,217 @@ impl WatcherInner {
 
     self.set_checksum_cache(&new_checksum_store);
     self.update_entries_from_store();
+    changes.extend(self.make_resolution_changes(&old_unresolved));
+    if report_cycles {
+      changes.extend(self.make_cycle_changes(&old_cycles));
+    }
+    changes.extend(self.make_rule_changes(&old_violations));
+    changes
+  }
 
+  /// Returns the chain of imports leading from `entry` to `file`, starting with `file`.
+  fn make_tree(&self, entry: &str, file: &str) -> Vec<String> {
+    match find_import_chain(&self.store, entry, file) {
+      Some(mut chain) => {
+        chain.reverse();
+        chain
+      }
+      // `file` was deleted, and the file importing it was made again since
+      None => vec![file.to_string(), entry.to_string()],
+    }
+  }
+
+  /// Returns the `UnresolvedImport` diagnostics of every file of the store.
+  fn get_unresolved_imports(&self) -> HashMap<String, Vec<Diagnostic>> {
+    self
+      .store
+      .iter()
+      .map(|item| {
+        let diagnostics = item
+          .diagnostics
+          .iter()
+          .filter(|x| x.kind == DiagnosticKind::UnresolvedImport)
+          .cloned()
+          .collect::<Vec<Diagnostic>>();
+        (item.key().to_string(), diagnostics)
+      })
+      .filter(|(_, diagnostics)| !diagnostics.is_empty())
+      .collect()
+  }
+
+  /// Makes again the files with unresolved imports, since creating a file or installing a
+  /// package can fix them. The deps of the entries are refreshed.
+  fn refresh_unresolved_imports(&mut self) {
+    let unresolved: Vec<String> = self.get_unresolved_imports().into_keys().collect();
+    if unresolved.is_empty() {
+      return;
+    }
+    let project_root = PathBuf::from(&self.setup_options.project_root);
+    for key in &unresolved {
+      let importers = detach_file(&self.store, key);
+      make_file_item(
+        &PathBuf::from(key),
+        &project_root,
+        &self.store,
+        &self.make_entries_opts,
+      );
+      restore_importers(&self.store, key, importers);
+    }
+    self.update_entries_from_store();
+  }
+
+  /// Compares the unresolved imports of the files of each entry with `old_unresolved`.
+  /// An import is identified by its file and specifier.
+  fn make_resolution_changes(
+    &self,
+    old_unresolved: &HashMap<String, Vec<Diagnostic>>,
+  ) -> Vec<EntryChange> {
+    let new_unresolved = self.get_unresolved_imports();
+    let diff =
+      |a: &HashMap<String, Vec<Diagnostic>>, b: &HashMap<String, Vec<Diagnostic>>, file: &str| {
+        a.get(file)
+          .into_iter()
+          .flatten()
+          .filter(|x| {
+            !b.get(file)
+              .into_iter()
+              .flatten()
+              .any(|y| y.specifier == x.specifier)
+          })
+          .cloned()
+          .collect::<Vec<Diagnostic>>()
+      };
+
+    let mut changes = Vec::new();
+    for entry in &self.entries {
+      let entry_path = entry.path.to_str().unwrap();
+      let files = std::iter::once(entry_path).chain(entry.deps.iter().map(|x| &x[..]));
+      for file in files {
+        for (change_type, diagnostics) in [
+          (
+            EntryChangeType::ImportUnresolved,
+            diff(&new_unresolved, old_unresolved, file),
+          ),
+          (
+            EntryChangeType::ImportResolved,
+            diff(old_unresolved, &new_unresolved, file),
+          ),
+        ] {
+          for diagnostic in diagnostics {
+            changes.push(EntryChange {
+              change_type,
+              entry: entry_path.to_string(),
+              cause: None,
+              tree: None,
+              diagnostic: Some(diagnostic),
+              cycle: None,
+              violation: None,
+            });
+          }
+        }
+      }
+    }
     changes
   }
 
+  /// Reports the cycles that aren't part of `old_cycles` to every entry depending on them.
+  /// A cycle that grew is a new one.
+  fn make_cycle_changes(&self, old_cycles: &[Vec<String>]) -> Vec<EntryChange> {
+    let mut changes = Vec::new();
+    for cycle in find_cycles(&self.store) {
+      if old_cycles.contains(&cycle) {
+        continue;
+      }
+      for entry in &self.entries {
+        let entry_path = entry.path.to_str().unwrap();
+        // the tree leads to the first file of the cycle, unless the entry is part of it
+        let tree = if cycle.iter().any(|x| x == entry_path) {
+          None
+        } else {
+          match cycle.iter().find(|x| entry.deps.contains(*x)) {
+            Some(file) => Some(self.make_tree(entry_path, file)),
+            None => continue,
+          }
+        };
+        changes.push(EntryChange {
+          change_type: EntryChangeType::CycleAdded,
+          entry: entry_path.to_string(),
+          cause: None,
+          tree,
+          diagnostic: None,
+          cycle: Some(cycle.clone()),
+          violation: None,
+        });
+      }
+    }
+    changes
+  }
+
+  /// Reports the violations that aren't part of `old_violations` to every entry depending on
+  /// their importer.
+  fn make_rule_changes(&self, old_violations: &[RuleViolation]) -> Vec<EntryChange> {
+    let mut changes = Vec::new();
+    for violation in self.check_rules() {
+      if old_violations.iter().any(|x| x.is_same(&violation)) {
+        continue;
+      }
+      for entry in &self.entries {
+        let entry_path = entry.path.to_str().unwrap();
+        let tree = if violation.importer == entry_path {
+          None
+        } else if entry.deps.contains(&violation.importer) {
+          Some(self.make_tree(entry_path, &violation.importer))
+        } else {
+          continue;
+        };
+        changes.push(EntryChange {
+          change_type: EntryChangeType::RuleViolated,
+          entry: entry_path.to_string(),
+          cause: None,
+          tree,
+          diagnostic: None,
+          cycle: None,
+          violation: Some(violation.clone()),
+        });
+      }
+    }
+    changes
+  }
+
+  /// Returns the checksum and the state of every file of the store, see `get_file_state`.
+  fn get_file_states(
+    &self,
+    checksum_store: &HashMap<String, i64>,
+  ) -> HashMap<String, (i64, FileState)> {
+    let files: Vec<String> = self.store.iter().map(|x| x.key().to_string()).collect();
+    files
+      .into_par_iter()
+      .map(|file| {
+        let state = self.get_file_state(&file, checksum_store);
+        (file, state)
+      })
+      .collect()
+  }
+
+  /// Makes again the files modified since the last call, entries or not, so that their new
+  /// imports are followed. The deps of the entries are refreshed.
+  fn refresh_modified_files(&mut self, states: &HashMap<String, (i64, FileState)>) {
+    let modified: Vec<&String> = states
+      .iter()
+      .filter(|(_, (_, state))| *state == FileState::Modified)
+      .map(|(file, _)| file)
+      // packages watched with `ExternalMode::Package` stand for their package.json, it isn't made
+      .filter(|file| Path::new(file).file_name() != Some("package.json".as_ref()))
+      .collect();
+    if modified.is_empty() {
+      return;
+    }
+    for file in modified {
+      self.make_file_deps(file);
+    }
+    self.update_entries_from_store();
+  }
+
   fn get_file_state(
     &self,
     file_path: &str,
@@ -390,6 +794,246 @@ impl WatcherInner {
     }
   }
 
+  pub fn get_diagnostics(&self) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = self
+      .store
+      .iter()
+      .flat_map(|x| x.diagnostics.clone())
+      .collect();
+    diagnostics.sort_by(|a, b| a.importer.cmp(&b.importer));
+    diagnostics
+  }
+
+  /// Explains how `specifier` resolves when imported from `importer` with an import of `kind`.
+  /// A relative `importer` is relative to the project root.
+  pub fn explain_resolve(&self, importer: &str, specifier: &str, kind: ImportKind) -> ResolveTrace {
+    let project_root = PathBuf::from(&self.setup_options.project_root);
+    let importer = project_root.join(importer).clean();
+    explain_resolve(
+      &importer,
+      specifier,
+      kind,
+      &project_root,
+      &self.make_entries_opts,
+    )
+  }
+
+  /// Returns the graph of the files of the entries, see `build_graph`.
+  pub fn get_graph(&self, opts: &GraphOptions) -> DependencyGraph {
+    let roots: Vec<String> = match &opts.entry {
+      Some(entry) => {
+        let entry = PathBuf::from(&self.setup_options.project_root)
+          .join(entry)
+          .clean();
+        vec![entry.to_str().unwrap().to_string()]
+      }
+      None => self
+        .entries
+        .iter()
+        .map(|x| x.path.to_str().unwrap().to_string())
+        .collect(),
+    };
+    build_graph(
+      &self.store,
+      &roots,
+      opts.max_depth,
+      opts.collapse_packages.unwrap_or(false),
+    )
+  }
+
+  pub fn export_graph(&self, format: GraphFormat, opts: &GraphOptions) -> String {
+    write_graph(
+      &self.get_graph(opts),
+      format,
+      &self.setup_options.project_root,
+    )
+  }
+
+  /// Saves the graph of every entry as the snapshot `name` of the cache directory, and returns
+  /// the path of the snapshot. Fails for names that aren't file names, see
+  /// `is_valid_snapshot_name`, and when the snapshot can't be written.
+  pub fn save_snapshot(&self, name: &str) -> Result<String> {
+    let snapshot = GraphSnapshot {
+      entries: self
+        .entries
+        .iter()
+        .map(|x| x.path.to_str().unwrap().to_string())
+        .collect(),
+      graph: self.get_graph(&GraphOptions::default()),
+    };
+    let project_root = Path::new(&self.setup_options.project_root);
+    let path =
+      save_snapshot(&self.cache_dir, name, &snapshot, project_root).map_err(Error::from_reason)?;
+    Ok(path.to_string_lossy().to_string())
+  }
+
+  /// Compares the snapshots `from` and `to` of the cache directory. Returns `None` when one of
+  /// them can't be read.
+  pub fn diff_graphs(&self, from: &str, to: &str) -> Option<GraphDiff> {
+    let project_root = Path::new(&self.setup_options.project_root);
+    let from = load_snapshot(&self.cache_dir, from, project_root)?;
+    let to = load_snapshot(&self.cache_dir, to, project_root)?;
+    Some(diff_snapshots(&from, &to))
+  }
+
+  /// Returns the files matching `globs` that no entry depends on, except the ones of
+  /// `node_modules`. Relative globs are relative to the project root. Fails on invalid globs,
+  /// the ignored and test ones included.
+  pub fn get_unreachable(
+    &self,
+    globs: &[String],
+    opts: &UnreachableOptions,
+  ) -> Result<Vec<String>> {
+    let project_root = PathBuf::from(&self.setup_options.project_root);
+    let to_patterns = |globs: &[String]| -> Result<Vec<Pattern>> {
+      globs
+        .iter()
+        .map(|x| {
+          Pattern::new(x)
+            .map_err(|err| Error::from_reason(format!("Invalid glob `{}`: {}", x, err.msg)))
+        })
+        .collect()
+    };
+    let matches = |patterns: &[Pattern], path: &Path| {
+      let relative = path.strip_prefix(&project_root).unwrap_or(path);
+      patterns.iter().any(|x| x.matches_path(relative))
+    };
+    // `expand_globs` panics on invalid globs
+    to_patterns(globs)?;
+    let ignore = to_patterns(&opts.ignore.clone().unwrap_or_default())?;
+    let test_patterns = match &opts.test_patterns {
+      Some(patterns) => to_patterns(patterns)?,
+      None => to_patterns(&DEFAULT_TEST_PATTERNS.map(String::from))?,
+    };
+    let globs: Vec<&str> = globs.iter().map(|x| &x[..]).collect();
+    let files: Vec<PathBuf> = expand_globs(&globs, &project_root)
+      .into_iter()
+      .filter(|x| {
+        x.is_file()
+          && !x.components().any(|x| x.as_os_str() == "node_modules")
+          && !matches(&ignore, x)
+      })
+      .collect();
+
+    let mut roots: Vec<String> = self
+      .entries
+      .iter()
+      .map(|x| x.path.to_str().unwrap().to_string())
+      .collect();
+    // test files are made in a copy of the store, they aren't watched
+    let tests_store: DashMap<String, FileItem>;
+    let store = if opts.tests_as_roots.unwrap_or(false) {
+      tests_store = self
+        .store
+        .iter()
+        .map(|x| (x.key().to_string(), x.clone_item()))
+        .collect();
+      for file in files.iter().filter(|x| matches(&test_patterns, x)) {
+        make_file_item(file, &project_root, &tests_store, &self.make_entries_opts);
+        roots.push(file.to_str().unwrap().to_string());
+      }
+      &tests_store
+    } else {
+      &self.store
+    };
+
+    let reachable = collect_reachable(store, &roots);
+    let mut unreachable: Vec<String> = files
+      .iter()
+      .map(|x| x.to_str().unwrap().to_string())
+      .filter(|x| !reachable.contains(x))
+      .collect();
+    unreachable.sort();
+    unreachable.dedup();
+    Ok(unreachable)
+  }
+
+  /// Returns the circular imports between the files of the project, see `find_cycles`.
+  pub fn get_cycles(&self) -> Vec<Vec<String>> {
+    find_cycles(&self.store)
+  }
+
+  /// Returns the imports breaking the `rules` of the setup options, see `check_rules`.
+  pub fn check_rules(&self) -> Vec<RuleViolation> {
+    if self.rules.is_empty() {
+      return Vec::new();
+    }
+    check_rules(
+      &self.store,
+      &self.rules,
+      Path::new(&self.setup_options.project_root),
+    )
+  }
+
+  /// Returns the exports of the files of the project that no file imports, see
+  /// `find_unused_exports`. Entries' exports are never reported.
+  pub fn get_unused_exports(&self) -> Vec<UnusedExport> {
+    let entries: Vec<String> = self
+      .entries
+      .iter()
+      .map(|x| x.path.to_str().unwrap().to_string())
+      .collect();
+    find_unused_exports(&self.store, &entries)
+  }
+
+  /// Explains why `file` is a dep of `entry`, with up to `max_paths` chains of imports leading
+  /// to it, the shortest first. Relative paths are relative to the project root.
+  pub fn explain_dependency(
+    &self,
+    entry: &str,
+    file: &str,
+    max_paths: u32,
+  ) -> Vec<Vec<ImportStep>> {
+    let project_root = PathBuf::from(&self.setup_options.project_root);
+    let entry = project_root.join(entry).clean();
+    let file = project_root.join(file).clean();
+    find_import_chains(
+      &self.store,
+      entry.to_str().unwrap(),
+      file.to_str().unwrap(),
+      max_paths as usize,
+    )
+    .iter()
+    .map(|chain| {
+      chain
+        .windows(2)
+        .map(|pair| {
+          let site = self
+            .store
+            .get(&pair[0])
+            .and_then(|x| x.import_sites.get(&pair[1]).cloned());
+          ImportStep {
+            importer: pair[0].clone(),
+            file: pair[1].clone(),
+            specifier: site.as_ref().map(|x| x.specifier.clone()),
+            span: site.map(|x| x.span),
+          }
+        })
+        .collect()
+    })
+    .collect()
+  }
+
+  /// Returns the files importing `file`, or depending on it at all with `transitive`.
+  /// A relative `file` is relative to the project root.
+  pub fn get_importers(&self, file: &str, transitive: bool) -> Vec<String> {
+    let file = PathBuf::from(&self.setup_options.project_root)
+      .join(file)
+      .clean();
+    let key = file.to_str().unwrap();
+    let mut importers: Vec<String> = if transitive {
+      collect_importers(&self.store, key).into_iter().collect()
+    } else {
+      self
+        .store
+        .get(key)
+        .map(|item| item.importers.iter().map(String::from).collect())
+        .unwrap_or_default()
+    };
+    importers.sort();
+    importers
+  }
+
   pub fn get_dirs_to_watch(&self) -> Vec<String> {
     let mut set = HashSet::new();
     set.insert(self.setup_options.project_root.clone());
@@ -404,13 +1048,14 @@ impl WatcherInner {
 
 #[napi]
 impl Watcher {
+  /// Fails on invalid rules
   #[napi(factory)]
-  pub fn setup(opts: SetupOptions) -> Self {
-    let inner = WatcherInner::setup(opts);
-    Watcher {
+  pub fn setup(opts: SetupOptions) -> Result<Self> {
+    let inner = WatcherInner::setup(opts)?;
+    Ok(Watcher {
       inner: Arc::new(Mutex::new(inner)),
       stop_watch_flag: Arc::new(AtomicBool::new(false)),
-    }
+    })
   }
 
   pub fn setup_options(&self) -> SetupOptions {
@@ -427,8 +1072,18 @@ impl Watcher {
   }
 
   #[napi]
-  pub fn get_entries(&self) -> Vec<FileItem> {
-    self.inner.lock().unwrap().get_entries()
+  pub fn get_entries(&self, opts: Option<GetEntriesOptions>) -> Vec<FileItem> {
+    let sorted = opts.and_then(|x| x.sorted).unwrap_or(false);
+    self.inner.lock().unwrap().get_entries(sorted)
+  }
+
+  #[napi]
+  pub fn topological_order(&self, entry: Option<String>) -> Vec<Vec<String>> {
+    self
+      .inner
+      .lock()
+      .unwrap()
+      .topological_order(entry.as_deref())
   }
 
   #[napi]
@@ -441,6 +1096,106 @@ impl Watcher {
     self.inner.lock().unwrap().get_dirs_to_watch()
   }
 
+  #[napi]
+  pub fn get_diagnostics(&self) -> Vec<Diagnostic> {
+    self.inner.lock().unwrap().get_diagnostics()
+  }
+
+  #[napi]
+  pub fn get_graph(&self, opts: Option<GraphOptions>) -> DependencyGraph {
+    self
+      .inner
+      .lock()
+      .unwrap()
+      .get_graph(&opts.unwrap_or_default())
+  }
+
+  /// Writes the graph of the files of the entries as JSON, Graphviz DOT or Mermaid
+  #[napi]
+  pub fn export_graph(&self, format: GraphFormat, opts: Option<GraphOptions>) -> String {
+    self
+      .inner
+      .lock()
+      .unwrap()
+      .export_graph(format, &opts.unwrap_or_default())
+  }
+
+  /// Fails for invalid names, and when the snapshot can't be written
+  #[napi]
+  pub fn save_snapshot(&self, name: String) -> Result<String> {
+    self.inner.lock().unwrap().save_snapshot(&name)
+  }
+
+  #[napi]
+  pub fn diff_graphs(&self, from: String, to: String) -> Option<GraphDiff> {
+    self.inner.lock().unwrap().diff_graphs(&from, &to)
+  }
+
+  /// Fails on invalid globs
+  #[napi]
+  pub fn get_unreachable(
+    &self,
+    globs: Vec<String>,
+    opts: Option<UnreachableOptions>,
+  ) -> Result<Vec<String>> {
+    self
+      .inner
+      .lock()
+      .unwrap()
+      .get_unreachable(&globs, &opts.unwrap_or_default())
+  }
+
+  #[napi]
+  pub fn get_cycles(&self) -> Vec<Vec<String>> {
+    self.inner.lock().unwrap().get_cycles()
+  }
+
+  #[napi]
+  pub fn get_unused_exports(&self) -> Vec<UnusedExport> {
+    self.inner.lock().unwrap().get_unused_exports()
+  }
+
+  #[napi]
+  pub fn check_rules(&self) -> Vec<RuleViolation> {
+    self.inner.lock().unwrap().check_rules()
+  }
+
+  #[napi]
+  pub fn explain_dependency(
+    &self,
+    entry: String,
+    file: String,
+    opts: Option<ExplainDependencyOptions>,
+  ) -> Vec<Vec<ImportStep>> {
+    let max_paths = opts.and_then(|x| x.max_paths).unwrap_or(1);
+    self
+      .inner
+      .lock()
+      .unwrap()
+      .explain_dependency(&entry, &file, max_paths)
+  }
+
+  #[napi]
+  pub fn get_importers(&self, file: String, opts: Option<GetImportersOptions>) -> Vec<String> {
+    let transitive = opts.and_then(|x| x.transitive).unwrap_or(false);
+    self.inner.lock().unwrap().get_importers(&file, transitive)
+  }
+
+  /// `kind` defaults to `Esm`
+  #[napi]
+  pub fn explain_resolve(
+    &self,
+    importer: String,
+    specifier: String,
+    kind: Option<ImportKind>,
+  ) -> ResolveTrace {
+    self.inner.lock().unwrap().explain_resolve(
+      &importer,
+      &specifier,
+      kind.unwrap_or(ImportKind::Esm),
+    )
+  }
+
   pub fn watch<F>(&mut self, on_event: F)
   where
     F: Fn(Vec<EntryChange>) -> Result<(), String> + std::marker::Sync + std::marker::Send + 'static,
@@ -449,12 +1204,12 @@ impl Watcher {
     let on_event_arced = Arc::new(on_event);
     let inner = self.inner.clone();
 
-    /**
+    /*
      * Spawn a thread to poll for changes.
      * We're using a poller because watching for file system changes is unreliable
      * across platforms.
      * See https://github.com/notify-rs/notify/issues/465 and https://github.com/notify-rs/notify/issues/468
-     **/
+     */
     std::thread::spawn(move || {
       let on_event_cb = on_event_arced.clone();
       loop {
@@ -511,8 +1266,17 @@ impl Watcher {
 
 #[cfg(test)]
 mod tests {
-  use crate::watcher::{EntryChangeType, SetupOptions, Watcher};
+  use crate::export::GraphFormat;
+  use crate::rules::{BoundaryRule, RuleViolationKind};
+  use crate::test_utils::copy_fixture;
+  #[cfg(unix)]
+  use crate::test_utils::link_fixture;
+  use crate::watcher::{
+    EntryChange, EntryChangeType, ExplainDependencyOptions, GetEntriesOptions, GetImportersOptions,
+    GraphOptions, SetupOptions, UnreachableOptions, Watcher, WatcherInner,
+  };
   use lazy_static::lazy_static;
+  use std::collections::HashSet;
   use std::path::PathBuf;
   use std::sync::atomic::{AtomicBool, Ordering};
   use std::sync::Arc;
@@ -522,6 +1286,12 @@ mod tests {
     static ref CWD: PathBuf = PathBuf::from(std::env::current_dir().unwrap());
     static ref PROJECT_A_PATH: PathBuf = CWD.join("tests").join("fixtures").join("project_a");
     static ref THREEJS_PATH: PathBuf = CWD.join("tests").join("fixtures").join("three_js");
+    static ref GRAPH_PATH: PathBuf = CWD.join("tests").join("fixtures").join("graph");
+    static ref UNREACHABLE_PATH: PathBuf = CWD.join("tests").join("fixtures").join("unreachable");
+    static ref CYCLES_PATH: PathBuf = CWD.join("tests").join("fixtures").join("cycles");
+    static ref RULES_PATH: PathBuf = CWD.join("tests").join("fixtures").join("rules");
+    static ref UNUSED_EXPORTS_PATH: PathBuf =
+      CWD.join("tests").join("fixtures").join("unused_exports");
   }
 
   #[test]
@@ -540,10 +1310,800 @@ mod tests {
       cache_dir: None,
       supported_paths: None,
       debug: None,
-    });
+      ..Default::default()
+    })
+    .unwrap();
     assert_eq!(watcher.processed(), true);
   }
 
+  #[test]
+  fn make_changes_tree() {
+    let graph_path = copy_fixture("graph", "make_changes_tree");
+    let entry_path = graph_path.join("entry.js");
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Graph".to_string(),
+      project_root: graph_path.to_str().unwrap().to_string(),
+      entries: Some(vec![entry_path.to_str().unwrap().to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    watcher.make_changes();
+
+    let b_path = graph_path.join("b.js");
+    let content = std::fs::read_to_string(&b_path).unwrap();
+    std::fs::write(&b_path, content.replace("'b'", "'B'")).unwrap();
+    let changes = watcher.make_changes();
+
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].change_type, EntryChangeType::DepModified);
+    // the actual imports leading to `b.js`, `c.js` isn't part of it
+    assert_eq!(
+      changes[0].tree,
+      Some(
+        [&b_path, &graph_path.join("a.js"), &entry_path]
+          .iter()
+          .map(|x| x.to_str().unwrap().to_string())
+          .collect()
+      )
+    );
+  }
+
+  #[test]
+  fn make_changes_cycle_added() {
+    let cycles_path = copy_fixture("cycles", "make_changes_cycle_added");
+    let entry_path = cycles_path.join("entry.js");
+    let y_path = cycles_path.join("y.js");
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Cycles".to_string(),
+      project_root: cycles_path.to_str().unwrap().to_string(),
+      entries: Some(vec![
+        entry_path.to_str().unwrap().to_string(),
+        y_path.to_str().unwrap().to_string(),
+      ]),
+      report_cycles: Some(true),
+      ..Default::default()
+    })
+    .unwrap();
+    watcher.make_changes();
+    assert!(watcher.get_cycles().is_empty());
+
+    // `y.js` now imports `x.js` back
+    let content = std::fs::read_to_string(&y_path).unwrap();
+    std::fs::write(&y_path, format!("import x from './x.js';\n{}", content)).unwrap();
+    let changes = watcher.make_changes();
+    let cycles = watcher.get_cycles();
+
+    let cycle: Vec<String> = ["x.js", "y.js"]
+      .iter()
+      .map(|x| cycles_path.join(x).to_str().unwrap().to_string())
+      .collect();
+    assert_eq!(cycles, vec![cycle.clone()]);
+    let mut cycle_changes: Vec<&EntryChange> = changes
+      .iter()
+      .filter(|x| x.change_type == EntryChangeType::CycleAdded)
+      .collect();
+    cycle_changes.sort_by(|a, b| a.entry.cmp(&b.entry));
+    assert_eq!(cycle_changes.len(), 2);
+    assert_eq!(cycle_changes[0].entry, entry_path.to_str().unwrap());
+    assert_eq!(cycle_changes[0].cycle, Some(cycle.clone()));
+    assert_eq!(
+      cycle_changes[0].tree,
+      Some(vec![
+        cycle[0].clone(),
+        entry_path.to_str().unwrap().to_string()
+      ])
+    );
+    assert_eq!(cycle_changes[1].entry, y_path.to_str().unwrap());
+    assert_eq!(cycle_changes[1].tree, None);
+  }
+
+  #[test]
+  fn make_changes_cycle_added_by_dep() {
+    let cycles_path = copy_fixture("cycles", "make_changes_cycle_added_by_dep");
+    let entry_path = cycles_path.join("entry.js");
+    let y_path = cycles_path.join("y.js");
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Cycles".to_string(),
+      project_root: cycles_path.to_str().unwrap().to_string(),
+      entries: Some(vec![entry_path.to_str().unwrap().to_string()]),
+      report_cycles: Some(true),
+      ..Default::default()
+    })
+    .unwrap();
+    watcher.make_changes();
+
+    // `y.js` isn't an entry, and now imports `x.js` back
+    let content = std::fs::read_to_string(&y_path).unwrap();
+    std::fs::write(&y_path, format!("import x from './x.js';\n{}", content)).unwrap();
+    let changes = watcher.make_changes();
+
+    let cycle: Vec<String> = ["x.js", "y.js"]
+      .iter()
+      .map(|x| cycles_path.join(x).to_str().unwrap().to_string())
+      .collect();
+    assert_eq!(watcher.get_cycles(), vec![cycle.clone()]);
+    assert!(changes
+      .iter()
+      .any(|x| x.change_type == EntryChangeType::DepModified
+        && x.cause.as_ref().unwrap().file == y_path.to_str().unwrap()));
+    let cycle_changes: Vec<&EntryChange> = changes
+      .iter()
+      .filter(|x| x.change_type == EntryChangeType::CycleAdded)
+      .collect();
+    assert_eq!(cycle_changes.len(), 1);
+    assert_eq!(cycle_changes[0].entry, entry_path.to_str().unwrap());
+    assert_eq!(cycle_changes[0].cycle, Some(cycle.clone()));
+    assert_eq!(
+      cycle_changes[0].tree,
+      Some(vec![
+        cycle[0].clone(),
+        entry_path.to_str().unwrap().to_string()
+      ])
+    );
+  }
+
+  #[test]
+  fn make_changes_rule_violated() {
+    let rules_path = copy_fixture("rules", "make_changes_rule_violated");
+    let main_path = rules_path.join("src").join("app").join("main.js");
+    let search_path = rules_path
+      .join("src")
+      .join("features")
+      .join("search")
+      .join("index.js");
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Rules".to_string(),
+      project_root: rules_path.to_str().unwrap().to_string(),
+      entries: Some(vec![
+        main_path.to_str().unwrap().to_string(),
+        search_path.to_str().unwrap().to_string(),
+      ]),
+      rules: Some(vec![BoundaryRule {
+        from: "src/features/**".to_string(),
+        deny: Some(vec!["src/app/**".to_string()]),
+        ..Default::default()
+      }]),
+      ..Default::default()
+    })
+    .unwrap();
+    watcher.make_changes();
+    // `cart/index.js` imports the store from the start
+    assert_eq!(watcher.check_rules().len(), 1);
+
+    // `search/index.js` now imports it too
+    let content = std::fs::read_to_string(&search_path).unwrap();
+    std::fs::write(
+      &search_path,
+      format!("import {{ store }} from '../../app/store.js';\n{}", content),
+    )
+    .unwrap();
+    let changes = watcher.make_changes();
+
+    let mut rule_changes: Vec<&EntryChange> = changes
+      .iter()
+      .filter(|x| x.change_type == EntryChangeType::RuleViolated)
+      .collect();
+    rule_changes.sort_by(|a, b| a.entry.cmp(&b.entry));
+    assert_eq!(rule_changes.len(), 2);
+    let search = search_path.to_str().unwrap().to_string();
+    let violation = rule_changes[0].violation.as_ref().unwrap();
+    assert_eq!(violation.importer, search);
+    assert_eq!(violation.kind, RuleViolationKind::Denied);
+    assert_eq!(
+      violation.imported,
+      rules_path.join("src/app/store.js").to_str().unwrap()
+    );
+    assert_eq!(rule_changes[0].entry, main_path.to_str().unwrap());
+    assert_eq!(
+      rule_changes[0].tree,
+      Some(vec![
+        search.clone(),
+        main_path.to_str().unwrap().to_string()
+      ])
+    );
+    assert_eq!(rule_changes[1].entry, search);
+    assert_eq!(rule_changes[1].tree, None);
+  }
+
+  #[test]
+  fn make_changes_rule_violated_by_dep() {
+    let rules_path = copy_fixture("rules", "make_changes_rule_violated_by_dep");
+    let main_path = rules_path.join("src").join("app").join("main.js");
+    let search_path = rules_path
+      .join("src")
+      .join("features")
+      .join("search")
+      .join("index.js");
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Rules".to_string(),
+      project_root: rules_path.to_str().unwrap().to_string(),
+      entries: Some(vec![main_path.to_str().unwrap().to_string()]),
+      rules: Some(vec![BoundaryRule {
+        from: "src/features/**".to_string(),
+        deny: Some(vec!["src/app/**".to_string()]),
+        ..Default::default()
+      }]),
+      ..Default::default()
+    })
+    .unwrap();
+    watcher.make_changes();
+
+    // `search/index.js` isn't an entry, and now imports the store
+    let content = std::fs::read_to_string(&search_path).unwrap();
+    std::fs::write(
+      &search_path,
+      format!("import {{ store }} from '../../app/store.js';\n{}", content),
+    )
+    .unwrap();
+    let changes = watcher.make_changes();
+
+    let rule_changes: Vec<&EntryChange> = changes
+      .iter()
+      .filter(|x| x.change_type == EntryChangeType::RuleViolated)
+      .collect();
+    assert_eq!(rule_changes.len(), 1);
+    let search = search_path.to_str().unwrap().to_string();
+    assert_eq!(rule_changes[0].violation.as_ref().unwrap().importer, search);
+    assert_eq!(rule_changes[0].entry, main_path.to_str().unwrap());
+    assert_eq!(
+      rule_changes[0].tree,
+      Some(vec![search, main_path.to_str().unwrap().to_string()])
+    );
+  }
+
+  #[test]
+  fn explain_dependency() {
+    let watcher = Watcher::setup(SetupOptions {
+      project: "Why".to_string(),
+      project_root: GRAPH_PATH.to_str().unwrap().to_string(),
+      entries: Some(vec![GRAPH_PATH
+        .join("entry.js")
+        .to_str()
+        .unwrap()
+        .to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    let path = |file: &str| GRAPH_PATH.join(file).to_str().unwrap().to_string();
+
+    let chains = watcher.explain_dependency("entry.js".to_string(), "b.js".to_string(), None);
+    assert_eq!(chains.len(), 1);
+    let steps: Vec<(String, String, Option<String>, Option<u32>)> = chains[0]
+      .iter()
+      .map(|x| {
+        (
+          x.importer.clone(),
+          x.file.clone(),
+          x.specifier.clone(),
+          x.span.map(|x| x.line),
+        )
+      })
+      .collect();
+    assert_eq!(
+      steps,
+      vec![
+        (
+          path("entry.js"),
+          path("a.js"),
+          Some("./a.js".to_string()),
+          Some(1)
+        ),
+        (
+          path("a.js"),
+          path("b.js"),
+          Some("./b.js".to_string()),
+          Some(1)
+        ),
+      ]
+    );
+    assert!(watcher
+      .explain_dependency(
+        "c.js".to_string(),
+        "a.js".to_string(),
+        Some(ExplainDependencyOptions { max_paths: Some(3) })
+      )
+      .is_empty());
+  }
+
+  #[test]
+  fn export_graph() {
+    let watcher = Watcher::setup(SetupOptions {
+      project: "Export".to_string(),
+      project_root: GRAPH_PATH.to_str().unwrap().to_string(),
+      entries: Some(vec![
+        GRAPH_PATH.join("entry.js").to_str().unwrap().to_string(),
+        GRAPH_PATH.join("c.js").to_str().unwrap().to_string(),
+      ]),
+      ..Default::default()
+    })
+    .unwrap();
+
+    assert_eq!(watcher.get_graph(None).nodes.len(), 4);
+    let opts = GraphOptions {
+      entry: Some("c.js".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(watcher.get_graph(Some(opts.clone())).nodes.len(), 1);
+    assert_eq!(
+      watcher.export_graph(GraphFormat::Mermaid, Some(opts)),
+      "graph LR\n  n0[\"./c.js\"]\n"
+    );
+  }
+
+  #[test]
+  fn diff_graphs() {
+    let cycles_path = copy_fixture("cycles", "diff_graphs");
+    let entry_path = cycles_path.join("entry.js");
+    let y_path = cycles_path.join("y.js");
+    let watcher = |project: &str| {
+      Watcher::setup(SetupOptions {
+        project: project.to_string(),
+        project_root: cycles_path.to_str().unwrap().to_string(),
+        entries: Some(vec![entry_path.to_str().unwrap().to_string()]),
+        ..Default::default()
+      })
+      .unwrap()
+    };
+    watcher("Before").save_snapshot("base".to_string()).unwrap();
+
+    // `y.js` now imports `node:fs` and `entry.js`
+    let content = std::fs::read_to_string(&y_path).unwrap();
+    std::fs::write(
+      &y_path,
+      format!(
+        "import fs from 'node:fs';\nimport './entry.js';\n{}",
+        content
+      ),
+    )
+    .unwrap();
+    let after = watcher("After");
+    after.save_snapshot("head".to_string()).unwrap();
+    let diff = after
+      .diff_graphs("base".to_string(), "head".to_string())
+      .unwrap();
+    assert!(after
+      .diff_graphs("base".to_string(), "missing".to_string())
+      .is_none());
+
+    let edges: Vec<(String, String)> = diff
+      .added_edges
+      .iter()
+      .map(|x| (x.from.clone(), x.to.clone()))
+      .collect();
+    let y = y_path.to_str().unwrap().to_string();
+    assert_eq!(
+      edges,
+      vec![
+        (y.clone(), entry_path.to_str().unwrap().to_string()),
+        (y, "node:fs".to_string()),
+      ]
+    );
+    assert!(diff.removed_edges.is_empty());
+    assert_eq!(diff.entries.len(), 1);
+    // `entry.js` is never part of its own tree, even when `y.js` imports it back
+    assert_eq!(diff.entries[0].added_nodes, vec!["node:fs".to_string()]);
+  }
+
+  #[test]
+  fn get_unreachable() {
+    let watcher = Watcher::setup(SetupOptions {
+      project: "Unreachable".to_string(),
+      project_root: UNREACHABLE_PATH.to_str().unwrap().to_string(),
+      entries: Some(vec![UNREACHABLE_PATH
+        .join("main.js")
+        .to_str()
+        .unwrap()
+        .to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    let paths = |files: &[&str]| -> Vec<String> {
+      files
+        .iter()
+        .map(|x| UNREACHABLE_PATH.join(x).to_str().unwrap().to_string())
+        .collect()
+    };
+    let globs = vec!["**/*.js".to_string()];
+
+    assert_eq!(
+      watcher.get_unreachable(globs.clone(), None).unwrap(),
+      paths(&[
+        "__tests__/used.test.js",
+        "generated/schema.js",
+        "helper.js",
+        "orphan/dead.js",
+        "unused.js"
+      ])
+    );
+    assert_eq!(
+      watcher
+        .get_unreachable(
+          globs,
+          Some(UnreachableOptions {
+            ignore: Some(vec!["generated/**".to_string()]),
+            tests_as_roots: Some(true),
+            ..Default::default()
+          })
+        )
+        .unwrap(),
+      paths(&["orphan/dead.js", "unused.js"])
+    );
+    // a typo in the ignored globs would report the files they were meant to leave out
+    let res = watcher.get_unreachable(
+      vec!["**/*.js".to_string()],
+      Some(UnreachableOptions {
+        ignore: Some(vec!["generated/[**".to_string()]),
+        ..Default::default()
+      }),
+    );
+    assert_eq!(
+      res.unwrap_err().reason,
+      "Invalid glob `generated/[**`: invalid range pattern"
+    );
+    // test files aren't watched
+    assert_eq!(watcher.get_dirs_to_watch().len(), 1);
+  }
+
+  #[test]
+  fn get_unused_exports() {
+    let watcher = Watcher::setup(SetupOptions {
+      project: "UnusedExports".to_string(),
+      project_root: UNUSED_EXPORTS_PATH.to_str().unwrap().to_string(),
+      entries: Some(vec![UNUSED_EXPORTS_PATH
+        .join("main.js")
+        .to_str()
+        .unwrap()
+        .to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    let unused: Vec<(String, String)> = watcher
+      .get_unused_exports()
+      .into_iter()
+      .map(|x| {
+        let file = PathBuf::from(x.file);
+        let file = file.strip_prefix(&*UNUSED_EXPORTS_PATH).unwrap();
+        (file.to_str().unwrap().to_string(), x.name)
+      })
+      .collect();
+    let expected = |file: &str, name: &str| (file.to_string(), name.to_string());
+    // `version` and `load` are exports of the entry
+    assert_eq!(
+      unused,
+      vec![
+        expected("barrel.js", "y"),
+        expected("inner.js", "x"),
+        expected("inner.js", "z"),
+        expected("lib.js", "unused"),
+        expected("lib.js", "default"),
+      ]
+    );
+  }
+
+  #[test]
+  fn get_entries_sorted() {
+    let watcher = Watcher::setup(SetupOptions {
+      project: "Sorted".to_string(),
+      project_root: CYCLES_PATH.to_str().unwrap().to_string(),
+      glob_entries: Some(vec!["*.js".to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    let key = |file: &str| CYCLES_PATH.join(file).to_str().unwrap().to_string();
+
+    // `entry.js` imports `x.js`, which imports `y.js`
+    let entries: Vec<String> = watcher
+      .get_entries(Some(GetEntriesOptions { sorted: Some(true) }))
+      .iter()
+      .map(|x| x.path.to_str().unwrap().to_string())
+      .collect();
+    assert_eq!(entries, vec![key("y.js"), key("x.js"), key("entry.js")]);
+    assert_eq!(
+      watcher.topological_order(Some("x.js".to_string())),
+      vec![vec![key("y.js")], vec![key("x.js")]]
+    );
+  }
+
+  #[test]
+  fn get_importers() {
+    let graph_path = copy_fixture("graph", "get_importers");
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Importers".to_string(),
+      project_root: graph_path.to_str().unwrap().to_string(),
+      entries: Some(vec![graph_path
+        .join("entry.js")
+        .to_str()
+        .unwrap()
+        .to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    watcher.make_changes();
+    let paths = |files: &[&str]| -> Vec<String> {
+      files
+        .iter()
+        .map(|x| graph_path.join(x).to_str().unwrap().to_string())
+        .collect()
+    };
+
+    assert_eq!(
+      watcher.get_importers("a.js".to_string(), None),
+      paths(&["b.js", "entry.js"])
+    );
+    assert_eq!(
+      watcher.get_importers(
+        "b.js".to_string(),
+        Some(GetImportersOptions {
+          transitive: Some(true)
+        })
+      ),
+      paths(&["a.js", "b.js", "entry.js"])
+    );
+    assert!(watcher
+      .get_importers("entry.js".to_string(), None)
+      .is_empty());
+  }
+
+  #[test]
+  fn remove_dep() {
+    let key = |file: &str| GRAPH_PATH.join(file).to_str().unwrap().to_string();
+    let mut watcher = WatcherInner::setup(SetupOptions {
+      project: "Graph".to_string(),
+      project_root: GRAPH_PATH.to_str().unwrap().to_string(),
+      entries: Some(vec![key("entry.js")]),
+      ..Default::default()
+    })
+    .unwrap();
+    let b = watcher.store.get(&key("b.js")).unwrap().clone_item();
+    assert_eq!(watcher.get_entries_from_item(&b).len(), 1);
+
+    watcher.remove_dep(&key("a.js"));
+    assert!(watcher.get_importers("b.js", false).is_empty());
+    assert_eq!(watcher.get_importers("c.js", false), vec![key("entry.js")]);
+    assert!(watcher.get_entries_from_item(&b).is_empty());
+    assert_eq!(watcher.entries[0].deps, HashSet::from([key("c.js")]));
+    assert!(!watcher
+      .store
+      .get(&key("entry.js"))
+      .unwrap()
+      .imports
+      .contains(&key("a.js")));
+  }
+
+  #[test]
+  fn make_changes_import_resolved() {
+    let resolution_path = copy_fixture("diagnostics/resolution", "make_changes_import_resolved");
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Resolution".to_string(),
+      project_root: resolution_path.to_str().unwrap().to_string(),
+      entries: Some(vec![resolution_path
+        .join("entry.js")
+        .to_str()
+        .unwrap()
+        .to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    let diagnostics = watcher.get_diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].specifier, "./later");
+    watcher.make_changes();
+
+    let later_path = resolution_path.join("later.js");
+    std::fs::write(&later_path, "export default 'later';").unwrap();
+    let changes = watcher.make_changes();
+
+    let resolved: Vec<&EntryChange> = changes
+      .iter()
+      .filter(|x| x.change_type == EntryChangeType::ImportResolved)
+      .collect();
+    assert_eq!(resolved.len(), 1);
+    assert_eq!(
+      resolved[0].diagnostic.as_ref().unwrap().specifier,
+      "./later"
+    );
+    assert!(watcher.get_diagnostics().is_empty());
+    assert!(watcher.get_entries(None)[0]
+      .deps
+      .contains(later_path.to_str().unwrap()));
+  }
+
+  #[test]
+  fn make_changes_tsconfig_edited() {
+    let project_path = copy_fixture("tsconfig_paths", "make_changes_tsconfig_edited");
+    let main_path = project_path.join("src").join("main.ts");
+    let content = std::fs::read_to_string(&main_path).unwrap();
+    std::fs::write(&main_path, format!("import '@later';\n{}", content)).unwrap();
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Tsconfig".to_string(),
+      project_root: project_path.to_str().unwrap().to_string(),
+      entries: Some(vec![main_path.to_str().unwrap().to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    watcher.make_changes();
+    assert_eq!(watcher.get_diagnostics()[0].specifier, "@later");
+
+    // `@later` is now mapped, the config loaded before has to be dropped
+    let tsconfig_path = project_path.join("tsconfig.json");
+    let tsconfig = std::fs::read_to_string(&tsconfig_path).unwrap();
+    std::fs::write(
+      &tsconfig_path,
+      tsconfig.replace(
+        "\"paths\": {",
+        "\"paths\": {\n      \"@later\": [\"lib/helper.ts\"],",
+      ),
+    )
+    .unwrap();
+    let changes = watcher.make_changes();
+
+    assert!(changes
+      .iter()
+      .any(|x| x.change_type == EntryChangeType::ImportResolved));
+    assert!(watcher.get_diagnostics().is_empty());
+  }
+
+  #[test]
+  fn make_changes_package_installed() {
+    let project_path = copy_fixture("tsconfig_paths", "make_changes_package_installed");
+    let main_path = project_path.join("src").join("main.ts");
+    let content = std::fs::read_to_string(&main_path).unwrap();
+    std::fs::write(&main_path, format!("import 'later';\n{}", content)).unwrap();
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Tsconfig".to_string(),
+      project_root: project_path.to_str().unwrap().to_string(),
+      entries: Some(vec![main_path.to_str().unwrap().to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    watcher.make_changes();
+    assert_eq!(watcher.get_diagnostics()[0].specifier, "later");
+
+    // the missing node_modules folder and package.json looked up before have to be dropped
+    let package_path = project_path.join("node_modules").join("later");
+    std::fs::create_dir_all(&package_path).unwrap();
+    std::fs::write(
+      package_path.join("package.json"),
+      r#"{ "main": "index.js" }"#,
+    )
+    .unwrap();
+    std::fs::write(package_path.join("index.js"), "").unwrap();
+    let changes = watcher.make_changes();
+
+    assert!(changes
+      .iter()
+      .any(|x| x.change_type == EntryChangeType::ImportResolved));
+    assert!(watcher.get_diagnostics().is_empty());
+  }
+
+  #[test]
+  fn make_changes_pnp_manifest_edited() {
+    let project_path = copy_fixture("yarn_pnp", "make_changes_pnp_manifest_edited");
+    let main_path = project_path.join("src").join("main.js");
+    let manifest_path = project_path.join(".pnp.data.json");
+    let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+    std::fs::write(
+      &manifest_path,
+      manifest.replace("[\"left-pad\", \"npm:1.3.0\"],", ""),
+    )
+    .unwrap();
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Yarn PnP".to_string(),
+      project_root: project_path.to_str().unwrap().to_string(),
+      entries: Some(vec![main_path.to_str().unwrap().to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    watcher.make_changes();
+    assert_eq!(watcher.get_diagnostics()[0].specifier, "left-pad");
+
+    // `left-pad` was added by `yarn add`, the manifest loaded before has to be dropped
+    std::fs::write(&manifest_path, manifest).unwrap();
+    let changes = watcher.make_changes();
+
+    assert!(changes
+      .iter()
+      .any(|x| x.change_type == EntryChangeType::ImportResolved));
+    assert!(watcher.get_diagnostics().is_empty());
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn symlinked_project_root() {
+    let (link, real_path) = link_fixture("unreachable", "symlinked_project_root");
+    let watcher = Watcher::setup(SetupOptions {
+      project: "Unreachable".to_string(),
+      project_root: link.to_str().unwrap().to_string(),
+      entries: Some(vec![link.join("main.js").to_str().unwrap().to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    let unreachable = watcher
+      .get_unreachable(vec!["*.js".to_string()], None)
+      .unwrap();
+    assert_eq!(
+      unreachable,
+      vec![
+        real_path.join("helper.js").to_str().unwrap().to_string(),
+        real_path.join("unused.js").to_str().unwrap().to_string(),
+      ]
+    );
+
+    let (link, _) = link_fixture("rules", "symlinked_project_root_rules");
+    let watcher = Watcher::setup(SetupOptions {
+      project: "Rules".to_string(),
+      project_root: link.to_str().unwrap().to_string(),
+      entries: Some(vec![link
+        .join("src/app/main.js")
+        .to_str()
+        .unwrap()
+        .to_string()]),
+      rules: Some(vec![BoundaryRule {
+        from: "src/features/**".to_string(),
+        deny: Some(vec!["src/app/**".to_string()]),
+        ..Default::default()
+      }]),
+      ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(watcher.check_rules().len(), 1);
+  }
+
+  #[test]
+  fn make_changes_rule_violation_moved() {
+    let rules_path = copy_fixture("rules", "make_changes_rule_violation_moved");
+    let main_path = rules_path.join("src").join("app").join("main.js");
+    let cart_path = rules_path
+      .join("src")
+      .join("features")
+      .join("cart")
+      .join("index.js");
+    let mut watcher = Watcher::setup(SetupOptions {
+      project: "Rules".to_string(),
+      project_root: rules_path.to_str().unwrap().to_string(),
+      entries: Some(vec![
+        main_path.to_str().unwrap().to_string(),
+        cart_path.to_str().unwrap().to_string(),
+      ]),
+      rules: Some(vec![BoundaryRule {
+        from: "src/features/**".to_string(),
+        deny: Some(vec!["src/app/**".to_string()]),
+        ..Default::default()
+      }]),
+      ..Default::default()
+    })
+    .unwrap();
+    watcher.make_changes();
+    assert_eq!(watcher.check_rules().len(), 1);
+
+    // the import of the store moves down a line, it's still the same violation
+    let content = std::fs::read_to_string(&cart_path).unwrap();
+    std::fs::write(&cart_path, format!("// cart\n{}", content)).unwrap();
+    let changes = watcher.make_changes();
+
+    assert!(!changes
+      .iter()
+      .any(|x| x.change_type == EntryChangeType::RuleViolated));
+    assert_eq!(watcher.check_rules()[0].span.unwrap().line, 3);
+  }
+
+  #[test]
+  fn setup_invalid_rules() {
+    let res = Watcher::setup(SetupOptions {
+      project: "Rules".to_string(),
+      project_root: CWD.to_str().unwrap().to_string(),
+      rules: Some(vec![BoundaryRule {
+        from: "src/[features".to_string(),
+        ..Default::default()
+      }]),
+      ..Default::default()
+    });
+    assert_eq!(
+      res.err().unwrap().reason,
+      "Invalid rules: invalid glob `src/[features` in the rule of `src/[features`: invalid range pattern"
+    );
+  }
+
   #[test]
   fn make_changes_three_js() {
     let mut watcher = Watcher::setup(SetupOptions {
@@ -554,7 +2114,9 @@ mod tests {
       cache_dir: None,
       supported_paths: None,
       debug: None,
-    });
+      ..Default::default()
+    })
+    .unwrap();
 
     let duration = std::time::Instant::now();
     watcher.make_changes();
@@ -578,7 +2140,9 @@ mod tests {
       cache_dir: None,
       supported_paths: None,
       debug: None,
-    });
+      ..Default::default()
+    })
+    .unwrap();
 
     // First call, we expect to detect two changes of type added
     if std::path::Path::new(&watcher.cache_dir()).exists() {
@@ -656,7 +2220,9 @@ mod tests {
       cache_dir: None,
       supported_paths: None,
       debug: None,
-    });
+      ..Default::default()
+    })
+    .unwrap();
     assert_eq!(watcher.processed(), true);
 
     let called = Arc::new(AtomicBool::new(false)).clone();
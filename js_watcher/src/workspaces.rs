@@ -0,0 +1,157 @@
+use glob::{glob, Pattern};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::resolve_cache::ResolveCache;
+
+/// Finds the closest workspace root from `dir`, walking up the parents until `project_path`,
+/// and maps the name of each of its packages to their directory.
+/// Workspaces are read from `pnpm-workspace.yaml`, or from the `workspaces` field of
+/// `package.json`.
+pub fn find_workspace_packages(
+  dir: &Path,
+  project_path: &Path,
+  cache: &ResolveCache,
+) -> Option<Arc<HashMap<String, PathBuf>>> {
+  if !dir.starts_with(project_path) {
+    return None;
+  }
+  cache
+    .workspace_packages
+    .get_or_insert_with(dir, || match read_workspace_patterns(dir) {
+      Some(patterns) => Some(Arc::new(expand_workspace_patterns(dir, &patterns))),
+      None => find_workspace_packages(dir.parent()?, project_path, cache),
+    })
+}
+
+fn read_workspace_patterns(dir: &Path) -> Option<Vec<String>> {
+  if let Ok(content) = std::fs::read_to_string(dir.join("pnpm-workspace.yaml")) {
+    return Some(parse_pnpm_workspace(&content));
+  }
+  let content = std::fs::read(dir.join("package.json")).ok()?;
+  let json: serde_json::Value = serde_json::from_slice(&content).ok()?;
+  // "workspaces": [...] or "workspaces": { "packages": [...] }
+  let workspaces = match &json["workspaces"] {
+    serde_json::Value::Object(workspaces) => workspaces.get("packages")?,
+    workspaces => workspaces,
+  };
+  Some(
+    workspaces
+      .as_array()?
+      .iter()
+      .filter_map(|x| x.as_str().map(String::from))
+      .collect(),
+  )
+}
+
+/// Reads the `packages` list of a `pnpm-workspace.yaml`. Only the block sequence form is
+/// supported, which is the one pnpm writes:
+/// ```yaml
+/// packages:
+///   - 'packages/*'
+///   - '!**/test/**'
+/// ```
+fn parse_pnpm_workspace(content: &str) -> Vec<String> {
+  let mut patterns = Vec::new();
+  let mut in_packages = false;
+  for line in content.lines() {
+    let line = match line.find(" #") {
+      Some(index) => &line[..index],
+      None => line,
+    };
+    if line.trim().is_empty() || line.trim_start().starts_with('#') {
+      continue;
+    }
+    if !line.starts_with(' ') && !line.starts_with('-') {
+      in_packages = line.trim_end() == "packages:";
+      continue;
+    }
+    if let Some(item) = line.trim().strip_prefix('-').filter(|_| in_packages) {
+      patterns.push(
+        item
+          .trim()
+          .trim_matches(|c| c == '\'' || c == '"')
+          .to_string(),
+      );
+    }
+  }
+  patterns
+}
+
+/// Lists the packages matching `patterns`, relative to the workspace root `dir`.
+/// Patterns starting with `!` exclude packages.
+fn expand_workspace_patterns(dir: &Path, patterns: &[String]) -> HashMap<String, PathBuf> {
+  let exclusions: Vec<Pattern> = patterns
+    .iter()
+    .filter_map(|x| x.strip_prefix('!'))
+    .filter_map(|x| Pattern::new(dir.join(x.trim_end_matches('/')).to_str()?).ok())
+    .collect();
+
+  let mut packages = HashMap::new();
+  for pattern in patterns.iter().filter(|x| !x.starts_with('!')) {
+    let pattern = dir.join(pattern.trim_end_matches('/')).join("package.json");
+    let paths = match glob(pattern.to_str().unwrap()) {
+      Ok(paths) => paths,
+      Err(_) => continue,
+    };
+    for pkg_path in paths.flatten() {
+      let pkg_dir = pkg_path.parent().unwrap();
+      if pkg_dir
+        .components()
+        .any(|x| x.as_os_str() == "node_modules")
+        || exclusions.iter().any(|x| x.matches_path(pkg_dir))
+      {
+        continue;
+      }
+      let name = std::fs::read(&pkg_path)
+        .ok()
+        .and_then(|x| serde_json::from_slice::<serde_json::Value>(&x).ok())
+        .and_then(|x| x["name"].as_str().map(String::from));
+      if let Some(name) = name {
+        packages
+          .entry(name)
+          .or_insert_with(|| pkg_dir.to_path_buf());
+      }
+    }
+  }
+  packages
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{find_workspace_packages, parse_pnpm_workspace};
+  use crate::resolve_cache::ResolveCache;
+  use lazy_static::lazy_static;
+  use std::path::PathBuf;
+
+  lazy_static! {
+    static ref CWD: PathBuf = std::env::current_dir().unwrap();
+    static ref WORKSPACES_PATH: PathBuf = CWD.join("tests/fixtures/workspaces");
+  }
+
+  #[test]
+  fn test_parse_pnpm_workspace() {
+    let content = "packages:\n  # all packages\n  - 'packages/*'\n  - \"apps/**\" # apps\n  - '!**/test/**'\n\ncatalog:\n  - react\n";
+    assert_eq!(
+      parse_pnpm_workspace(content),
+      vec!["packages/*", "apps/**", "!**/test/**"]
+    );
+  }
+
+  #[test]
+  fn test_find_workspace_packages() {
+    let cache = ResolveCache::default();
+    let dir = WORKSPACES_PATH.join("packages/ui/src");
+    let packages = find_workspace_packages(&dir, &WORKSPACES_PATH, &cache).unwrap();
+    let mut names: Vec<&String> = packages.keys().collect();
+    names.sort();
+    assert_eq!(names, vec!["@acme/ui", "@acme/utils"]);
+    assert_eq!(packages["@acme/ui"], WORKSPACES_PATH.join("packages/ui"));
+
+    // the workspace root is above the project
+    let project_path = WORKSPACES_PATH.join("packages/ui");
+    let cache = ResolveCache::default();
+    assert_eq!(find_workspace_packages(&dir, &project_path, &cache), None);
+  }
+}
@@ -0,0 +1 @@
+export const C = 1;
@@ -0,0 +1,6 @@
+import { B } from '@app/b';
+import Vue from 'vue';
+import 'styles/theme';
+import { C } from '~/lib/c';
+
+console.log(B, Vue, C);
@@ -0,0 +1,3 @@
+import universal from 'universal';
+import { Buffer } from 'buffer';
+import path from 'path';
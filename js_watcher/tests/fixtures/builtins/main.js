@@ -0,0 +1,6 @@
+import fs from 'fs';
+import { readFile } from 'fs/promises';
+import crypto from 'node:crypto';
+import test from 'node:test';
+const path = require('path');
+import { helper } from './helper.js';
@@ -0,0 +1,3 @@
+import x from './x.js';
+
+export default x;
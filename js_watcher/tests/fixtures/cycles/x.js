@@ -0,0 +1,3 @@
+import y from './y.js';
+
+export default y;
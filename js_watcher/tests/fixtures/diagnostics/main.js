@@ -0,0 +1,6 @@
+import b from './b.js';
+import missing from './missing';
+import notInstalled from 'not-installed';
+import c from './c.js';
+
+console.log(b, missing, notInstalled, c);
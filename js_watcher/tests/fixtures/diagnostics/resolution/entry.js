@@ -0,0 +1,3 @@
+import later from './later';
+
+console.log(later);
@@ -0,0 +1,5 @@
+import Button from './Button';
+import utils from './utils';
+import c from './c';
+
+console.log(Button, utils, c);
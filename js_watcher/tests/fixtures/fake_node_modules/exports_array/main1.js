@@ -0,0 +1 @@
+module.exports = 'exports_array/main1.js';
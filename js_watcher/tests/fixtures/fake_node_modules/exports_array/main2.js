@@ -0,0 +1 @@
+module.exports = 'exports_array/main2.js';
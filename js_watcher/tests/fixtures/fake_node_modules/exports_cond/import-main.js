@@ -0,0 +1 @@
+module.exports = 'exports_cond/import-main.js';
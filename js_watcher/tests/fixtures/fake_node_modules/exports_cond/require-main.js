@@ -0,0 +1 @@
+module.exports = 'exports_cond/require-main.js';
@@ -0,0 +1 @@
+module.exports = 'exports_cond_default/main.js';
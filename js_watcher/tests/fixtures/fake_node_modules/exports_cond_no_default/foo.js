@@ -0,0 +1 @@
+module.exports = 'exports_cond_no_default/foo.js';
@@ -0,0 +1 @@
+module.exports = 'exports_obj/main.js';
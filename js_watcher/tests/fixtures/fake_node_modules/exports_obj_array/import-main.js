@@ -0,0 +1 @@
+module.exports = 'exports_obj_array/import-main.js';
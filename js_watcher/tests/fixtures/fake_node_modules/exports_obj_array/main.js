@@ -0,0 +1 @@
+module.exports = 'exports_obj_array/main.js';
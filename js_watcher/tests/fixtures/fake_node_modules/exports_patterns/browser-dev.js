@@ -0,0 +1 @@
+module.exports = 'exports_patterns/browser-dev.js';
@@ -0,0 +1 @@
+module.exports = 'exports_patterns/src/features/internal/x.js';
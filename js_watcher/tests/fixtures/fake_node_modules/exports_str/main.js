@@ -0,0 +1 @@
+module.exports = 'exports_str/main.js';
@@ -0,0 +1 @@
+module.exports = 'main/main.js';
@@ -0,0 +1 @@
+module.exports = 'nested/a.js';
@@ -0,0 +1 @@
+module.exports = 'nested/b/b.js';
@@ -0,0 +1 @@
+module.exports = 'nested/c.js';
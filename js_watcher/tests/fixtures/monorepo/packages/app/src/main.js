@@ -0,0 +1,5 @@
+import shared from 'shared';
+import outer from 'outer';
+import localOnly from 'local-only';
+
+console.log(shared, outer, localOnly);
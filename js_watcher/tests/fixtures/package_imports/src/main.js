@@ -0,0 +1,5 @@
+import { log } from '#utils/log';
+import config from '#config';
+import dep from '#dep';
+
+log(config, dep);
@@ -0,0 +1,3 @@
+const foo = require('foo');
+
+module.exports = foo;
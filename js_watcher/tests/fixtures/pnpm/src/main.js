@@ -0,0 +1,5 @@
+import foo from 'foo';
+import ui from '@acme/ui';
+import loop from './loop.js';
+
+console.log(foo, ui, loop);
@@ -0,0 +1,4 @@
+import { addToCart } from '../features/cart/index.js';
+import { search } from '../features/search/index.js';
+
+addToCart(search('shoes'));
@@ -0,0 +1,6 @@
+import { exec } from 'child_process';
+import { store } from '../../app/store.js';
+import { format } from '../../shared/format.js';
+import { Button } from '../../../packages/ui/button.js';
+
+export const addToCart = (item) => [exec, store, format(item), Button];
@@ -0,0 +1,3 @@
+import { format } from '../../shared/format.js';
+
+export const search = (query) => format(query);
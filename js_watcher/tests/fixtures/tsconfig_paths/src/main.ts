@@ -0,0 +1,5 @@
+import { Button } from '@app/components/Button';
+import { log } from '@utils';
+import { helper } from 'lib/helper';
+
+log(Button, helper);
@@ -0,0 +1,4 @@
+import helper from '../helper.js';
+import used from '../used.js';
+
+console.log(helper, used);
@@ -0,0 +1,3 @@
+import used from './used.js';
+
+export default used;
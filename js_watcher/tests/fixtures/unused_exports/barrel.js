@@ -0,0 +1,2 @@
+export * from './inner.js';
+export { x as y } from './inner.js';
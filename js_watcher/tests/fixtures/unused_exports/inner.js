@@ -0,0 +1,3 @@
+export const viaBarrel = 1;
+export const x = 2;
+export const z = 3;
@@ -0,0 +1,4 @@
+export const used = 1;
+export const unused = 2;
+export function renamed() {}
+export default function () {}
@@ -0,0 +1,10 @@
+import { used, renamed as r } from './lib.js';
+import * as all from './all.js';
+import { viaBarrel } from './barrel.js';
+
+export const version = 1;
+
+export async function load() {
+  const { lazy } = await import('./lazy.js');
+  return [used, r, all, viaBarrel, lazy];
+}
@@ -0,0 +1,4 @@
+import React from 'https://esm.sh/react@18.2.0';
+import { assert } from "http://deno.land/std/assert/mod.ts";
+import answer from 'data:text/javascript,export default 42';
+import { local } from './local.ts';
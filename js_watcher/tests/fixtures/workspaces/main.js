@@ -0,0 +1,2 @@
+import { Button } from '@acme/ui';
+import { noop } from '@acme/ignored';
@@ -0,0 +1,2 @@
+export { Button } from '@acme/ui/button';
+export { noop } from '@acme/utils';
@@ -0,0 +1,4 @@
+import utils from '@acme/utils';
+import leftPad from 'left-pad';
+
+console.log(leftPad(utils(), 4));